@@ -2,6 +2,7 @@ use std::sync::Arc;
 use std::thread::JoinHandle;
 
 use bioimg_runtime::zip_archive_ext::SharedZipArchive;
+use bioimg_runtime::zip_writer_ext::CompressionOptions;
 use bioimg_spec::rdf::model::model_rdf_0_5::PartialModelRdfV0_5;
 use bioimg_spec::rdf::model::ModelRdfName;
 use bioimg_zoo::collection::ZooNickname;
@@ -33,6 +34,8 @@ use crate::widgets::search_and_pick_widget::SearchAndPickWidget;
 use crate::widgets::staging_opt::StagingOpt;
 use crate::widgets::staging_string::{InputLines, StagingString};
 use crate::widgets::staging_vec::StagingVec;
+use crate::widgets::training_data_widget::TrainingDataWidget;
+use crate::widgets::packing_progress_widget::PackingProgressWidget;
 use crate::widgets::util::{widget_vec_from_values, TaskChannel, VecItemRender, VecWidget};
 use crate::widgets::version_widget::VersionWidget;
 use crate::widgets::weights_widget::WeightsWidget;
@@ -56,6 +59,8 @@ pub enum TaskResult{
     Notification(Result<String, String>),
     ModelImport(Box<rt::zoo_model::ZooModel>),
     PartialModelLoad(AppStateFromPartial),
+    ModelPackingStarted(PackingProgressWidget),
+    ModelPackingFinished(Result<String, String>),
 }
 
 impl TaskResult{
@@ -67,6 +72,25 @@ impl TaskResult{
     }
 }
 
+/// How hard to try to shrink exported model packages
+#[derive(Default, Copy, Clone, PartialEq, Eq)]
+pub enum ExportCompression{
+    None,
+    #[default]
+    Standard,
+    Smallest,
+}
+
+impl ExportCompression{
+    pub fn options(self) -> CompressionOptions{
+        match self{
+            Self::None => CompressionOptions::stored(),
+            Self::Standard => CompressionOptions::default(),
+            Self::Smallest => CompressionOptions::smallest(),
+        }
+    }
+}
+
 #[derive(Default, Copy, Clone)]
 enum ExitingStatus{
     #[default]
@@ -96,6 +120,7 @@ pub struct AppState1 {
 
     pub staging_documentation: CodeEditorWidget<MarkdwownLang>,
     pub staging_license: SearchAndPickWidget<rdf::LicenseId>,
+    pub training_data_widget: StagingOpt<TrainingDataWidget, false>,
     //badges
     pub model_interface_widget: ModelInterfaceWidget,
     ////
@@ -113,6 +138,12 @@ pub struct AppState1 {
     pub zoo_login_widget: ZooLoginWidget,
     #[restore(default)]
     pub zoo_model_creation_task: Option<JoinHandle<Result<ZooNickname>>>,
+    #[restore(default)]
+    pub packing_progress_widget: Option<PackingProgressWidget>,
+    #[restore(default)]
+    pub export_compression: ExportCompression,
+    #[restore(default)]
+    pub deterministic_export: bool,
 
     #[restore(default)]
     pub notifications_widget: NotificationsWidget,
@@ -172,6 +203,7 @@ impl ValueWidget for AppState1{
         self.staging_version.set_value(zoo_model.version);
         self.staging_documentation.set_value(&zoo_model.documentation);
         self.staging_license.set_value(zoo_model.license);
+        self.training_data_widget.set_value(zoo_model.training_data);
 
         self.model_interface_widget.set_value(zoo_model.interface);
 
@@ -199,6 +231,7 @@ impl Default for AppState1 {
             staging_version_comment: Default::default(),
             staging_documentation: Default::default(),
             staging_license: SearchAndPickWidget::from_enum(Default::default()),
+            training_data_widget: Default::default(),
 
             model_interface_widget: Default::default(),
 
@@ -208,6 +241,9 @@ impl Default for AppState1 {
             #[cfg(not(target_arch="wasm32"))]
             zoo_login_widget: Default::default(),
             zoo_model_creation_task: Default::default(),
+            packing_progress_widget: None,
+            export_compression: Default::default(),
+            deterministic_export: false,
             pipeline_widget: Default::default(),
 
             exiting_status: Default::default(),
@@ -297,6 +333,9 @@ impl AppState1{
             .cloned();
         let documentation = self.staging_documentation.state().to_owned();
         let license = self.staging_license.state();
+        let training_data = self.training_data_widget.state()
+            .transpose()
+            .map_err(|e| GuiError::new_with_rect("Check training data for errors", e.failed_widget_rect))?;
         let model_interface = self.model_interface_widget.get_value()
             .map_err(|_| GuiError::new("Check model interface for errors"))?;
         let weights = self.weights_widget.get_value()
@@ -321,8 +360,10 @@ impl AppState1{
             documentation,
             license,
             id: model_id,
+            training_data,
             weights,
             interface: model_interface,
+            lossy_conversions: vec![],
         })
     }
 
@@ -357,6 +398,8 @@ impl AppState1{
     }
     fn launch_model_saving(&mut self, zoo_model: ZooModel) {
         let sender = self.notifications_channel.sender().clone();
+        let compression = self.export_compression.options();
+        let deterministic = self.deterministic_export;
         let fut = async move {
             let model_name = format!("{}.zip", zoo_model.name);
             let Some(file_handle) = rfd::AsyncFileDialog::new().set_file_name(model_name).save_file().await else {
@@ -367,7 +410,14 @@ impl AppState1{
             let message = 'packing_wasm: {
                 let mut buffer = Vec::<u8>::new(); //FIXME: check FileSystemWritableFileStream: seek() 
                 let cursor = std::io::Cursor::new(&mut buffer);
-                if let Err(err) = zoo_model.pack_into(cursor) {
+                let fetcher = rt::url_fetcher::BrowserFetcher::default();
+                if let Err(err) = fetcher.prefetch(zoo_model.remote_urls()).await {
+                    break 'packing_wasm TaskResult::err_message(format!("Error saving model: {err}"));
+                }
+                let options = rt::zip_writer_ext::PackingOptions{
+                    compression, deterministic, fetcher: Box::new(fetcher), ..Default::default()
+                };
+                if let Err(err) = zoo_model.pack_into_with(cursor, options) {
                     let msg = TaskResult::err_message(format!("Error saving model: {err:?}"));
                     break 'packing_wasm msg;
                 };
@@ -378,8 +428,10 @@ impl AppState1{
             };
 
             #[cfg(not(target_arch="wasm32"))]
-            let message = 'packing: {
-                use std::borrow::Cow;
+            let message = {
+                use rt::url_fetcher::{CancellationToken, FetchOptions, UreqFetcher};
+                use rt::zip_writer_ext::PackingOptions;
+                use rt::zoo_model::ModelPackingError;
 
                 let file_name = file_handle.file_name();
                 if !file_name.ends_with(".zip"){
@@ -387,35 +439,22 @@ impl AppState1{
                     sender.send(msg).unwrap();
                     return
                 }
-                let notification_message = format!("Packing into {file_name}...");
-                sender.send(TaskResult::ok_message(notification_message)).unwrap();
-
-                let temp_path = {
-                    let current_extension = file_handle.path().extension().map(|s| s.to_string_lossy()).unwrap_or(Cow::Borrowed(""));
-                    let temp_extension = format!("{current_extension}.partial");
-                    let mut temp_path = file_handle.path().to_owned();
-                    temp_path.set_extension(temp_extension);
-                    temp_path
-                };
-
-                let file = match std::fs::File::create(&temp_path){
-                    Ok(file) => file,
-                    Err(err) => {
-                        break 'packing TaskResult::err_message(format!("Could not create zip file: {err}"));
-                    }
+                let cancellation = CancellationToken::new();
+                let progress_widget = PackingProgressWidget::new(file_name.clone(), cancellation.clone());
+                let options = PackingOptions{
+                    fetcher: Box::new(UreqFetcher::new(FetchOptions{cancellation: cancellation.clone(), ..Default::default()})),
+                    observer: Some(progress_widget.observer()),
+                    cancellation,
+                    compression,
+                    deterministic,
                 };
+                sender.send(TaskResult::ModelPackingStarted(progress_widget)).unwrap();
 
-                if let Err(err) = zoo_model.pack_into(file){
-                    break 'packing TaskResult::err_message(format!("Error saving model: {err}"));
-                }
-                if let Err(err) = std::fs::rename(&temp_path, file_handle.path()) {
-                    if let Err(rm_err) = std::fs::remove_file(&temp_path){
-                        let msg = format!("Could not delete temp file {}: {rm_err}", temp_path.to_string_lossy());
-                        sender.send(TaskResult::err_message(msg)).unwrap();
-                    }
-                    break 'packing TaskResult::err_message(format!("Error saving model: {err}"))
-                }
-                TaskResult::ok_message(format!("Model saved to {file_name}"))
+                TaskResult::ModelPackingFinished(match zoo_model.pack_into_path(file_handle.path(), options){
+                    Ok(()) => Ok(format!("Model saved to {file_name}")),
+                    Err(ModelPackingError::Cancelled) => Ok(format!("Cancelled saving model to {file_name}")),
+                    Err(err) => Err(format!("Error saving model: {err}")),
+                })
             };
 
             sender.send(message).unwrap();
@@ -617,11 +656,27 @@ impl eframe::App for AppState1 {
             while let Ok(msg) = self.notifications_channel.receiver().try_recv(){
                 match msg{
                     TaskResult::Notification(msg) => self.notifications_widget.push(msg.into()),
-                    TaskResult::ModelImport(model) => self.set_value(*model),
+                    TaskResult::ModelImport(model) => {
+                        if !model.lossy_conversions.is_empty(){
+                            let mut warnings = String::from("Model was converted to the latest supported format. Some fields could not be carried over exactly:");
+                            for conversion in &model.lossy_conversions{
+                                warnings += &format!("\n{conversion}");
+                            }
+                            self.notifications_widget.push(Notification::warning(warnings, None));
+                        }
+                        self.set_value(*model)
+                    },
                     TaskResult::PartialModelLoad(AppStateFromPartial{state, warnings}) => {
                         self.restore(state);
                         self.notifications_widget.push(Notification::warning(warnings, None));
                     }
+                    TaskResult::ModelPackingStarted(progress_widget) => {
+                        self.packing_progress_widget = Some(progress_widget);
+                    }
+                    TaskResult::ModelPackingFinished(msg) => {
+                        self.packing_progress_widget = None;
+                        self.notifications_widget.push(msg.into());
+                    }
                 }
             }
             if let Some(error_rect) = self.notifications_widget.draw(ui, egui::Id::from("messages_widget")){
@@ -856,6 +911,14 @@ impl eframe::App for AppState1 {
                     ui.strong("License: ").on_hover_text("A standard software licence, specifying how this model can be used and for what purposes.");
                     self.staging_license.draw_and_parse(ui, egui::Id::from("License"));
                 });
+
+                ui.horizontal(|ui| {
+                    ui.strong("Training Data: ").on_hover_text(indoc!("
+                        The dataset used to train this model. Prefer referencing a dataset from the \
+                        bioimage.io collection by its id when possible."
+                    ));
+                    self.training_data_widget.draw_and_parse(ui, egui::Id::from("Training Data"));
+                });
                 ui.add_space(20.0);
 
 
@@ -881,9 +944,25 @@ impl eframe::App for AppState1 {
 
                 ui.separator();
 
-                let save_button_clicked = ui.button("Export Model ⤵📦")
-                    .on_hover_text("Exports this model to a .zip file, ready to be used or uploaded to the Model Zoo")
-                    .clicked();
+                let save_button_clicked = ui.horizontal(|ui|{
+                    let clicked = ui.add_enabled(self.packing_progress_widget.is_none(), egui::Button::new("Export Model ⤵📦"))
+                        .on_hover_text("Exports this model to a .zip file, ready to be used or uploaded to the Model Zoo")
+                        .clicked();
+                    ui.strong("Compression: ").on_hover_text(
+                        "Compressing makes packages with large test tensors much smaller, but exporting takes longer"
+                    );
+                    ui.radio_value(&mut self.export_compression, ExportCompression::None, "None");
+                    ui.radio_value(&mut self.export_compression, ExportCompression::Standard, "Standard")
+                        .on_hover_text("Compresses text files and test tensors, but not weights or images");
+                    ui.radio_value(&mut self.export_compression, ExportCompression::Smallest, "Smallest");
+                    ui.checkbox(&mut self.deterministic_export, "Reproducible").on_hover_text(
+                        "Names files inside the package by their role and uses fixed timestamps, so that exporting the same model twice produces identical files"
+                    );
+                    clicked
+                }).inner;
+                if let Some(progress_widget) = &self.packing_progress_widget{
+                    progress_widget.draw(ui);
+                }
 
                 if save_button_clicked {
                     match self.create_model(){
@@ -19,6 +19,7 @@ use bioimg_spec::rdf::cite_entry::PartialCiteEntry2Msg;
 use bioimg_spec::rdf::file_description::PartialFileDescription;
 use bioimg_spec::rdf::maintainer::PartialMaintainer;
 use bioimg_spec::rdf::model::{self as modelrdf, AxisType};
+use bioimg_spec::rdf::model::tensor_data_descr::AnyTensorDataDescr;
 use bioimg_spec::util::PartialSingleOrMultiple;
 use crate::widgets::author_widget::AuthorWidget;
 use crate::widgets::onnx_weights_widget::OnnxWeightsWidget;
@@ -123,6 +124,33 @@ impl TestTensorWidgetSavedData {
     }
 }
 
+#[derive(serde::Serialize, serde::Deserialize, Default)]
+pub enum SampleTensorWidgetSavedData{
+    #[default]
+    Empty,
+    Loaded{path: Option<PathBuf>, data: Vec<u8>},
+}
+
+impl SampleTensorWidgetSavedData {
+    pub fn from_partial<W: std::fmt::Write>(
+        archive: &SharedZipArchive,
+        partial: Partial<rdf::FileDescription>,
+        mut warnings: W
+    ) -> Self {
+        let Some(source) = partial.source else {
+            return Self::Empty
+        };
+        let data = match archive.read_full_entry(&source){
+            Ok(bytes) => bytes,
+            Err(e) => {
+                _ = writeln!(warnings, "Could not read sample tensor bytes at '{source}': {e}");
+                return Self::Empty;
+            }
+        };
+        Self::Loaded{path: Some(source.into()), data}
+    }
+}
+
 #[derive(serde::Serialize, serde::Deserialize, Default)]
 pub enum LocalFileSourceWidgetSavedData{
     #[default]
@@ -607,6 +635,10 @@ impl InputAxisWidgetSavedData {
 pub struct WeightsDescrBaseWidgetSavedData{
     pub source_widget: FileSourceWidgetSavedData,
     pub authors_widget: Option<Vec<CollapsibleWidgetSavedData<AuthorWidget>>>,
+    #[serde(default)]
+    pub parent_widget: Option<modelrdf::WeightsFormat>,
+    #[serde(default)]
+    pub comment_widget: Option<String>,
 }
 
 impl WeightsDescrBaseWidgetSavedData {
@@ -626,7 +658,21 @@ impl WeightsDescrBaseWidgetSavedData {
                 })
                 .collect::<Vec<_>>()
         });
-        Self{source_widget: source, authors_widget: authors}
+        let parent = partial.parent.flatten().and_then(|raw_parent| {
+            match raw_parent.parse::<modelrdf::WeightsFormat>(){
+                Ok(parent) => Some(parent),
+                Err(_) => {
+                    _ = writeln!(warnings, "Unrecognized weights parent: '{raw_parent}'");
+                    None
+                }
+            }
+        });
+        Self{
+            source_widget: source,
+            authors_widget: authors,
+            parent_widget: parent,
+            comment_widget: partial.comment.unwrap_or(None),
+        }
     }
 }
 
@@ -660,6 +706,17 @@ impl JsonObjectEditorWidgetSavedData {
     }
 }
 
+#[derive(serde::Serialize, serde::Deserialize, Default)]
+pub struct TensorDataDescrWidgetSavedData{
+    pub code_editor_widget: CodeEditorWidgetSavedData,
+}
+
+impl TensorDataDescrWidgetSavedData {
+    pub fn from_partial(_archive: &SharedZipArchive, partial: AnyTensorDataDescr) -> Self {
+        Self{code_editor_widget: CodeEditorWidgetSavedData{raw: serde_json::to_string_pretty(&partial).unwrap()}}
+    }
+}
+
 #[derive(serde::Serialize, serde::Deserialize, Default)]
 pub struct CondaEnvEditorWidgetSavedData{
     pub code_editor_widget: CodeEditorWidgetSavedData,
@@ -878,7 +935,11 @@ pub struct InputTensorWidgetSavedData {
     pub description_widget: String,
     pub axis_widgets: Vec<InputAxisWidgetSavedData>,
     pub test_tensor_widget: TestTensorWidgetSavedData,
+    #[serde(default)]
+    pub sample_tensor_widget: SampleTensorWidgetSavedData,
     pub preprocessing_widget: Vec<PreprocessingWidgetSavedData>,
+    #[serde(default)]
+    pub data_widget: TensorDataDescrWidgetSavedData,
 }
 
 impl InputTensorWidgetSavedData {
@@ -892,6 +953,7 @@ impl InputTensorWidgetSavedData {
         let mut description_widget = String::new();
         let mut axis_widgets = Vec::<InputAxisWidgetSavedData>::new();
         let mut preprocessing_widget = Vec::<PreprocessingWidgetSavedData>::new();
+        let mut data_widget = TensorDataDescrWidgetSavedData::default();
         
         if let Some(meta) = partial.meta {
             if let Some(id) = meta.id {
@@ -911,12 +973,22 @@ impl InputTensorWidgetSavedData {
                     preprocessing_widget.push(PreprocessingWidgetSavedData::from_partial(archive, partial_preproc));
                 }
             }
+            if let Some(data) = meta.data.flatten() {
+                data_widget = TensorDataDescrWidgetSavedData::from_partial(archive, data);
+            }
         }
         let test_tensor_widget = partial.test_tensor
-            .map(|tt| TestTensorWidgetSavedData::from_partial(archive, tt, warnings))
+            .map(|tt| TestTensorWidgetSavedData::from_partial(archive, tt, &mut *warnings))
+            .unwrap_or_default();
+        let sample_tensor_widget = partial.sample_tensor
+            .flatten()
+            .map(|st| SampleTensorWidgetSavedData::from_partial(archive, st, &mut *warnings))
             .unwrap_or_default();
 
-        Self{id_widget, is_optional, description_widget, axis_widgets, test_tensor_widget, preprocessing_widget}
+        Self{
+            id_widget, is_optional, description_widget, axis_widgets, test_tensor_widget, sample_tensor_widget, preprocessing_widget,
+            data_widget,
+        }
     }
 }
 
@@ -1622,7 +1694,11 @@ pub struct OutputTensorWidgetSavedData {
     pub description_widget: String,
     pub axis_widgets: Vec<OutputAxisWidgetSavedData>,
     pub test_tensor_widget: TestTensorWidgetSavedData,
+    #[serde(default)]
+    pub sample_tensor_widget: SampleTensorWidgetSavedData,
     pub postprocessing_widgets: Vec<CollapsibleWidgetSavedData<PostprocessingWidget>>,
+    #[serde(default)]
+    pub data_widget: TensorDataDescrWidgetSavedData,
 }
 
 impl OutputTensorWidgetSavedData {
@@ -1635,6 +1711,7 @@ impl OutputTensorWidgetSavedData {
         let mut description_widget = String::new();
         let mut axis_widgets = Vec::<OutputAxisWidgetSavedData>::new();
         let mut postprocessing_widgets = Vec::<CollapsibleWidgetSavedData<PostprocessingWidget>>::new();
+        let mut data_widget = TensorDataDescrWidgetSavedData::default();
         
         if let Some(meta) = partial.metadata {
             if let Some(id) = meta.id {
@@ -1656,12 +1733,18 @@ impl OutputTensorWidgetSavedData {
                     postprocessing_widgets.push(widget);
                 }
             }
+            if let Some(data) = meta.data.flatten() {
+                data_widget = TensorDataDescrWidgetSavedData::from_partial(archive, data);
+            }
         }
         let test_tensor_widget = partial.test_tensor
-            .map(|tt| TestTensorWidgetSavedData::from_partial(archive, tt, warnings))
+            .map(|tt| TestTensorWidgetSavedData::from_partial(archive, tt, &mut *warnings))
+            .unwrap_or_default();
+        let sample_tensor_widget = partial.sample_tensor
+            .map(|st| SampleTensorWidgetSavedData::from_partial(archive, st, &mut *warnings))
             .unwrap_or_default();
 
-        Self{id_widget, description_widget, axis_widgets, test_tensor_widget, postprocessing_widgets}
+        Self{id_widget, description_widget, axis_widgets, test_tensor_widget, sample_tensor_widget, postprocessing_widgets, data_widget}
     }
 }
 
@@ -1745,6 +1828,53 @@ impl AppStateSavedData{
     }
 }
 
+#[derive(serde::Serialize, serde::Deserialize, Default)]
+pub enum TrainingDataWidgetModeSavedData{
+    #[default]
+    Linked,
+    Inline,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Default)]
+pub struct TrainingDataWidgetSavedData{
+    pub mode: TrainingDataWidgetModeSavedData,
+    pub dataset_id_widget: String,
+    #[serde(default)]
+    pub version_number_widget: Option<u64>,
+    pub source_widget: Option<String>,
+    #[serde(default)]
+    pub extra_fields_widget: JsonObjectEditorWidgetSavedData,
+}
+
+impl TrainingDataWidgetSavedData{
+    pub fn from_partial(archive: &SharedZipArchive, partial: Partial<modelrdf::dataset_descr::DatasetDescrEnum>) -> Self{
+        use modelrdf::dataset_descr::PartialLinkedDatasetDescr;
+        if let Some(PartialLinkedDatasetDescr{id: Some(dataset_id), version_number}) = partial.linked_dataset_descr {
+            return Self{
+                mode: TrainingDataWidgetModeSavedData::Linked,
+                dataset_id_widget: dataset_id,
+                version_number_widget: version_number.flatten(),
+                source_widget: None,
+                extra_fields_widget: Default::default(),
+            }
+        }
+        let (source_widget, extra_fields_widget) = match partial.dataset_descr {
+            Some(descr) => (
+                descr.source,
+                descr.extra.map(|extra| JsonObjectEditorWidgetSavedData::from_partial(archive, extra)).unwrap_or_default(),
+            ),
+            None => (None, Default::default()),
+        };
+        Self{
+            mode: TrainingDataWidgetModeSavedData::Inline,
+            dataset_id_widget: String::new(),
+            version_number_widget: None,
+            source_widget,
+            extra_fields_widget,
+        }
+    }
+}
+
 #[derive(serde::Serialize, serde::Deserialize)]
 pub struct AppState1SavedData{
     pub staging_name: String,
@@ -1768,6 +1898,8 @@ pub struct AppState1SavedData{
     pub staging_version_comment: Option<String>,
     pub staging_documentation: CodeEditorWidgetSavedData,
     pub staging_license: ::bioimg_spec::rdf::LicenseId,
+    #[serde(default)] // added after AppState1SavedData
+    pub training_data_widget: Option<TrainingDataWidgetSavedData>,
     //badges
     pub model_interface_widget: ModelInterfaceWidgetSavedData,
     ////
@@ -1853,6 +1985,8 @@ impl AppState1SavedData {
                     }
                 })
                 .unwrap_or(rdf::LicenseId::MIT),
+            training_data_widget: partial.training_data
+                .map(|partial| TrainingDataWidgetSavedData::from_partial(archive, partial)),
             model_interface_widget: ModelInterfaceWidgetSavedData::from_partial(
                 archive, partial.inputs.unwrap_or_default(), partial.outputs.unwrap_or_default(), warnings
             ),
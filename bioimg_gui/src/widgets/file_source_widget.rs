@@ -391,6 +391,12 @@ impl ValueWidget for FileSourceWidget{
                 self.mode = FileSourceWidgetMode::Local;
                 self.local_file_source_widget = LocalFileSourceWidget::from_outer_path(path, None, None);
             },
+            #[cfg(not(target_arch="wasm32"))]
+            rt::FileSource::FileInDirectory { dir, inner_path } => {
+                self.mode = FileSourceWidgetMode::Local;
+                let path: Arc<Path> = Arc::from(dir.join(&*inner_path));
+                self.local_file_source_widget = LocalFileSourceWidget::from_outer_path(path, None, None);
+            },
             rt::FileSource::FileInZipArchive { inner_path, archive} => {
                 self.mode = FileSourceWidgetMode::Local;
                 self.local_file_source_widget = {
@@ -6,11 +6,12 @@ use bioimg_spec::rdf::model::axis_size::FixedOrRefAxisSize;
 use indoc::indoc;
 
 use bioimg_runtime::model_interface::{InputSlot, OutputSlot};
-use bioimg_runtime::npy_array::ArcNpyArray;
+use bioimg_runtime::npy_array::{ArcNpyArray, NpyArray};
 
 use crate::result::{GuiError, Result};
 use bioimg_spec::rdf::model::{self as modelrdf, AnyAxisSize, AxisId, AxisSizeReference, AxisType, InputAxis, ParameterizedAxisSize};
 use bioimg_spec::rdf::model::input_tensor as rdfinput;
+use bioimg_spec::rdf::model::tensor_data_descr::{AnyTensorDataDescr, IntervalOrRatioDataDescr, TensorDataDescr};
 
 use super::collapsible_widget::{CollapsibleWidget, SummarizableWidget};
 use super::error_display::show_error;
@@ -19,11 +20,34 @@ use super::preprocessing_widget::{PreprocessingWidget, ShowPreprocTypePicker};
 use super::staging_string::StagingString;
 use super::input_axis_widget::InputAxisWidget;
 use super::output_axis_widget::OutputAxisWidget;
+use super::sample_tensor_widget::SampleTensorWidget;
+use super::tensor_data_descr_widget::TensorDataDescrWidget;
 use super::test_tensor_widget::{TestTensorWidget, TestTensorWidgetState};
 use super::util::{VecItemRender, VecWidget};
 use super::{Restore, StatefulWidget, ValueWidget};
 use crate::widgets::staging_vec::ItemWidgetConf;
 
+/// Describes the data of a tensor as unbounded values of the same type as its test tensor
+fn data_descr_from_test_tensor(test_tensor: &NpyArray) -> Option<AnyTensorDataDescr>{
+    let data_type = test_tensor.dtype()?;
+    Some(TensorDataDescr::IntervalOrRatio(IntervalOrRatioDataDescr{data_type, ..Default::default()}).into())
+}
+
+/// The data description entered in `data_widget`, falling back to one derived from the test tensor
+fn parse_data_descr(data_widget: &TensorDataDescrWidget, test_tensor: &NpyArray) -> Result<Option<AnyTensorDataDescr>>{
+    match data_widget.state(){
+        Ok(Some(data)) => Ok(Some(data.clone())),
+        Ok(None) => Ok(data_descr_from_test_tensor(test_tensor)),
+        Err(err) => Err(GuiError::new(format!("Bad data description: {err}"))),
+    }
+}
+
+const DATA_DESCR_HOVER_TEXT: &str = indoc!("
+    A JSON description of the values in this tensor, e.g. the allowed values of a label tensor or the \
+    range of an intensity tensor. It can also be a list with one description per channel. \
+    Leave it empty to describe the data as unbounded values of the same type as the test tensor."
+);
+
 trait IAnyAxisSizeExt{
     fn as_header(&self, axis_id: &AxisId) -> String;
 }
@@ -111,13 +135,16 @@ pub struct InputTensorWidget {
     pub description_widget: StagingString<modelrdf::TensorTextDescription>,
     pub axis_widgets: Vec<InputAxisWidget>,
     pub test_tensor_widget: TestTensorWidget,
+    pub sample_tensor_widget: SampleTensorWidget,
     pub preprocessing_widget: Vec<PreprocessingWidget>,
+    pub data_widget: TensorDataDescrWidget,
 }
 
 
 impl ValueWidget for InputTensorWidget{
     type Value<'v> = InputSlot<ArcNpyArray>;
     fn set_value<'v>(&mut self, value: Self::Value<'v>) {
+        self.data_widget.set_value(value.tensor_meta.data().cloned());
         self.axis_widgets = value.tensor_meta.axes().iter()
             .map(|descr|{
                 let mut w = InputAxisWidget::default();
@@ -135,6 +162,7 @@ impl ValueWidget for InputTensorWidget{
         self.id_widget.set_value(value.tensor_meta.id);
         self.description_widget.set_value(value.tensor_meta.description);
         self.test_tensor_widget.set_value(value.test_tensor);
+        self.sample_tensor_widget.set_value(value.sample_tensor);
     }
 }
 
@@ -164,7 +192,7 @@ impl SummarizableWidget for InputTensorWidget{
 impl InputTensorWidget{
     fn autofill_from_test_tensor(&mut self){
         let guard = self.test_tensor_widget.state();
-        let TestTensorWidgetState::Loaded { path, data: gui_npy_arr } = &guard.1 else {
+        let TestTensorWidgetState::Loaded { path, data: gui_npy_arr, .. } = &guard.1 else {
             self.adjust_num_axes_on_file_selected = true;
             return;
         };
@@ -223,9 +251,14 @@ impl InputTensorWidget{
                 .map_err(|err| GuiError::new(format!("Preprocessing error: {err}")))?,
             description: self.description_widget.state()?.clone(),
             axes: input_axis_group,
+            data: parse_data_descr(&self.data_widget, gui_npy_array)?,
         };
         return Ok(
-            InputSlot{ tensor_meta: meta_msg.try_into()?, test_tensor: Arc::clone(gui_npy_array) }
+            InputSlot{
+                tensor_meta: meta_msg.try_into()?,
+                test_tensor: Arc::clone(gui_npy_array),
+                sample_tensor: self.sample_tensor_widget.state()?,
+            }
         );
     }
     pub fn draw(&mut self, ui: &mut egui::Ui, id: egui::Id) {
@@ -244,6 +277,22 @@ impl InputTensorWidget{
                     show_error(ui, "Missing a npy test tensor");
                 }
             });
+            ui.horizontal(|ui| {
+                ui.strong("Sample Image: ").on_hover_text(indoc!("
+                    An optional PNG, TIFF or JPEG image illustrating what this tensor looks like. Unlike the \
+                    test tensor, it is not used for testing the model; it is meant to be shown to people browsing \
+                    the model."
+                ));
+                self.sample_tensor_widget.draw_and_parse(ui, id.with("sample tensor"));
+                let test_tensor = match &self.test_tensor_widget.state().1 {
+                    TestTensorWidgetState::Loaded { data, .. } => Arc::clone(data),
+                    _ => return,
+                };
+                if ui.button("Generate from test tensor").clicked(){
+                    let axis_types: Vec<_> = self.axis_widgets.iter().map(|w| w.axis_type_widget.value).collect();
+                    self.sample_tensor_widget.generate_from_test_tensor(&test_tensor, &axis_types);
+                }
+            });
             ui.horizontal(|ui|{
                 ui.strong("Input is optional: ").on_hover_text(indoc!("
                     Marks whether the model can do inference without this input."
@@ -300,6 +349,10 @@ impl InputTensorWidget{
                 };
                 ui.add(vec_widget);
             });
+            ui.horizontal(|ui| {
+                ui.strong("Data Description: ").on_hover_text(DATA_DESCR_HOVER_TEXT);
+                self.data_widget.draw_and_parse(ui, id.with("data descr".as_ptr()));
+            });
             ui.horizontal(|ui| {
                 ui.strong("Preprocessing: ").on_hover_text(indoc!("
                     A list of preprocessing steps that will be applied to this input tensor before it is \
@@ -338,7 +391,9 @@ pub struct OutputTensorWidget {
     pub description_widget: StagingString<modelrdf::TensorTextDescription>,
     pub axis_widgets: Vec<OutputAxisWidget>,
     pub test_tensor_widget: TestTensorWidget,
+    pub sample_tensor_widget: SampleTensorWidget,
     pub postprocessing_widgets: Vec<CollapsibleWidget<PostprocessingWidget>>,
+    pub data_widget: TensorDataDescrWidget,
 }
 
 impl Default for OutputTensorWidget{
@@ -349,7 +404,9 @@ impl Default for OutputTensorWidget{
             description_widget: Default::default(),
             axis_widgets: Default::default(),
             test_tensor_widget: Default::default(),
+            sample_tensor_widget: Default::default(),
             postprocessing_widgets: Default::default(),
+            data_widget: Default::default(),
         }
     }
 }
@@ -357,6 +414,7 @@ impl Default for OutputTensorWidget{
 impl ValueWidget for OutputTensorWidget{
     type Value<'v> = OutputSlot<ArcNpyArray>;
     fn set_value<'v>(&mut self, value: Self::Value<'v>) {
+        self.data_widget.set_value(value.tensor_meta.data().cloned());
         self.axis_widgets = value.tensor_meta.axes().iter()
             .map(|descr|{
                 let mut widget = OutputAxisWidget::default();
@@ -374,6 +432,7 @@ impl ValueWidget for OutputTensorWidget{
         self.id_widget.set_value(value.tensor_meta.id);
         self.description_widget.set_value(value.tensor_meta.description);
         self.test_tensor_widget.set_value(value.test_tensor);
+        self.sample_tensor_widget.set_value(value.sample_tensor);
     }
 }
 
@@ -402,7 +461,7 @@ impl SummarizableWidget for OutputTensorWidget{
 impl OutputTensorWidget{
     fn autofill_from_test_tensor(&mut self){
         let guard = self.test_tensor_widget.state();
-        let TestTensorWidgetState::Loaded { path, data: gui_npy_arr } = &guard.1 else {
+        let TestTensorWidgetState::Loaded { path, data: gui_npy_arr, .. } = &guard.1 else {
             self.adjust_num_axes_on_file_selected = true;
             return;
         };
@@ -453,9 +512,14 @@ impl OutputTensorWidget{
                 .collect::<Result<_>>()?,
             description: self.description_widget.state()?.clone(),
             axes: axis_group,
+            data: parse_data_descr(&self.data_widget, gui_npy_array)?,
         };
         Ok(
-            OutputSlot{ tensor_meta: meta_msg.try_into()?, test_tensor: Arc::clone(gui_npy_array) }
+            OutputSlot{
+                tensor_meta: meta_msg.try_into()?,
+                test_tensor: Arc::clone(gui_npy_array),
+                sample_tensor: self.sample_tensor_widget.state()?,
+            }
         )
     }
     pub fn draw(&mut self, ui: &mut egui::Ui, id: egui::Id) {
@@ -473,6 +537,22 @@ impl OutputTensorWidget{
                     show_error(ui, "Missing a npy test tensor");
                 }
             });
+            ui.horizontal(|ui| {
+                ui.strong("Sample Image: ").on_hover_text(indoc!("
+                    An optional PNG, TIFF or JPEG image illustrating what this tensor looks like. Unlike the \
+                    test tensor, it is not used for testing the model; it is meant to be shown to people browsing \
+                    the model."
+                ));
+                self.sample_tensor_widget.draw_and_parse(ui, id.with("sample tensor"));
+                let test_tensor = match &self.test_tensor_widget.state().1 {
+                    TestTensorWidgetState::Loaded { data, .. } => Arc::clone(data),
+                    _ => return,
+                };
+                if ui.button("Generate from test tensor").clicked(){
+                    let axis_types: Vec<_> = self.axis_widgets.iter().map(|w| w.axis_type_widget.value).collect();
+                    self.sample_tensor_widget.generate_from_test_tensor(&test_tensor, &axis_types);
+                }
+            });
             ui.horizontal(|ui| {
                 ui.strong("Tensor Id: ").on_hover_text(indoc!("
                     The name of this output tensor. Running this model will produce a mapping of strings \
@@ -525,6 +605,10 @@ impl OutputTensorWidget{
                 };
                 ui.add(vec_widget);
             });
+            ui.horizontal(|ui| {
+                ui.strong("Data Description: ").on_hover_text(DATA_DESCR_HOVER_TEXT);
+                self.data_widget.draw_and_parse(ui, id.with("data descr".as_ptr()));
+            });
             ui.horizontal(|ui| {
                 ui.strong("Postprocessing: ").on_hover_text(indoc!("
                     A list of postprocessing steps that will be applied to this output tensor \
@@ -38,6 +38,7 @@ pub mod scale_linear_widget;
 pub mod zero_mean_unit_variance_widget;
 pub mod scale_range_widget;
 pub mod json_editor_widget;
+pub mod tensor_data_descr_widget;
 pub mod conda_env_editor_widget;
 pub mod version_widget;
 pub mod file_source_widget;
@@ -50,6 +51,9 @@ pub mod staging_float;
 pub mod collapsible_widget;
 pub mod model_links_widget;
 pub mod test_tensor_widget;
+pub mod sample_tensor_widget;
+pub mod training_data_widget;
+pub mod packing_progress_widget;
 #[cfg(not(target_arch="wasm32"))]
 pub mod zoo_widget;
 pub mod labels;
@@ -145,3 +149,4 @@ impl_Restore_for!(rdf::model::axes::AxisType);
 impl_Restore_for!(rdf::model::SpaceUnit);
 impl_Restore_for!(rdf::model::TimeUnit);
 impl_Restore_for!(rdf::model::DataType);
+impl_Restore_for!(rdf::model::WeightsFormat);
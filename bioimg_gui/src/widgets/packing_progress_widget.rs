@@ -0,0 +1,56 @@
+use std::sync::{Arc, Mutex};
+
+use bioimg_runtime::url_fetcher::CancellationToken;
+use bioimg_runtime::zip_writer_ext::{PackingObserver, PackingProgress};
+
+/// Shows how far along the export of a model is, with a button to abort it
+#[derive(Clone)]
+pub struct PackingProgressWidget{
+    pub file_name: String,
+    progress: Arc<Mutex<Option<PackingProgress>>>,
+    cancellation: CancellationToken,
+}
+
+impl PackingProgressWidget{
+    pub fn new(file_name: String, cancellation: CancellationToken) -> Self{
+        Self{file_name, progress: Default::default(), cancellation}
+    }
+
+    /// An observer that feeds the progress of the packing task into this widget
+    pub fn observer(&self) -> Box<dyn PackingObserver>{
+        let progress = self.progress.clone();
+        Box::new(move |p: &PackingProgress| {
+            *progress.lock().unwrap() = Some(p.clone());
+        })
+    }
+
+    pub fn draw(&self, ui: &mut egui::Ui){
+        const MB: f64 = 1024.0 * 1024.0;
+
+        let progress = self.progress.lock().unwrap().clone();
+        ui.horizontal(|ui|{
+            ui.label(format!("Packing into {}", self.file_name));
+            if let Some(progress) = &progress{
+                let written_mb = progress.bytes_written as f64 / MB;
+                let bar = match progress.total_bytes{
+                    Some(total) if total > 0 => {
+                        let fraction = (progress.bytes_written as f32 / total as f32).min(1.0);
+                        egui::ProgressBar::new(fraction)
+                            .text(format!("{}: {written_mb:.1} of {:.1} MB", progress.component, total as f64 / MB))
+                    },
+                    _ => egui::ProgressBar::new(0.0)
+                        .animate(true)
+                        .text(format!("{}: {written_mb:.1} MB", progress.component)),
+                };
+                ui.add(bar.desired_width(300.0));
+            }
+            if self.cancellation.is_cancelled(){
+                ui.weak("Cancelling...");
+            } else if ui.button("Cancel").clicked(){
+                self.cancellation.cancel();
+            }
+        });
+        // packing happens in another thread, so keep polling for progress
+        ui.ctx().request_repaint_after(std::time::Duration::from_millis(100));
+    }
+}
@@ -0,0 +1,140 @@
+use std::path::PathBuf;
+
+use bioimg_runtime::sample_tensor::SampleTensor;
+use bioimg_runtime::NpyArray;
+use bioimg_spec::rdf::model::AxisType;
+
+use crate::{project_data::SampleTensorWidgetSavedData, result::GuiError};
+
+use super::util::{GenSync, Generation};
+use super::{error_display::show_error, Restore, StatefulWidget, ValueWidget};
+
+
+#[derive(Default)]
+pub enum SampleTensorWidgetState{
+    #[default]
+    Empty,
+    Loaded{path: Option<PathBuf>, data: SampleTensor},
+    Error{message: String}
+}
+
+/// A widget for selecting an optional image that illustrates a Model input or output to humans
+#[derive(Default)]
+pub struct SampleTensorWidget{
+    state: GenSync<SampleTensorWidgetState>,
+}
+
+impl ValueWidget for SampleTensorWidget{
+    type Value<'v> = Option<SampleTensor>;
+
+    fn set_value<'v>(&mut self, value: Self::Value<'v>) {
+        self.state = GenSync::new(match value{
+            Some(data) => SampleTensorWidgetState::Loaded { path: None, data },
+            None => SampleTensorWidgetState::Empty,
+        });
+    }
+}
+
+impl Restore for SampleTensorWidget{
+    type SavedData = SampleTensorWidgetSavedData;
+
+    fn dump(&self) -> Self::SavedData {
+        let guard = self.state.lock();
+        match &guard.1 {
+            SampleTensorWidgetState::Empty  | SampleTensorWidgetState::Error { .. } => SampleTensorWidgetSavedData::Empty,
+            SampleTensorWidgetState::Loaded { path, data } => SampleTensorWidgetSavedData::Loaded {
+                path: path.clone(),
+                data: data.bytes().to_vec(),
+            }
+        }
+    }
+
+    fn restore(&mut self, saved_data: Self::SavedData) {
+        self.state = GenSync::new(match saved_data{
+            SampleTensorWidgetSavedData::Empty => SampleTensorWidgetState::Empty,
+            SampleTensorWidgetSavedData::Loaded { path, data } => match SampleTensor::try_from_bytes(data){
+                Ok(data) => SampleTensorWidgetState::Loaded { path, data },
+                Err(e) => SampleTensorWidgetState::Error { message: e.to_string() },
+            }
+        });
+    }
+}
+
+impl SampleTensorWidget{
+    /// Replaces the current sample with one rendered from `test_tensor`
+    pub fn generate_from_test_tensor(&mut self, test_tensor: &NpyArray, axis_types: &[AxisType]){
+        let new_state = match SampleTensor::from_test_tensor(test_tensor, axis_types){
+            Ok(data) => SampleTensorWidgetState::Loaded { path: None, data },
+            Err(e) => SampleTensorWidgetState::Error { message: format!("Could not generate sample: {e}") },
+        };
+        self.state.lock_then_replace_with(|generation, _| (generation.incremented(), new_state));
+    }
+
+    pub fn launch_sample_tensor_picker(
+        request_generation: Generation,
+        state: GenSync<SampleTensorWidgetState>,
+    ){
+        let fut  = async move {
+            let Some(file_handle) = rfd::AsyncFileDialog::new()
+                .add_filter("image", &["png", "tif", "tiff", "jpg", "jpeg"])
+                .pick_file().await
+            else {
+                return
+            };
+            let file_data = file_handle.read().await;
+            #[cfg(target_arch="wasm32")]
+            let path = None;
+            #[cfg(not(target_arch="wasm32"))]
+            let path = Some(file_handle.path().to_owned());
+            let new_state = match SampleTensor::try_from_bytes(file_data){
+                Ok(data) => SampleTensorWidgetState::Loaded { path, data },
+                Err(e) => SampleTensorWidgetState::Error { message: e.to_string() }
+            };
+            state.lock_then_maybe_set(request_generation, new_state);
+        };
+
+        #[cfg(target_arch="wasm32")]
+        wasm_bindgen_futures::spawn_local(fut);
+        #[cfg(not(target_arch="wasm32"))]
+        std::thread::spawn(move || smol::block_on(fut));
+    }
+}
+
+impl StatefulWidget for SampleTensorWidget{
+    type Value<'p> = Result<Option<SampleTensor>, GuiError>;
+
+    fn draw_and_parse(&mut self, ui: &mut egui::Ui, _id: egui::Id) {
+        let mut guard = self.state.lock();
+        if ui.button("Open...").clicked(){
+            Self::launch_sample_tensor_picker(guard.0, self.state.clone());
+        }
+        let mut clear = false;
+        match &guard.1{
+            SampleTensorWidgetState::Empty => (),
+            SampleTensorWidgetState::Loaded { path, data } => {
+                ui.weak(format!("{} image", data.format().extension()));
+                if let Some(p) = path{
+                    ui.weak("from");
+                    ui.weak(p.to_string_lossy());
+                }
+                clear = ui.button("Clear").clicked();
+            },
+            SampleTensorWidgetState::Error { message } => {
+                show_error(ui, message);
+            }
+        }
+        if clear{
+            guard.0 = guard.0.incremented();
+            guard.1 = SampleTensorWidgetState::Empty;
+        }
+    }
+
+    fn state<'p>(&'p self) -> Self::Value<'p> {
+        let guard = self.state.lock();
+        match &guard.1{
+            SampleTensorWidgetState::Empty => Ok(None),
+            SampleTensorWidgetState::Error { message } => Err(GuiError::new(format!("Bad sample tensor: {message}"))),
+            SampleTensorWidgetState::Loaded { data, .. } => Ok(Some(data.clone())),
+        }
+    }
+}
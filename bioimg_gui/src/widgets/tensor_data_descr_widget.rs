@@ -0,0 +1,70 @@
+use bioimg_spec::rdf::model::tensor_data_descr::AnyTensorDataDescr;
+
+use crate::{project_data::TensorDataDescrWidgetSavedData, result::Result};
+use super::{code_editor_widget::{CodeEditorWidget, JsonLanguage}, error_display::show_if_error, Restore, StatefulWidget, ValueWidget};
+
+/// Edits the `data` field of a tensor description as JSON. An empty editor means
+/// the description is left for the caller to derive (e.g. from the test tensor)
+pub struct TensorDataDescrWidget{
+    pub code_editor_widget: CodeEditorWidget<JsonLanguage>,
+    pub parsed: Result<Option<AnyTensorDataDescr>>,
+}
+
+impl TensorDataDescrWidget{
+    pub fn update(&mut self){
+        self.parsed = if self.code_editor_widget.raw.trim().is_empty() {
+            Ok(None)
+        } else {
+            serde_json::from_str(&self.code_editor_widget.raw)
+                .map(Some)
+                .map_err(|err| err.into())
+        };
+    }
+}
+
+impl Default for TensorDataDescrWidget{
+    fn default() -> Self {
+        Self{code_editor_widget: Default::default(), parsed: Ok(None)}
+    }
+}
+
+impl Restore for TensorDataDescrWidget{
+    type SavedData = TensorDataDescrWidgetSavedData;
+    fn dump(&self) -> Self::SavedData {
+        TensorDataDescrWidgetSavedData{
+            code_editor_widget: self.code_editor_widget.dump()
+        }
+    }
+    fn restore(&mut self, saved_data: Self::SavedData) {
+        self.code_editor_widget.restore(saved_data.code_editor_widget);
+        self.update()
+    }
+}
+
+impl ValueWidget for TensorDataDescrWidget{
+    type Value<'v> = Option<AnyTensorDataDescr>;
+
+    fn set_value<'v>(&mut self, value: Self::Value<'v>) {
+        self.code_editor_widget.raw = match &value {
+            Some(data) => serde_json::to_string_pretty(data).unwrap(),
+            None => String::new(),
+        };
+        self.parsed = Ok(value)
+    }
+}
+
+impl StatefulWidget for TensorDataDescrWidget{
+    type Value<'p> = &'p Result<Option<AnyTensorDataDescr>>;
+
+    fn draw_and_parse(&mut self, ui: &mut egui::Ui, id: egui::Id) {
+        ui.vertical(|ui|{
+            self.code_editor_widget.draw_and_parse(ui, id.with("code".as_ptr()));
+            self.update();
+            show_if_error(ui, &self.parsed);
+        });
+    }
+
+    fn state<'p>(&'p self) -> Self::Value<'p> {
+        &self.parsed
+    }
+}
@@ -2,7 +2,7 @@ use std::io::Cursor;
 use std::path::PathBuf;
 use std::sync::Arc;
 
-use bioimg_runtime::{npy_array::ArcNpyArray, NpyArray};
+use bioimg_runtime::{npy_array::ArcNpyArray, tensor_import::ImportedTensor, NpyArray};
 
 use crate::{project_data::TestTensorWidgetSavedData, result::GuiError};
 
@@ -14,7 +14,7 @@ use super::{error_display::show_error, Restore, StatefulWidget, ValueWidget};
 pub enum TestTensorWidgetState{
     #[default]
     Empty,
-    Loaded{path: Option<PathBuf>, data: ArcNpyArray},
+    Loaded{path: Option<PathBuf>, data: ArcNpyArray, dim_order: Option<String>},
     Error{message: String}
 }
 
@@ -29,7 +29,7 @@ impl ValueWidget for TestTensorWidget{
 
     fn set_value<'v>(&mut self, data: Self::Value<'v>) {
         self.state = GenSync::new(
-            TestTensorWidgetState::Loaded { path: None, data}
+            TestTensorWidgetState::Loaded { path: None, data, dim_order: None}
         );
     }
 }
@@ -41,7 +41,7 @@ impl Restore for TestTensorWidget{
         let guard = self.state.lock();
         match &guard.1 {
             TestTensorWidgetState::Empty  | &TestTensorWidgetState::Error { .. }=> TestTensorWidgetSavedData::Empty,
-            TestTensorWidgetState::Loaded { path, data } => TestTensorWidgetSavedData::Loaded {
+            TestTensorWidgetState::Loaded { path, data, .. } => TestTensorWidgetSavedData::Loaded {
                 path: path.clone(),
                 data: {
                     let mut v = vec![];
@@ -57,7 +57,7 @@ impl Restore for TestTensorWidget{
             TestTensorWidgetSavedData::Empty => TestTensorWidgetState::Empty,
             TestTensorWidgetSavedData::Loaded { path, data } => {
                 let state = match NpyArray::try_load(Cursor::new(data)){
-                    Ok(data) => TestTensorWidgetState::Loaded { path, data: Arc::new(data) },
+                    Ok(data) => TestTensorWidgetState::Loaded { path, data: Arc::new(data), dim_order: None },
                     Err(_e) => TestTensorWidgetState::Error { message: "Could not deserialize npy data".to_owned() }
                 };
                 state
@@ -68,14 +68,9 @@ impl Restore for TestTensorWidget{
 
 impl TestTensorWidget{
     #[cfg(not(target_arch="wasm32"))]
-    pub async fn try_load_path(path: &std::path::Path) -> Result<ArcNpyArray, GuiError>{
-        use smol::io::AsyncReadExt;
-
-        let mut reader = smol::fs::File::open(path).await?;
-        let mut data = vec![];
-        reader.read_to_end(&mut data).await ?;
-        let data = NpyArray::try_load(&mut data.as_slice())?;
-        Ok(Arc::new(data))
+    pub async fn try_load_path(path: &std::path::Path) -> Result<ImportedTensor, GuiError>{
+        let path = path.to_owned();
+        Ok(smol::unblock(move || ImportedTensor::try_load_path(&path)).await?)
     }
     pub fn state(&self) -> std::sync::MutexGuard<'_, (Generation, TestTensorWidgetState)>{
         self.state.lock()
@@ -85,15 +80,15 @@ impl TestTensorWidget{
         state: GenSync<TestTensorWidgetState>,
     ){
         let fut  = async move {
-            let Some(file_handle) = rfd::AsyncFileDialog::new().add_filter("numpy array", &["npy"],).pick_file().await else {
+            let Some(file_handle) = rfd::AsyncFileDialog::new().add_filter("numpy array or image", ImportedTensor::EXTENSIONS).pick_file().await else {
                 state.lock_then_maybe_set(request_generation, TestTensorWidgetState::Empty);
                 return
             };
             #[cfg(target_arch="wasm32")]
             let (result, path) = {
                 let file_data = file_handle.read().await; //FIXME: This could panic. Read from the JsObj instead
-                let array_result = NpyArray::try_load(&mut file_data.as_slice()).map(Arc::new);
-                (array_result, None)
+                let result = ImportedTensor::try_load(Cursor::new(file_data)).map_err(GuiError::from);
+                (result, None)
             };
             #[cfg(not(target_arch="wasm32"))]
             let (result, path) = {
@@ -101,7 +96,9 @@ impl TestTensorWidget{
                 (result, Some(file_handle.path().to_owned()))
             };
             let new_state = match result {
-                Ok(data) => TestTensorWidgetState::Loaded { path, data },
+                Ok(imported) => TestTensorWidgetState::Loaded {
+                    path, data: Arc::new(imported.data), dim_order: Some(imported.dim_order)
+                },
                 Err(e) => TestTensorWidgetState::Error { message: e.to_string() }
             };
             state.lock_then_maybe_set(request_generation, new_state);
@@ -126,7 +123,7 @@ impl StatefulWidget for TestTensorWidget{
             
             match &guard.1{
                 TestTensorWidgetState::Empty => (),
-                TestTensorWidgetState::Loaded { path, data } => {
+                TestTensorWidgetState::Loaded { path, data, dim_order } => {
                     let shape = data.shape();
                     let last_item_idx = shape.len() - 1;
                     let shape_str = shape
@@ -141,6 +138,9 @@ impl StatefulWidget for TestTensorWidget{
                             acc
                         });
                     ui.weak(format!("C-order shape: [{shape_str}] "));
+                    if let Some(dim_order) = dim_order.as_ref().filter(|order| !order.contains('?')){
+                        ui.weak(format!("({dim_order})"));
+                    }
                     if let Some(p) = path{
                         ui.weak("from");
                         ui.weak(p.to_string_lossy());
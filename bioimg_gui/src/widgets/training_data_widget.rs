@@ -0,0 +1,121 @@
+use std::sync::Arc;
+
+use bioimg_spec::rdf::model::dataset_descr::{DatasetDescr, DatasetDescrEnum, LinkedDatasetDescr};
+use bioimg_spec::rdf::ResourceId;
+
+use crate::project_data::TrainingDataWidgetModeSavedData;
+use crate::result::Result;
+
+use super::json_editor_widget::JsonObjectEditorWidget;
+use super::staging_num::StagingNum;
+use super::staging_opt::StagingOpt;
+use super::staging_string::StagingString;
+use super::url_widget::StagingUrl;
+use super::{Restore, StatefulWidget, ValueWidget};
+
+#[derive(PartialEq, Eq, Default, Copy, Clone)]
+pub enum TrainingDataWidgetMode {
+    #[default]
+    Linked,
+    Inline,
+}
+
+impl Restore for TrainingDataWidgetMode{
+    type SavedData = TrainingDataWidgetModeSavedData;
+    fn dump(&self) -> Self::SavedData {
+        match self{
+            Self::Linked => Self::SavedData::Linked,
+            Self::Inline => Self::SavedData::Inline,
+        }
+    }
+    fn restore(&mut self, saved_data: Self::SavedData) {
+        *self = match saved_data{
+            Self::SavedData::Linked => Self::Linked,
+            Self::SavedData::Inline => Self::Inline,
+        }
+    }
+}
+
+/// Describes the dataset a model was trained on, either by its id in the bioimage.io collection
+/// or inline by the URL it can be downloaded from plus any other fields of a dataset description
+#[derive(Default, Restore)]
+#[restore(saved_data=crate::project_data::TrainingDataWidgetSavedData)]
+pub struct TrainingDataWidget{
+    pub mode: TrainingDataWidgetMode,
+    pub dataset_id_widget: StagingString<ResourceId>,
+    pub version_number_widget: StagingOpt<StagingNum<u64, u64>, false>,
+    pub source_widget: StagingOpt<StagingUrl, false>,
+    pub extra_fields_widget: JsonObjectEditorWidget,
+}
+
+impl ValueWidget for TrainingDataWidget{
+    type Value<'v> = DatasetDescrEnum;
+
+    fn set_value<'v>(&mut self, value: Self::Value<'v>) {
+        match value{
+            DatasetDescrEnum::LinkedDatasetDescr(linked) => {
+                self.mode = TrainingDataWidgetMode::Linked;
+                self.dataset_id_widget.set_value(linked.id);
+                self.version_number_widget.set_value(linked.version_number);
+            },
+            DatasetDescrEnum::DatasetDescr(descr) => {
+                self.mode = TrainingDataWidgetMode::Inline;
+                self.source_widget.set_value(descr.source.map(Arc::new));
+                self.extra_fields_widget.set_value(descr.extra);
+            },
+        }
+    }
+}
+
+impl StatefulWidget for TrainingDataWidget{
+    type Value<'p> = Result<DatasetDescrEnum>;
+
+    fn draw_and_parse(&mut self, ui: &mut egui::Ui, id: egui::Id) {
+        ui.vertical(|ui| {
+            ui.horizontal(|ui| {
+                ui.radio_value(&mut self.mode, TrainingDataWidgetMode::Linked, "Dataset from the bioimage.io collection");
+                ui.radio_value(&mut self.mode, TrainingDataWidgetMode::Inline, "Other dataset");
+            });
+            match self.mode{
+                TrainingDataWidgetMode::Linked => {
+                    ui.horizontal(|ui| {
+                        ui.strong("Dataset Id: ").on_hover_text("The id of a dataset in the bioimage.io collection");
+                        self.dataset_id_widget.draw_and_parse(ui, id.with("dataset id".as_ptr()));
+                    });
+                    ui.horizontal(|ui| {
+                        ui.strong("Version Number: ").on_hover_text(
+                            "The n-th published version of the dataset. Leave it empty to refer to the latest one"
+                        );
+                        self.version_number_widget.draw_and_parse(ui, id.with("version number".as_ptr()));
+                    });
+                },
+                TrainingDataWidgetMode::Inline => {
+                    ui.horizontal(|ui| {
+                        ui.strong("Source: ").on_hover_text("URL from where the dataset can be obtained");
+                        self.source_widget.draw_and_parse(ui, id.with("source".as_ptr()));
+                    });
+                    ui.horizontal(|ui| {
+                        ui.strong("Other Fields: ").on_hover_text(
+                            "Any other fields of the dataset description, like its name, description or authors"
+                        );
+                        self.extra_fields_widget.draw_and_parse(ui, id.with("extra fields".as_ptr()));
+                    });
+                },
+            };
+        });
+    }
+
+    fn state<'p>(&'p self) -> Self::Value<'p> {
+        Ok(match self.mode{
+            TrainingDataWidgetMode::Linked => DatasetDescrEnum::LinkedDatasetDescr(LinkedDatasetDescr{
+                id: self.dataset_id_widget.state()?.clone(),
+                version_number: self.version_number_widget.state().transpose()?,
+            }),
+            TrainingDataWidgetMode::Inline => DatasetDescrEnum::DatasetDescr(DatasetDescr{
+                source: self.source_widget.state().transpose()?.map(|url| url.as_ref().clone()),
+                extra: self.extra_fields_widget.state().clone()?,
+                ..Default::default()
+            }),
+        })
+    }
+}
@@ -1,6 +1,8 @@
 use std::sync::Arc;
 
 use bioimg_runtime as rt;
+use bioimg_spec::rdf::model as modelrdf;
+use bioimg_spec::rdf::BoundedString;
 
 use crate::result::{GuiError, Result, VecResultExt};
 use super::{Restore, StatefulWidget, ValueWidget};
@@ -9,6 +11,8 @@ use super::version_widget::VersionWidget;
 use super::util::group_frame;
 use super::staging_vec::StagingVec;
 use super::staging_opt::StagingOpt;
+use super::staging_string::StagingString;
+use super::search_and_pick_widget::SearchAndPickWidget;
 use super::pytorch_statedict_weights_widget::PytorchStateDictWidget;
 use super::onnx_weights_widget::OnnxWeightsWidget;
 use super::file_source_widget::FileSourceWidget;
@@ -77,7 +81,8 @@ impl  WeightsWidget{
 pub struct WeightsDescrBaseWidget{
     pub source_widget: FileSourceWidget,
     pub authors_widget: StagingOpt<StagingVec<CollapsibleWidget<AuthorWidget>>>,
-    // pub parent_widget: Option<WeightsFormat>,
+    pub parent_widget: StagingOpt<SearchAndPickWidget<modelrdf::WeightsFormat>>,
+    pub comment_widget: StagingOpt<StagingString<BoundedString<0, 512>>>,
 }
 
 impl SummarizableWidget for WeightsDescrBaseWidget{
@@ -99,6 +104,8 @@ impl ValueWidget for WeightsDescrBaseWidget{
     fn set_value<'v>(&mut self, value: Self::Value<'v>) {
         self.source_widget.set_value(value.source);
         self.authors_widget.set_value(value.authors);
+        self.parent_widget.set_value(value.parent);
+        self.comment_widget.set_value(value.comment);
     }
 }
 
@@ -117,6 +124,16 @@ impl StatefulWidget for WeightsDescrBaseWidget{
                 ui.strong("Authors: ").on_hover_text("The people who trained these weights and biases");
                 self.authors_widget.draw_and_parse(ui, id.with("authors"));
             });
+            ui.horizontal(|ui|{
+                ui.strong("Converted from: ").on_hover_text(
+                    "The weights format these weights were converted from, if they were obtained by conversion"
+                );
+                self.parent_widget.draw_and_parse(ui, id.with("parent"));
+            });
+            ui.horizontal(|ui|{
+                ui.strong("Comment: ").on_hover_text("Any notes about these weights, e.g. how they were converted");
+                self.comment_widget.draw_and_parse(ui, id.with("comment"));
+            });
         });
     }
 
@@ -125,7 +142,10 @@ impl StatefulWidget for WeightsDescrBaseWidget{
             authors.collect_result()
         }).transpose()?;
         let source = self.source_widget.state().map_err(|e| GuiError::new(format!("Model source error: {e}")))?;
-        Ok(rt::WeightsBase{authors, source})
+        let comment = self.comment_widget.state().transpose()
+            .map_err(|e| GuiError::new(format!("Invalid weights comment: {e}")))?
+            .cloned();
+        Ok(rt::WeightsBase{authors, source, parent: self.parent_widget.state(), comment, sha256: None})
     }
 }
 
@@ -1,8 +1,8 @@
-use std::collections::{BTreeMap, HashMap};
+use std::collections::HashMap;
 use ordermap::OrderSet;
 
 use bioimg_spec::rdf::model::{
-    axis_size::{QualifiedAxisId, ResolvedAxisSize}, AnyAxisSize, AxisSizeReference, ParameterizedAxisSize
+    axis_size::{QualifiedAxisId, ResolvedAxisSize}, AnyAxisSize, ParameterizedAxisSize
 };
 
 pub trait ResolvedAxisSizeExt{
@@ -25,14 +25,52 @@ impl ResolvedAxisSizeExt for ResolvedAxisSize{
     }
 }
 
+/// Follows chains of axis size references, e.g. from an output axis to the input axis it depends on
 pub struct SlotResolver {
-    resolved_axes: HashMap<QualifiedAxisId, ResolvedAxisSize>,
-    unresolved_axes: BTreeMap<QualifiedAxisId, AxisSizeReference>,
+    axes: HashMap<QualifiedAxisId, AxisNode>,
 }
 
-pub enum ResolverStatus {
-    Done(HashMap<QualifiedAxisId, ResolvedAxisSize>),
-    Resolving(SlotResolver),
+struct AxisNode {
+    /// `None` for batch axes
+    size: Option<AnyAxisSize>,
+    scale: f64,
+}
+
+/// How the extent of an axis follows from the extent of the first non-referencing axis in its chain of references
+#[derive(Clone, Debug)]
+pub struct ReferenceChain {
+    /// Every axis visited, from the starting axis to the root of the chain
+    path: OrderSet<QualifiedAxisId>,
+    /// Size of the root, or `None` if the root is a batch axis
+    root_size: Option<ResolvedAxisSize>,
+    /// Scale of the referenced axis, scale of the referencing axis and offset of each reference,
+    /// starting with the one closest to the root
+    hops: Vec<(f64, f64, usize)>,
+}
+
+impl ReferenceChain {
+    /// The first axis in the chain whose size is not a reference
+    pub fn root(&self) -> &QualifiedAxisId {
+        self.path.last().expect("Chains always contain their starting axis")
+    }
+    pub fn root_size(&self) -> Option<&ResolvedAxisSize> {
+        self.root_size.as_ref()
+    }
+    /// Whether the starting axis references another axis at all
+    pub fn is_reference(&self) -> bool {
+        !self.hops.is_empty()
+    }
+    /// Combined scale ratio of all references, i.e. how many pixels of the starting axis correspond to one of the root
+    pub fn ratio(&self) -> f64 {
+        self.hops.iter().map(|(ref_scale, scale, _)| ref_scale / scale).product()
+    }
+    /// The extent of the starting axis when the root has `root_extent`. Like the spec's
+    /// `int(ref_size * ref_scale / scale + offset)`, every reference truncates its scaled extent
+    pub fn extent_from_root(&self, root_extent: usize) -> usize {
+        self.hops.iter().fold(root_extent, |extent, (ref_scale, scale, offset)| {
+            (extent as f64 * ref_scale / scale) as usize + offset
+        })
+    }
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -67,64 +105,85 @@ impl std::fmt::Display for AxisSizeResolutionError{
 }
 
 impl SlotResolver {
+    /// A resolver over axes that all have a size, ignoring their scales
     pub fn new(sizes: Vec<(QualifiedAxisId, AnyAxisSize)>) -> Result<Self, AxisSizeResolutionError> {
-        let mut resolved_axes: HashMap<QualifiedAxisId, ResolvedAxisSize> = HashMap::with_capacity(sizes.len());
-        let mut unresolved_axes: BTreeMap<QualifiedAxisId, AxisSizeReference> = BTreeMap::new();
-        for (qual_id, inp_size) in sizes.into_iter() {
-            let duplicate_detected = match inp_size {
-                AnyAxisSize::Reference(size_ref) => {
-                    unresolved_axes.insert(qual_id.clone(), size_ref.clone()).is_some()
-                },
-                AnyAxisSize::Parameterized(resolved_size) => {
-                    resolved_axes.insert(qual_id.clone(), resolved_size.into()).is_some()
-                },
-                AnyAxisSize::Fixed(resolved_size) => {
-                    resolved_axes.insert(qual_id.clone(), resolved_size.into()).is_some()
-                }
-            };
-            if duplicate_detected {
+        Self::with_scales(sizes.into_iter().map(|(qual_id, size)| (qual_id, Some(size), 1.0)))
+    }
+
+    /// A resolver over axes with their sizes (`None` for batch axes) and scales
+    pub fn with_scales(
+        axes: impl IntoIterator<Item=(QualifiedAxisId, Option<AnyAxisSize>, f64)>
+    ) -> Result<Self, AxisSizeResolutionError> {
+        let mut nodes: HashMap<QualifiedAxisId, AxisNode> = HashMap::new();
+        for (qual_id, size, scale) in axes {
+            if nodes.insert(qual_id.clone(), AxisNode{size, scale}).is_some() {
                 return Err(AxisSizeResolutionError::DuplicateId(qual_id));
             }
         }
-        Ok(Self { resolved_axes, unresolved_axes })
+        Ok(Self { axes: nodes })
     }
 
-    fn try_resolve(
-        &mut self,
-        current: QualifiedAxisId,
-        mut visited: OrderSet<QualifiedAxisId>,
-    ) -> Result<ResolvedAxisSize, AxisSizeResolutionError> {
-        if let Some(resolved) = self.resolved_axes.get(&current) {
-            return Ok(resolved.clone());
-        }
-        if !visited.insert(current.clone()) {
-            return Err(AxisSizeResolutionError::Loop(current));
+    /// Follows the references starting at `start` until reaching an axis whose size is not a reference
+    pub fn trace(&self, start: &QualifiedAxisId) -> Result<ReferenceChain, AxisSizeResolutionError> {
+        let mut path: OrderSet<QualifiedAxisId> = OrderSet::new();
+        let mut hops = vec![];
+        let mut current = start.clone();
+        loop {
+            if !path.insert(current.clone()) {
+                return Err(AxisSizeResolutionError::Loop(current));
+            }
+            let Some(node) = self.axes.get(&current) else {
+                return Err(AxisSizeResolutionError::Unresolvable{visited: path});
+            };
+            let root_size = match &node.size {
+                None => None,
+                Some(AnyAxisSize::Fixed(fixed)) => Some(ResolvedAxisSize::Fixed(*fixed)),
+                Some(AnyAxisSize::Parameterized(size)) => Some(ResolvedAxisSize::Parameterized(size.clone())),
+                Some(AnyAxisSize::Reference(size_ref)) => {
+                    let target = &size_ref.qualified_axis_id;
+                    let target_scale = self.axes.get(target).map(|target| target.scale).unwrap_or(node.scale);
+                    hops.push((target_scale, node.scale, size_ref.offset));
+                    current = target.clone();
+                    continue;
+                }
+            };
+            hops.reverse();
+            return Ok(ReferenceChain{path, root_size, hops});
         }
-        let Some(size_ref) = self.unresolved_axes.get(&current) else {
-            return Err(AxisSizeResolutionError::Unresolvable{visited: visited.clone()});
-        };
-        let resolved = self.try_resolve(size_ref.qualified_axis_id.clone(), visited)?;
-        self.unresolved_axes.remove(&current);
-        self.resolved_axes.insert(current.clone(), resolved.clone());
-        Ok(resolved)
     }
 
-    fn step(mut self) -> Result<ResolverStatus, AxisSizeResolutionError> {
-        let Some((key, _)) = self.unresolved_axes.last_key_value() else{
-            return Ok(ResolverStatus::Done(self.resolved_axes));
-        };
-        self.try_resolve(key.clone(), OrderSet::new())?;
-        Ok(ResolverStatus::Resolving(self))
-    }
-
-    pub fn solve(mut self) -> Result<HashMap<QualifiedAxisId, ResolvedAxisSize>, AxisSizeResolutionError> {
-        loop {
-            match self.step()? {
-                ResolverStatus::Done(map) => break Ok(map),
-                ResolverStatus::Resolving(resolver) => {
-                    self = resolver;
-                }
+    /// The size class of every axis that has a size, with references replaced by the size of the axis they point to
+    pub fn solve(self) -> Result<HashMap<QualifiedAxisId, ResolvedAxisSize>, AxisSizeResolutionError> {
+        let mut resolved = HashMap::with_capacity(self.axes.len());
+        for (qual_id, _) in self.axes.iter().filter(|(_, node)| node.size.is_some()) {
+            let chain = self.trace(qual_id)?;
+            match chain.root_size {
+                Some(size) => _ = resolved.insert(qual_id.clone(), size),
+                None => return Err(AxisSizeResolutionError::Unresolvable{visited: chain.path}),
             }
         }
+        Ok(resolved)
     }
 }
+
+#[test]
+fn test_reference_cycles_are_detected(){
+    let qual_id = |tensor_id: &str| serde_json::from_value::<QualifiedAxisId>(
+        serde_json::json!({"tensor_id": tensor_id, "axis_id": "x"})
+    ).unwrap();
+    let reference = |tensor_id: &str| Some(AnyAxisSize::Reference(
+        serde_json::from_value(serde_json::json!({"tensor_id": tensor_id, "axis_id": "x"})).unwrap()
+    ));
+    let resolver = SlotResolver::with_scales([
+        (qual_id("a"), reference("b"), 1.0),
+        (qual_id("b"), reference("c"), 1.0),
+        (qual_id("c"), reference("b"), 1.0),
+        (qual_id("d"), reference("missing"), 1.0),
+    ]).unwrap();
+    assert!(matches!(resolver.trace(&qual_id("a")), Err(AxisSizeResolutionError::Loop(id)) if id == qual_id("b")));
+    assert!(matches!(
+        resolver.trace(&qual_id("d")),
+        Err(AxisSizeResolutionError::Unresolvable { visited }) if visited.len() == 2
+    ));
+    assert!(matches!(resolver.solve(), Err(AxisSizeResolutionError::Loop(_) | AxisSizeResolutionError::Unresolvable { .. })));
+}
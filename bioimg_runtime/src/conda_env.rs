@@ -3,7 +3,9 @@ use std::{fmt::Display, io::{Seek, Write}, str::FromStr};
 
 use bioimg_spec::rdf;
 
-use crate::zip_archive_ext::{RdfFileReferenceReadError, SharedZipArchive};
+use crate::url_fetcher::UrlFetcher;
+use crate::model_package::ModelPackage;
+use crate::zip_archive_ext::{RdfFileReferenceExt, RdfFileReferenceReadError};
 use crate::zoo_model::ModelPackingError;
 use crate::zip_writer_ext::ModelZipWriter;
 
@@ -23,8 +25,6 @@ pub enum CondaEnvLoadingError{
     ParsingError(#[from] CondaEnvParsingError),
     #[error(transparent)]
     RdfFileReferenceReadError(#[from] RdfFileReferenceReadError),
-    #[error("Url file reference not supported yet")]
-    UrlFileReferenceNotSupportedYet,
 }
 
 #[derive(Clone)]
@@ -54,15 +54,13 @@ impl CondaEnv{
     }
 
     pub fn try_load_rdf(
-        descr: rdf::FileDescription<rdf::EnvironmentFile>, zip_archive: &SharedZipArchive
+        descr: rdf::FileDescription<rdf::EnvironmentFile>,
+        package: &ModelPackage,
+        fetcher: &dyn UrlFetcher,
     ) -> Result<Self, CondaEnvLoadingError>{
         let file_ref: &rdf::FileReference = &descr.source;
-        let inner_path: String = match file_ref{
-            rdf::FileReference::Url(_) => return Err(CondaEnvLoadingError::UrlFileReferenceNotSupportedYet),
-            rdf::FileReference::Path(path) => path.into(),
-        };
-        let conda_env = zip_archive.with_entry(&inner_path, |entry|{
-            CondaEnv::try_load(entry)
+        let conda_env = file_ref.try_read(package, fetcher, |reader|{
+            CondaEnv::try_load(reader)
         })??;
         Ok(conda_env)
     }
@@ -72,15 +70,40 @@ impl CondaEnv{
     pub fn rdf_dump(
         &self,
         zip_file: &mut ModelZipWriter<impl Write + Seek>,
+        role: &str,
     ) -> Result<rdf::EnvironmentFileDescr, ModelPackingError> {
-        let zip_path = rdf::FsPath::unique_suffixed("_environment.yml");
-        zip_file.write_file(&zip_path, |writer| {
+        let zip_path = zip_file.entry_path(&format!("{role}.yml"), "_environment.yml");
+        let (_, sha256) = zip_file.write_file(&zip_path, |writer| {
             serde_yaml::to_writer(writer, &self.raw)
         })?;
         let file_ref = rdf::FileReference::Path(zip_path);
         Ok(rdf::FileDescription{
             source: file_ref.try_into().unwrap(),
-            sha256: None //FIXME
+            sha256: Some(sha256),
         })
     }
 }
+
+#[test]
+fn test_loading_conda_env_from_url(){
+    use crate::url_fetcher::{FetchError, NoFetcher};
+
+    let mut empty_zip = std::io::Cursor::new(Vec::<u8>::new());
+    zip::ZipWriter::new(&mut empty_zip).finish().unwrap();
+    let archive = ModelPackage::from(crate::zip_archive_ext::SharedZipArchive::from_raw_data(empty_zip.into_inner(), "empty".to_owned()));
+
+    let descr: rdf::FileDescription<rdf::EnvironmentFile> = serde_yaml::from_str(
+        "source: https://example.com/environment.yaml"
+    ).unwrap();
+    let fetcher = |url: &rdf::HttpUrl| -> Result<Vec<u8>, FetchError> {
+        assert_eq!(url.as_str(), "https://example.com/environment.yaml");
+        Ok(b"name: my_env\ndependencies: [python]\n".to_vec())
+    };
+    let env = CondaEnv::try_load_rdf(descr.clone(), &archive, &fetcher).unwrap();
+    assert_eq!(env.raw.get("name").and_then(|name| name.as_str()), Some("my_env"));
+
+    assert!(matches!(
+        CondaEnv::try_load_rdf(descr, &archive, &NoFetcher),
+        Err(CondaEnvLoadingError::RdfFileReferenceReadError(RdfFileReferenceReadError::FetchError(_)))
+    ));
+}
@@ -1,13 +1,25 @@
-use std::{borrow::Borrow, io::{Cursor, Read, Seek, Write}, ops::Deref, sync::Arc};
+use std::{borrow::Borrow, io::{Cursor, Seek, Write}, ops::Deref, sync::Arc};
 
 use bioimg_spec::rdf;
 use image::codecs::png::PngEncoder;
 
-use crate::zip_archive_ext::{RdfFileReferenceExt, SharedZipArchive};
+use crate::url_fetcher::UrlFetcher;
+use crate::model_package::ModelPackage;
+use crate::zip_archive_ext::RdfFileReferenceExt;
 use crate::{zip_archive_ext::RdfFileReferenceReadError, zip_writer_ext::ModelZipWriter, zoo_model::ModelPackingError};
 
 #[derive(Clone)]
-pub struct CoverImage(Arc<image::DynamicImage>);
+pub struct CoverImage{
+    image: Arc<image::DynamicImage>,
+    /// The file this cover was loaded from, which is written back as is instead of being re-encoded
+    original: Option<Arc<OriginalCoverFile>>,
+}
+
+struct OriginalCoverFile{
+    /// The path of the file inside the package it came from, if it didn't come from a URL
+    inner_path: Option<String>,
+    bytes: Vec<u8>,
+}
 
 impl CoverImage {
     pub const ALLOWED_WIDTH_TO_HEIGHT_RATIOS: [f32; 2] = [1.0, 2.0];
@@ -24,25 +36,35 @@ impl CoverImage {
         &self,
         zip_file: &mut ModelZipWriter<impl Write + Seek>,
     ) -> Result< rdf::CoverImageSource, ModelPackingError> {
-        let test_tensor_zip_path = rdf::FsPath::unique_suffixed("_cover_image.png");
-        zip_file.write_file(&test_tensor_zip_path, |writer| -> Result<(), ModelPackingError> {
-            let encoder = PngEncoder::new(writer);
-            Ok(self.0.write_with_encoder(encoder)?)
-        })?;
-        Ok(rdf::CoverImageSource::try_from(rdf::FileReference::Path(test_tensor_zip_path)).unwrap())
+        let Some(original) = &self.original else {
+            let cover_zip_path = zip_file.entry_path("covers/cover.png", "_cover_image.png");
+            zip_file.write_file(&cover_zip_path, |writer| -> Result<(), ModelPackingError> {
+                let encoder = PngEncoder::new(writer);
+                Ok(self.image.write_with_encoder(encoder)?)
+            })?;
+            return Ok(rdf::CoverImageSource::try_from(rdf::FileReference::Path(cover_zip_path)).unwrap())
+        };
+        let extension = image::guess_format(&original.bytes).ok()
+            .and_then(|format| format.extensions_str().first().copied())
+            .unwrap_or("png");
+        let cover_zip_path = zip_file.preferred_entry_path(
+            original.inner_path.as_deref(), &format!("covers/cover.{extension}"), &format!("_cover_image.{extension}"),
+        );
+        zip_file.write_file(&cover_zip_path, |writer| writer.write_all(&original.bytes))?;
+        Ok(rdf::CoverImageSource::try_from(rdf::FileReference::Path(cover_zip_path)).unwrap())
     }
 }
 
 impl Borrow<Arc<image::DynamicImage>> for CoverImage{
     fn borrow(&self) -> &Arc<image::DynamicImage> {
-        &self.0
+        &self.image
     }
 }
 
 impl Deref for CoverImage {
     type Target = image::DynamicImage;
     fn deref(&self) -> &Self::Target {
-        &self.0
+        &self.image
     }
 }
 
@@ -63,7 +85,7 @@ impl TryFrom<Arc<image::DynamicImage>> for CoverImage{
         if !Self::is_valid_ratio(ratio) {
             return Err(CoverImageParsingError::BadAspectRatio { ratio });
         }
-        return Ok(Self(img));
+        return Ok(Self{image: img, original: None});
     }
 }
 
@@ -82,17 +104,61 @@ pub enum CoverImageLoadingError{
 impl CoverImage{
     pub fn try_load(
         rdf_cover: rdf::CoverImageSource,
-        archive: &SharedZipArchive,
+        package: &ModelPackage,
+        fetcher: &dyn UrlFetcher,
     ) -> Result<Self, CoverImageLoadingError>{
-        let a = rdf_cover.try_read(archive, |entry|{
+        let inner_path = match rdf_cover.deref(){
+            rdf::FileReference::Path(path) => Some(String::from(path)),
+            rdf::FileReference::Url(_) => None,
+        };
+        let a = rdf_cover.try_read(package, fetcher, |entry|{
             let mut image_bytes = Vec::<u8>::new();
             entry.read_to_end(&mut image_bytes)?;
-            let cursor = Cursor::new(image_bytes);
-            let image = image::io::Reader::new(cursor).with_guessed_format()?.decode()?;
-            Ok(CoverImage::try_from(Arc::new(image))?)
+            let image = image::io::Reader::new(Cursor::new(&image_bytes)).with_guessed_format()?.decode()?;
+            let cover = CoverImage::try_from(Arc::new(image))?;
+            Ok(CoverImage{original: Some(Arc::new(OriginalCoverFile{inner_path, bytes: image_bytes})), ..cover})
         })?;
         return a
     }
 }
 
 
+
+#[test]
+fn test_dumping_keeps_original_cover_files(){
+    use std::io::Read;
+    use crate::url_fetcher::NoFetcher;
+    use crate::zip_archive_ext::SharedZipArchive;
+    use crate::zip_writer_ext::PackingOptions;
+
+    let image = image::DynamicImage::new_rgb8(4, 2);
+    let mut jpeg_bytes = Vec::<u8>::new();
+    image.write_to(&mut Cursor::new(&mut jpeg_bytes), image::ImageFormat::Jpeg).unwrap();
+
+    let mut source_zip = Cursor::new(Vec::<u8>::new());
+    let mut source_writer = ModelZipWriter::new(&mut source_zip);
+    let source_path = rdf::FsPath::try_from("docs/thumbnail.jpg".to_owned()).unwrap();
+    source_writer.write_file(&source_path, |w| w.write_all(&jpeg_bytes)).unwrap();
+    source_writer.finish().unwrap();
+    let package = ModelPackage::Zip(SharedZipArchive::from_raw_data(source_zip.into_inner(), "source".to_owned()));
+
+    let rdf_cover = rdf::CoverImageSource::try_from(rdf::FileReference::Path(source_path)).unwrap();
+    let loaded = CoverImage::try_load(rdf_cover, &package, &NoFetcher).unwrap();
+    let created = CoverImage::try_from(Arc::new(image)).unwrap();
+
+    let mut zip_bytes = Cursor::new(Vec::<u8>::new());
+    let mut writer = ModelZipWriter::with_options(&mut zip_bytes, PackingOptions{deterministic: true, ..Default::default()});
+    let paths: Vec<String> = [loaded, created].iter()
+        .map(|cover| cover.dump(&mut writer).unwrap().to_string())
+        .collect();
+    writer.finish().unwrap();
+    assert_eq!(paths, ["docs/thumbnail.jpg", "covers/cover.png"]);
+
+    let archive = SharedZipArchive::from_raw_data(zip_bytes.into_inner(), "packed".to_owned());
+    let dumped_bytes = archive.with_entry("docs/thumbnail.jpg", |entry| {
+        let mut bytes = vec![];
+        entry.read_to_end(&mut bytes).unwrap();
+        bytes
+    }).unwrap();
+    assert_eq!(dumped_bytes, jpeg_bytes);
+}
@@ -6,6 +6,8 @@ use std::borrow::Borrow;
 use bioimg_spec::rdf::{self, FileReference, HttpUrl};
 
 use crate::{zip_archive_ext::SharedZipArchive, zip_writer_ext::ModelZipWriter, zoo_model::ModelPackingError};
+use crate::model_package::ModelPackage;
+use crate::url_fetcher::{DefaultUrlFetcher, FetchError, UrlFetcher};
 
 #[derive(thiserror::Error, Debug)]
 pub enum FileSourceError{
@@ -13,8 +15,27 @@ pub enum FileSourceError{
     IoError(#[from] std::io::Error),
     #[error("IO error trying to read {path}: {inner}")]
     ZipError{inner: zip::result::ZipError, path: String},
-    #[error("Error downloading file: {reason}")]
-    HttpError{reason: String}
+    #[error("IO error trying to read {path}: {inner}")]
+    LocalFileError{inner: std::io::Error, path: std::path::PathBuf},
+    #[error("Error downloading file: {0}")]
+    FetchError(#[from] FetchError),
+    #[error("Checksum mismatch for {path}: expected sha256 {expected}, found {found}")]
+    Sha256Mismatch{path: String, expected: rdf::Sha256, found: rdf::Sha256},
+}
+
+/// Computes the sha256 of all bytes that get read through it
+struct HashingReader<'r>{
+    inner: &'r mut dyn Read,
+    hasher: sha2::Sha256,
+}
+
+impl Read for HashingReader<'_>{
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        use sha2::Digest;
+        let num_read = self.inner.read(buf)?;
+        self.hasher.update(&buf[..num_read]);
+        Ok(num_read)
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -23,6 +44,9 @@ pub enum FileSource{
     #[cfg(not(target_arch="wasm32"))]
     LocalFile{path: Arc<std::path::Path>},
     FileInZipArchive{archive: SharedZipArchive, inner_path: Arc<str>},
+    /// A file inside an unpacked model package, at `inner_path` relative to `dir`
+    #[cfg(not(target_arch="wasm32"))]
+    FileInDirectory{dir: Arc<std::path::Path>, inner_path: Arc<str>},
     HttpUrl(Arc<HttpUrl>),
 }
 
@@ -40,6 +64,11 @@ impl PartialEq for FileSource{
             ) => {
                 arch_self == arch_other && path_self == path_other
             },
+            #[cfg(not(target_arch="wasm32"))]
+            (
+                Self::FileInDirectory{dir: dir_self, inner_path: path_self},
+                Self::FileInDirectory{dir: dir_other, inner_path: path_other}
+            ) => dir_self == dir_other && path_self == path_other,
             (Self::HttpUrl(self_url), Self::HttpUrl(other_url)) => self_url == other_url,
             (Self::Data{name, data, ..}, Self::Data{name: other_name, data: other_data, ..}) => {
                 name == other_name && Arc::ptr_eq(data, other_data)
@@ -62,6 +91,8 @@ impl Display for FileSource{
             #[cfg(not(target_arch="wasm32"))]
             Self::LocalFile { path } => write!(f, "{}", path.to_string_lossy()),
             Self::FileInZipArchive { inner_path, .. } => write!(f, "*.zip/{inner_path}"), //FIXME? *.zip?
+            #[cfg(not(target_arch="wasm32"))]
+            Self::FileInDirectory { dir, inner_path } => write!(f, "{}", dir.join(&**inner_path).to_string_lossy()),
             Self::HttpUrl(http_url) => write!(f, "{}", http_url.as_str()),
         }
     }
@@ -69,10 +100,26 @@ impl Display for FileSource{
 
 impl FileSource{
     //FIXME: add some cancellation token?
+    /// The path this file had inside the package it came from, or just its name if it didn't come from a package
+    fn original_inner_path(&self) -> Option<String>{
+        match self{
+            Self::Data { name, .. } => name.clone(),
+            #[cfg(not(target_arch="wasm32"))]
+            Self::LocalFile { path } => path.file_name().map(|name| name.to_string_lossy().into_owned()),
+            Self::FileInZipArchive { inner_path, .. } => Some(inner_path.as_ref().to_owned()),
+            #[cfg(not(target_arch="wasm32"))]
+            Self::FileInDirectory { inner_path, .. } => Some(inner_path.as_ref().to_owned()),
+            Self::HttpUrl(_) => None,
+        }
+    }
+
+    /// Copies the contents into the archive, keeping the original path of the file if possible. Otherwise
+    /// `role` is the extension-less path to use in deterministic mode, e.g. `weights/onnx/model`
     fn rdf_dump(
         &self,
         zip_file: &mut ModelZipWriter<impl Write + Seek>,
-    ) -> Result<rdf::FsPath, ModelPackingError> {
+        role: &str,
+    ) -> Result<(rdf::FsPath, rdf::Sha256), ModelPackingError> {
         let extension = match self{
             Self::Data{name, ..} => if let Some(name) = name {
                 name.split(".").last().map(|s| s.to_owned())
@@ -84,15 +131,25 @@ impl FileSource{
             Self::FileInZipArchive { inner_path, .. } => {
                 inner_path.split(".").last().map(|s| s.to_owned())
             },
+            #[cfg(not(target_arch="wasm32"))]
+            Self::FileInDirectory { inner_path, .. } => {
+                inner_path.split(".").last().map(|s| s.to_owned())
+            },
             Self::HttpUrl(url) => {
                 url.path().split(".").last().map(|s| s.to_owned())
             }
         };
-        let output_inner_path = match extension{
-            Some(ext) => rdf::FsPath::unique_suffixed(&format!(".{ext}")),
-            None => rdf::FsPath::unique(),
+        let original_inner_path = self.original_inner_path();
+        let output_inner_path = match extension.filter(|ext| !ext.is_empty() && !ext.contains(['/', '\\'])){
+            Some(ext) => zip_file.preferred_entry_path(original_inner_path.as_deref(), &format!("{role}.{ext}"), &format!(".{ext}")),
+            None => zip_file.preferred_entry_path(original_inner_path.as_deref(), role, ""),
         };
-        zip_file.write_file(&output_inner_path, |writer| -> Result<u64, ModelPackingError>{
+        // open remote files before starting the zip entry so that failed requests don't leave empty entries behind
+        let mut remote_reader = match self{
+            Self::HttpUrl(http_url) => Some(zip_file.fetcher().open(http_url)?),
+            _ => None,
+        };
+        let (_, sha256) = zip_file.write_file(&output_inner_path, |writer| -> Result<u64, ModelPackingError>{
             let copied_bytes: u64 = match self{
                 Self::Data{ data, .. } => {
                     let mut reader = std::io::Cursor::new(&data);
@@ -107,74 +164,77 @@ impl FileSource{
                         std::io::copy(entry, writer)
                     })??
                 },
-                #[cfg(target_arch = "wasm32")]
-                Self::HttpUrl(_http_url) => {
-                    return Err(ModelPackingError::HttpErro { reason: "Downloading in wasm not implemented yet".to_owned() })
-                }
-                #[cfg(not(target_arch = "wasm32"))]
-                Self::HttpUrl(http_url) => {
-                    let response = ureq::get(http_url.as_str()).call()
-                        .map_err(|e| ModelPackingError::HttpErro { reason: e.to_string()})?;
-                    eprintln!("Requesting {http_url} returned result {}", response.status());
-                    if response.status() / 100 != 2{
-                        return Err(ModelPackingError::UnexpectedHttpStatus {
-                            status: response.status(),
-                            url: http_url.as_ref().clone(),
-                        })
-                    }
-                    let mut response_reader = response.into_reader();
-                    std::io::copy(&mut response_reader, writer)? //FIXME!! limit size or whatever
+                #[cfg(not(target_arch="wasm32"))]
+                Self::FileInDirectory { dir, inner_path } => {
+                    std::io::copy(&mut std::fs::File::open(dir.join(&**inner_path))?, writer)?
+                },
+                Self::HttpUrl(_) => {
+                    let reader = remote_reader.as_mut().unwrap();
+                    std::io::copy(reader, writer).map_err(|e| match FetchError::try_from_io(e){
+                        Ok(fetch_err) => ModelPackingError::FetchError(fetch_err),
+                        Err(io_err) => ModelPackingError::IoError(io_err),
+                    })?
                 }
             };
             Ok(copied_bytes)
         })?;
-        Ok(output_inner_path)
+        Ok((output_inner_path, sha256))
     }
 
     pub fn rdf_dump_as_file_reference(
         &self,
         zip_file: &mut ModelZipWriter<impl Write + Seek>,
+        role: &str,
     ) -> Result<rdf::FileReference, ModelPackingError> {
-        let output_inner_path = self.rdf_dump(zip_file)?;
+        let (output_inner_path, _) = self.rdf_dump(zip_file, role)?;
         Ok(rdf::FileReference::Path(output_inner_path))
     }
 
     pub fn dump_as_file_description(
         &self,
         zip_file: &mut ModelZipWriter<impl Write + Seek>,
+        role: &str,
     ) -> Result<rdf::FileDescription, ModelPackingError> {
-        let file_reference = self.rdf_dump_as_file_reference(zip_file)?;
-        Ok(rdf::FileDescription{source: file_reference, sha256: None})
+        let (output_inner_path, sha256) = self.rdf_dump(zip_file, role)?;
+        Ok(rdf::FileDescription{source: rdf::FileReference::Path(output_inner_path), sha256: Some(sha256)})
     }
 }
 
 impl FileSource{
     pub fn from_rdf_file_descr<T: Borrow<FileReference>>(
-        archive: SharedZipArchive, file_reference: &rdf::FileDescription<T>
+        package: ModelPackage, file_reference: &rdf::FileDescription<T>
     ) -> Result<Self, FileSourceError>{
-        Self::from_rdf_file_reference(archive, file_reference.source.borrow())
+        Self::from_rdf_file_reference(package, file_reference.source.borrow())
     }
 
 
     pub fn from_rdf_file_reference(
-        archive: SharedZipArchive, file_reference: &rdf::FileReference
+        package: ModelPackage, file_reference: &rdf::FileReference
     ) -> Result<Self, FileSourceError>{
         Ok(match file_reference{
             rdf::FileReference::Url(url) => Self::HttpUrl(Arc::new(url.clone())),
-            rdf::FileReference::Path(path) => {
-                let path = String::from(path);
-                archive.with_entry(&path, |_| {}).map_err(|e|{
-                    FileSourceError::ZipError{inner: e, path: path.clone()}
-                })?;
-                Self::FileInZipArchive {
-                    archive,
-                    inner_path:  Arc::from(path.as_str())
-                }
-            }
+            rdf::FileReference::Path(path) => package.file_source(&String::from(path))?,
         })
     }
 
+    /// The size of the contents, if it can be known without downloading them
+    pub fn size_hint(&self) -> Option<u64>{
+        match self{
+            Self::Data { data, .. } => Some(data.len() as u64),
+            #[cfg(not(target_arch="wasm32"))]
+            Self::LocalFile { path } => std::fs::metadata(path).ok().map(|meta| meta.len()),
+            Self::FileInZipArchive { archive, inner_path } => archive.with_entry(inner_path, |entry| entry.size()).ok(),
+            #[cfg(not(target_arch="wasm32"))]
+            Self::FileInDirectory { dir, inner_path } => std::fs::metadata(dir.join(&**inner_path)).ok().map(|meta| meta.len()),
+            Self::HttpUrl(_) => None,
+        }
+    }
+
     pub fn read_to_end(&self, buf: &mut Vec<u8>) -> Result<usize, FileSourceError>{
+        self.read_to_end_with_fetcher(buf, &DefaultUrlFetcher::default())
+    }
+
+    pub fn read_to_end_with_fetcher(&self, buf: &mut Vec<u8>, fetcher: &dyn UrlFetcher) -> Result<usize, FileSourceError>{
         match self{
             Self::Data { data, .. } => {
                 let mut reader = std::io::Cursor::new(data);
@@ -187,18 +247,107 @@ impl FileSource{
                     .map_err(|inner| FileSourceError::ZipError { inner, path: inner_path.as_ref().to_owned()})??;
                 Ok(bytes_read)
             },
-            #[cfg(target_arch = "wasm32")]
-            Self::HttpUrl(_http_url) => {
-                Err(FileSourceError::HttpError { reason: "Can't download on was yet; needs to be async".to_owned() })
+            #[cfg(not(target_arch="wasm32"))]
+            Self::FileInDirectory { dir, inner_path } => {
+                let path = dir.join(&**inner_path);
+                let mut file = std::fs::File::open(&path).map_err(|inner| FileSourceError::LocalFileError { inner, path })?;
+                Ok(file.read_to_end(buf)?)
+            },
+            Self::HttpUrl(http_url) => {
+                let mut response_reader = fetcher.open(http_url)?;
+                Ok(response_reader.read_to_end(buf).map_err(|e| FetchError::from_io(http_url, e))?)
+            }
+        }
+    }
+
+    /// Streams the contents of this file through `f` without buffering all of it in memory first
+    pub fn with_reader<F, Out>(&self, f: F) -> Result<Out, FileSourceError>
+    where
+        F: FnOnce(&mut dyn Read) -> Out,
+        Out: 'static,
+    {
+        self.with_reader_and_fetcher(&DefaultUrlFetcher::default(), f)
+    }
+
+    pub fn with_reader_and_fetcher<F, Out>(&self, fetcher: &dyn UrlFetcher, f: F) -> Result<Out, FileSourceError>
+    where
+        F: FnOnce(&mut dyn Read) -> Out,
+        Out: 'static,
+    {
+        match self{
+            Self::Data { data, .. } => Ok(f(&mut std::io::Cursor::new(data))),
+            #[cfg(not(target_arch="wasm32"))]
+            Self::LocalFile { path } => Ok(f(&mut std::io::BufReader::new(std::fs::File::open(path)?))),
+            Self::FileInZipArchive { archive, inner_path } => {
+                archive.with_entry(inner_path, |entry| f(entry))
+                    .map_err(|inner| FileSourceError::ZipError { inner, path: inner_path.as_ref().to_owned()})
+            },
+            #[cfg(not(target_arch="wasm32"))]
+            Self::FileInDirectory { dir, inner_path } => {
+                let path = dir.join(&**inner_path);
+                let file = std::fs::File::open(&path).map_err(|inner| FileSourceError::LocalFileError { inner, path })?;
+                Ok(f(&mut std::io::BufReader::new(file)))
             },
-            #[cfg(not(target_arch = "wasm32"))]
             Self::HttpUrl(http_url) => {
-                let mut response_reader = ureq::get(http_url.as_str())
-                .call()
-                .map_err(|e| FileSourceError::HttpError { reason: e.to_string()})?
-                .into_reader();
-                Ok(response_reader.read_to_end(buf)?)
+                let mut response_reader = fetcher.open(http_url)?;
+                Ok(f(&mut response_reader))
             }
         }
     }
+
+    /// Like [Self::with_reader_and_fetcher], but also checks that the contents hash to `expected`, if given.
+    /// Whatever `f` leaves unread is consumed afterwards so that the whole file gets hashed in a single pass
+    pub fn with_checked_reader<F, Out>(
+        &self, expected: Option<&rdf::Sha256>, fetcher: &dyn UrlFetcher, f: F
+    ) -> Result<Out, FileSourceError>
+    where
+        F: FnOnce(&mut dyn Read) -> Out,
+        Out: 'static,
+    {
+        use sha2::Digest;
+        let Some(expected) = expected else {
+            return self.with_reader_and_fetcher(fetcher, f)
+        };
+        let (out, digest) = self.with_reader_and_fetcher(fetcher, |reader| {
+            let mut hashing_reader = HashingReader{inner: reader, hasher: sha2::Sha256::new()};
+            let out = f(&mut hashing_reader);
+            let digest = std::io::copy(&mut hashing_reader, &mut std::io::sink())
+                .map(|_| -> [u8; 32] { hashing_reader.hasher.finalize().into() });
+            (out, digest)
+        })?;
+        let found = rdf::Sha256::from(digest?);
+        if &found != expected{
+            return Err(FileSourceError::Sha256Mismatch { path: self.to_string(), expected: expected.clone(), found })
+        }
+        Ok(out)
+    }
+}
+
+#[test]
+fn test_dumping_keeps_original_paths(){
+    use crate::zip_writer_ext::PackingOptions;
+
+    let mut source_zip = std::io::Cursor::new(Vec::<u8>::new());
+    let mut source_writer = ModelZipWriter::new(&mut source_zip);
+    for path in ["images/fig1.png", "arch.py"]{
+        let path = rdf::FsPath::try_from(path.to_owned()).unwrap();
+        source_writer.write_file(&path, |w| w.write_all(b"data")).unwrap();
+    }
+    source_writer.finish().unwrap();
+    let archive = SharedZipArchive::from_raw_data(source_zip.into_inner(), "source".to_owned());
+
+    let sources = [
+        FileSource::FileInZipArchive { archive: archive.clone(), inner_path: Arc::from("images/fig1.png") },
+        FileSource::FileInZipArchive { archive, inner_path: Arc::from("arch.py") },
+        FileSource::Data { data: Arc::from(b"one".as_slice()), name: Some("arch.py".to_owned()) },
+        FileSource::Data { data: Arc::from(b"two".as_slice()), name: Some("../escape.txt".to_owned()) },
+    ];
+    let mut writer = ModelZipWriter::with_options(
+        std::io::Cursor::new(Vec::<u8>::new()),
+        PackingOptions{deterministic: true, ..Default::default()},
+    );
+    let paths: Vec<String> = sources.iter()
+        .map(|source| source.rdf_dump_as_file_reference(&mut writer, "attachments/attachment").unwrap().to_string())
+        .collect();
+    assert_eq!(paths, ["images/fig1.png", "arch.py", "arch_2.py", "attachments/attachment.txt"]);
 }
@@ -1,12 +1,14 @@
 use std::borrow::Borrow;
-use std::io::{Cursor, Read, Seek, Write};
+use std::io::{Cursor, Seek, Write};
 use std::sync::Arc;
 
 use bioimg_spec::rdf;
 use image::codecs::png::PngEncoder;
 use image::DynamicImage;
 
-use crate::zip_archive_ext::{RdfFileReferenceExt, RdfFileReferenceReadError, SharedZipArchive};
+use crate::url_fetcher::UrlFetcher;
+use crate::model_package::ModelPackage;
+use crate::zip_archive_ext::{RdfFileReferenceExt, RdfFileReferenceReadError};
 use crate::zip_writer_ext::ModelZipWriter;
 use crate::zoo_model::ModelPackingError;
 
@@ -66,7 +68,7 @@ impl Icon{
             Self::Text(emoji) => return Ok(rdf::Icon::Emoji(emoji.clone())),
             Self::Image(icon_img) => icon_img,
         };
-        let test_tensor_zip_path = rdf::FsPath::unique_suffixed("_icon.png");
+        let test_tensor_zip_path = zip_file.entry_path("icon.png", "_icon.png");
         zip_file.write_file(&test_tensor_zip_path, |writer| -> Result<(), ModelPackingError> {
             let encoder = PngEncoder::new(writer);
             Ok(icon_img.0.write_with_encoder(encoder)?)
@@ -99,13 +101,14 @@ pub enum IconLoadingError{
 impl Icon{
     pub fn try_load(
         rdf_icon: rdf::Icon,
-        archive: &SharedZipArchive,
+        package: &ModelPackage,
+        fetcher: &dyn UrlFetcher,
     ) -> Result<Self, IconLoadingError>{
         let file_ref = match rdf_icon{
             rdf::Icon::Emoji(emoji_icon) => return Ok(Icon::Text(emoji_icon)),
             rdf::Icon::FileRef(file_ref) => file_ref,
         };
-        let out = file_ref.try_read(archive, |entry|{
+        let out = file_ref.try_read(package, fetcher, |entry|{
             let mut image_bytes = Vec::<u8>::new();
             entry.read_to_end(&mut image_bytes)?;
             let cursor = Cursor::new(image_bytes);
@@ -5,13 +5,26 @@ pub mod file_reference;
 pub mod model_interface;
 pub mod model_record;
 pub mod npy_array;
+pub mod processing;
 pub mod package_component;
 pub mod zip_writer_ext;
 pub mod zoo_model;
 pub mod model_weights;
+pub mod model_runner;
+#[cfg(not(target_arch="wasm32"))]
+pub mod onnx_runner;
+pub mod test_model;
+pub mod tiling;
+pub mod shape_inference;
+pub mod tensor_import;
+pub mod sample_tensor;
 pub mod conda_env;
 pub mod file_source;
 pub mod zip_archive_ext;
+pub mod model_package;
+pub mod model_diff;
+pub mod model_validation;
+pub mod url_fetcher;
 
 pub use cover_image::{CoverImage, CoverImageParsingError};
 pub use icon::{Icon, IconImage};
@@ -20,3 +33,4 @@ pub use npy_array::NpyArray;
 pub use model_weights::{WeightsBase, KerasHdf5Weights, TorchscriptWeights, ModelWeights};
 pub use conda_env::CondaEnv;
 pub use file_source::FileSource;
+pub use model_runner::ModelRunner;
@@ -0,0 +1,121 @@
+use bioimg_spec::rdf::model::{ModelDiff, ModelRdfV0_5};
+use serde_json::Value;
+
+use crate::model_package::ModelPackage;
+use crate::zip_archive_ext::SharedZipArchive;
+use crate::zip_writer_ext::{CompressionOptions, PackingOptions};
+use crate::zoo_model::{read_model_rdf, ModelLoadingError, ModelPackingError, ZooModel};
+
+#[derive(thiserror::Error, Debug)]
+pub enum ModelDiffError{
+    #[error("Could not pack model for comparison: {0}")]
+    PackingError(#[from] ModelPackingError),
+    #[error("Could not read packed model: {0}")]
+    LoadingError(Box<ModelLoadingError>),
+}
+
+impl From<ModelLoadingError> for ModelDiffError{
+    fn from(value: ModelLoadingError) -> Self {
+        Self::LoadingError(Box::new(value))
+    }
+}
+
+/// Adds the sha256 of every file in `package` that the serialized model `value` references without one, so
+/// that all files get compared by their contents
+fn with_file_hashes(mut value: Value, package: &ModelPackage) -> Value{
+    let hash_of = |path: &str| -> Option<String> {
+        package.has_entry(path).then(|| package.entry_sha256(path).ok()).flatten().map(|sha256| sha256.to_string())
+    };
+    fn fill_descriptions(value: &mut Value, hash_of: &dyn Fn(&str) -> Option<String>){
+        match value{
+            Value::Object(map) => {
+                let missing_hash = map.get("sha256").is_none_or(|sha256| sha256.is_null());
+                let sha256 = match map.get("source"){
+                    Some(Value::String(path)) if missing_hash => hash_of(path),
+                    _ => None,
+                };
+                if let Some(sha256) = sha256{
+                    map.insert("sha256".to_owned(), Value::String(sha256));
+                }
+                map.values_mut().for_each(|value| fill_descriptions(value, hash_of));
+            },
+            Value::Array(items) => items.iter_mut().for_each(|item| fill_descriptions(item, hash_of)),
+            _ => (),
+        }
+    }
+    // fields that hold a bare file reference rather than a file description
+    let describe = |value: &mut Value| {
+        let Value::String(path) = value else {
+            return
+        };
+        if let Some(sha256) = hash_of(path){
+            *value = serde_json::json!({"source": path, "sha256": sha256});
+        }
+    };
+
+    fill_descriptions(&mut value, &hash_of);
+    if let Some(documentation) = value.get_mut("documentation"){
+        describe(documentation);
+    }
+    if let Some(icon) = value.get_mut("icon"){
+        describe(icon);
+    }
+    if let Some(Value::Array(covers)) = value.get_mut("covers"){
+        covers.iter_mut().for_each(describe);
+    }
+    value
+}
+
+/// Compares the models in two packages, telling files apart by their contents rather than by their paths
+pub fn diff_packages(old: &ModelPackage, new: &ModelPackage) -> Result<ModelDiff, ModelLoadingError>{
+    let serialize = |model_rdf: ModelRdfV0_5| serde_json::to_value(model_rdf).unwrap();
+    let old_value = with_file_hashes(serialize(read_model_rdf(old)?.0), old);
+    let new_value = with_file_hashes(serialize(read_model_rdf(new)?.0), new);
+    Ok(ModelDiff::from_values(&old_value, &new_value))
+}
+
+/// Compares two models by packing them in deterministic mode, so that things like timestamps don't show up as
+/// changes. Any remote files they reference are downloaded in the process
+pub fn diff_zoo_models(old: ZooModel, new: ZooModel) -> Result<ModelDiff, ModelDiffError>{
+    let pack = |model: ZooModel, name: &str| -> Result<ModelPackage, ModelPackingError> {
+        let mut sink = std::io::Cursor::new(Vec::<u8>::new());
+        let options = PackingOptions{
+            deterministic: true,
+            compression: CompressionOptions::stored(),
+            ..Default::default()
+        };
+        model.pack_into_with(&mut sink, options)?;
+        Ok(ModelPackage::Zip(SharedZipArchive::from_raw_data(sink.into_inner(), name.to_owned())))
+    };
+    let old = pack(old, "old")?;
+    let new = pack(new, "new")?;
+    Ok(diff_packages(&old, &new)?)
+}
+
+#[test]
+fn test_diffing_packages_by_file_contents(){
+    use crate::zip_writer_ext::ModelZipWriter;
+    use bioimg_spec::rdf::FsPath;
+
+    let make_package = |readme: &[u8], readme_path: &str| {
+        let mut sink = std::io::Cursor::new(Vec::<u8>::new());
+        let mut writer = ModelZipWriter::new(&mut sink);
+        writer.write_file(&FsPath::try_from(readme_path.to_owned()).unwrap(), |w| w.write_all(readme)).unwrap();
+        writer.finish().unwrap();
+        ModelPackage::Zip(SharedZipArchive::from_raw_data(sink.into_inner(), readme_path.to_owned()))
+    };
+    let hashed = |package: &ModelPackage, readme_path: &str| with_file_hashes(
+        serde_json::json!({"documentation": readme_path, "icon": "🦀", "attachments": [{"source": readme_path, "sha256": null}]}),
+        package,
+    );
+
+    let old = hashed(&make_package(b"# My model", "a_README.md"), "a_README.md");
+    let renamed = hashed(&make_package(b"# My model", "b_README.md"), "b_README.md");
+    let edited = hashed(&make_package(b"# My better model", "a_README.md"), "a_README.md");
+    assert!(ModelDiff::from_values(&old, &renamed).is_empty());
+
+    let diff = ModelDiff::from_values(&old, &edited);
+    let mut paths: Vec<&str> = diff.changes.iter().map(|change| change.path()).collect();
+    paths.sort();
+    assert_eq!(paths, ["attachments[0]", "documentation"]);
+}
@@ -12,13 +12,18 @@ use ndarray_npy::ReadNpyError;
 use crate::axis_size_resolver::{ResolvedAxisSizeExt, SlotResolver};
 use crate::file_source::FileSourceError;
 use crate::npy_array::NpyArray;
-use crate::zip_archive_ext::SharedZipArchive;
+use crate::processing::{self, ProcessingError, Sample};
+use crate::sample_tensor::{SampleTensor, SampleTensorLoadingError};
+use crate::shape_inference::ShapeInferenceError;
+use crate::model_package::ModelPackage;
 use crate::zip_writer_ext::ModelZipWriter;
 use crate::zoo_model::ModelPackingError;
+use crate::url_fetcher::UrlFetcher;
 use crate::FileSource;
 use bioimg_spec::rdf::model::axis_size::QualifiedAxisId;
-use bioimg_spec::rdf::model::{AnyAxisSize, InputAxis, OutputAxis, PreprocessingDescr};
-use bioimg_spec::rdf::model::{self as modelrdf, TensorId};
+use bioimg_spec::rdf::model::{AnyAxisSize, InputAxis, OutputAxis, PreprocessingDescr, ResolvedAxisSize};
+use bioimg_spec::rdf::model::{self as modelrdf, DataType, TensorId};
+use bioimg_spec::rdf::model::tensor_data_descr::{AnyTensorDataDescr, TVs, TensorDataDescr};
 
 use super::axis_size_resolver::AxisSizeResolutionError;
 
@@ -28,6 +33,8 @@ pub enum ModelInterfaceLoadingError{
     FileSourceOpenError(#[from] FileSourceError),
     #[error(transparent)]
     ReadNpyError(#[from] ReadNpyError),
+    #[error("Could not load sample tensor: {0}")]
+    SampleTensorLoadingError(#[from] SampleTensorLoadingError),
 }
 
 #[allow(dead_code)]
@@ -35,6 +42,7 @@ pub enum ModelInterfaceLoadingError{
 pub struct InputSlot <DATA: Borrow<NpyArray>> {
     pub tensor_meta: modelrdf::input_tensor::InputTensorMetadata,
     pub test_tensor: DATA,
+    pub sample_tensor: Option<SampleTensor>,
 }
 
 impl<DATA: Borrow<NpyArray>> Display for InputSlot<DATA>{
@@ -59,29 +67,36 @@ impl InputSlot<Arc<NpyArray>> {
         &self,
         zip_file: &mut ModelZipWriter<impl Write + Seek>,
     ) -> Result<modelrdf::InputTensorDescr, ModelPackingError> {
-        let test_tensor_zip_path = rdf::FsPath::unique_suffixed(&format!("_{}_test_tensor.npy", self.tensor_meta.id));
-        zip_file.write_file(&test_tensor_zip_path, |writer| self.test_tensor.write_npy(writer))?;
+        let test_tensor_zip_path = zip_file.entry_path(
+            &format!("inputs/{}/test_tensor.npy", self.tensor_meta.id),
+            &format!("_{}_test_tensor.npy", self.tensor_meta.id),
+        );
+        let (_, sha256) = zip_file.write_file(&test_tensor_zip_path, |writer| self.test_tensor.write_npy(writer))?;
         Ok(modelrdf::input_tensor::InputTensorDescr{
             meta: self.tensor_meta.clone(),
             test_tensor: rdf::FileDescription{
                 source: test_tensor_zip_path.into(),
-                sha256: None,
+                sha256: Some(sha256),
             },
-            sample_tensor: None, //FIXME
+            sample_tensor: self.sample_tensor.as_ref()
+                .map(|sample| sample.dump(zip_file, &format!("inputs/{}", self.tensor_meta.id), &self.tensor_meta.id))
+                .transpose()?,
         })
     }
 
     pub fn try_from_rdf(
-        rdf: modelrdf::InputTensorDescr, archive: SharedZipArchive
+        rdf: modelrdf::InputTensorDescr, package: ModelPackage, fetcher: &dyn UrlFetcher,
     ) -> Result<Self, ModelInterfaceLoadingError>{
-        let mut test_tensor_raw_data = vec![];
-        FileSource::from_rdf_file_descr(archive, &rdf.test_tensor)?.read_to_end(&mut test_tensor_raw_data)?;
-        //FIXME: there's another copy inside try_load, i think
-        let test_tensor = NpyArray::try_load(&mut test_tensor_raw_data.as_slice())?;
+        let test_tensor = FileSource::from_rdf_file_descr(package.clone(), &rdf.test_tensor)?
+            .with_checked_reader(rdf.test_tensor.sha256.as_ref(), fetcher, |reader| NpyArray::try_load(reader))??;
+        let sample_tensor = rdf.sample_tensor
+            .map(|sample| SampleTensor::try_load(package, &sample, fetcher))
+            .transpose()?;
 
         Ok(Self{
             tensor_meta: rdf.meta,
             test_tensor: Arc::new(test_tensor),
+            sample_tensor,
         })
     }
 }
@@ -110,6 +125,7 @@ impl<DATA: Borrow<NpyArray>> VecInputSlotExt for [InputSlot<DATA>]{
 pub struct OutputSlot<DATA: Borrow<NpyArray>> {
     pub tensor_meta: modelrdf::output_tensor::OutputTensorMetadata,
     pub test_tensor: DATA,
+    pub sample_tensor: Option<SampleTensor>,
 }
 
 impl<DATA: Borrow<NpyArray>> Display for OutputSlot<DATA>{
@@ -134,28 +150,35 @@ impl OutputSlot<Arc<NpyArray>> {
         &self,
         zip_file: &mut ModelZipWriter<impl Write + Seek>,
     ) -> Result<modelrdf::OutputTensorDescr, ModelPackingError> {
-        let test_tensor_zip_path = rdf::FsPath::unique_suffixed(&format!("_{}_test_tensor.npy", self.tensor_meta.id));
-        zip_file.write_file(&test_tensor_zip_path, |writer| self.test_tensor.write_npy(writer))?;
+        let test_tensor_zip_path = zip_file.entry_path(
+            &format!("outputs/{}/test_tensor.npy", self.tensor_meta.id),
+            &format!("_{}_test_tensor.npy", self.tensor_meta.id),
+        );
+        let (_, sha256) = zip_file.write_file(&test_tensor_zip_path, |writer| self.test_tensor.write_npy(writer))?;
         Ok(modelrdf::OutputTensorDescr{
             metadata: self.tensor_meta.clone(),
             test_tensor: rdf::FileDescription{
                 source: test_tensor_zip_path.into(),
-                sha256: None,
+                sha256: Some(sha256),
             },
-            sample_tensor: None, //FIXME
+            sample_tensor: self.sample_tensor.as_ref()
+                .map(|sample| sample.dump(zip_file, &format!("outputs/{}", self.tensor_meta.id), &self.tensor_meta.id))
+                .transpose()?,
         })
     }
 
     pub fn try_from_rdf(
-        rdf: modelrdf::OutputTensorDescr, archive: SharedZipArchive,
+        rdf: modelrdf::OutputTensorDescr, package: ModelPackage, fetcher: &dyn UrlFetcher,
     ) -> Result<Self, ModelInterfaceLoadingError>{
-        let mut test_tensor_raw_data = vec![];
-        FileSource::from_rdf_file_descr(archive, &rdf.test_tensor)?.read_to_end(&mut test_tensor_raw_data)?;
-        //FIXME: there's another copy inside try_load, i think
-        let test_tensor = NpyArray::try_load(&mut test_tensor_raw_data.as_slice())?;
+        let test_tensor = FileSource::from_rdf_file_descr(package.clone(), &rdf.test_tensor)?
+            .with_checked_reader(rdf.test_tensor.sha256.as_ref(), fetcher, |reader| NpyArray::try_load(reader))??;
+        let sample_tensor = rdf.sample_tensor
+            .map(|sample| SampleTensor::try_load(package, &sample, fetcher))
+            .transpose()?;
         Ok(Self{
             tensor_meta: rdf.metadata,
             test_tensor: Arc::new(test_tensor),
+            sample_tensor,
         })
     }
 }
@@ -201,6 +224,8 @@ pub enum TensorValidationError {
     },
     #[error("{0}")]
     AxisSizeResolutionError(#[from] AxisSizeResolutionError),
+    #[error("Test tensor shapes are inconsistent: {0}")]
+    ShapeInferenceError(#[from] ShapeInferenceError),
     #[error("Duplicate tensor id: {0}")]
     DuplicateTensorId(TensorId),
     #[error("Empty model interface inputs")]
@@ -208,7 +233,70 @@ pub enum TensorValidationError {
     #[error("Empty model interface outputs")]
     EmptyOutputs,
     #[error("No tensor with ID {reference}")]
-    InvalidTensorReference{reference: TensorId}
+    InvalidTensorReference{reference: TensorId},
+    #[error("Test tensor of '{tensor_id}' has elements of type {found} but its data is described as {expected}")]
+    MismatchedDataType{tensor_id: TensorId, expected: DataType, found: String},
+    #[error("Test tensor of '{tensor_id}' contains value {value}, which is not one of the described values")]
+    UnexpectedNominalValue{tensor_id: TensorId, value: f64},
+    #[error("Test tensor of '{tensor_id}' contains value {value}, which is outside of the described range ({min:?}, {max:?})")]
+    ValueOutOfRange{tensor_id: TensorId, value: f64, min: Option<f32>, max: Option<f32>},
+}
+
+fn validate_test_tensor_values(
+    tensor_id: &TensorId, descr: &TensorDataDescr, mut values: impl Iterator<Item=f64>
+) -> Result<(), TensorValidationError>{
+    let offending_value = match descr{
+        TensorDataDescr::NominalOrOrdinal(descr) => values.find(|value| match &descr.values{
+            TVs::Ints(ints) => !ints.iter().any(|allowed| *allowed as f64 == *value),
+            TVs::Floats(floats) => !floats.contains(&(*value as f32)),
+            TVs::Bools(bools) => !bools.iter().any(|allowed| *allowed as u8 as f64 == *value),
+            // labels for the values 0, ..., N
+            TVs::Strings(labels) => value.fract() != 0.0 || *value < 0.0 || *value >= labels.len().get() as f64,
+        }),
+        TensorDataDescr::IntervalOrRatio(descr) => {
+            let (min, max) = descr.range;
+            let out_of_range = values.find(|value|{
+                min.is_some_and(|min| *value < min as f64) || max.is_some_and(|max| *value > max as f64)
+            });
+            return match out_of_range{
+                Some(value) => Err(TensorValidationError::ValueOutOfRange { tensor_id: tensor_id.clone(), value, min, max }),
+                None => Ok(()),
+            }
+        },
+    };
+    match offending_value{
+        Some(value) => Err(TensorValidationError::UnexpectedNominalValue { tensor_id: tensor_id.clone(), value }),
+        None => Ok(()),
+    }
+}
+
+/// Checks the element type and values of `test_tensor` against the `data` description of its tensor.
+/// `channel_axis_index` is the position of the channel axis, needed for per-channel descriptions
+fn validate_test_tensor_data(
+    tensor_id: &TensorId,
+    data: &AnyTensorDataDescr,
+    channel_axis_index: Option<usize>,
+    test_tensor: &NpyArray,
+) -> Result<(), TensorValidationError>{
+    let expected = data.data_type().expect("per-channel data types should have been checked when parsing");
+    if test_tensor.dtype() != Some(expected){
+        return Err(TensorValidationError::MismatchedDataType {
+            tensor_id: tensor_id.clone(),
+            expected,
+            found: test_tensor.dtype().map(|dtype| dtype.to_string()).unwrap_or(format!("'{}'", test_tensor.type_code())),
+        })
+    }
+    let values = test_tensor.cast::<f64>();
+    match (data, channel_axis_index){
+        (AnyTensorDataDescr::Single(descr), _) => validate_test_tensor_values(tensor_id, descr, values.iter().copied()),
+        (AnyTensorDataDescr::PerChannel(descrs), Some(channel_axis_index)) => {
+            for (channel, descr) in values.axis_iter(ndarray::Axis(channel_axis_index)).zip(descrs.iter()){
+                validate_test_tensor_values(tensor_id, descr, channel.iter().copied())?;
+            }
+            Ok(())
+        },
+        (AnyTensorDataDescr::PerChannel(descrs), None) => validate_test_tensor_values(tensor_id, &descrs[0], values.iter().copied()),
+    }
 }
 
 #[allow(dead_code)]
@@ -242,6 +330,13 @@ impl<DATA: Borrow<NpyArray>> ModelInterface<DATA> {
     pub fn outputs(&self) -> &rdf::NonEmptyList<OutputSlot<DATA>>{
         &self.outputs
     }
+    /// A resolver over every axis of this interface, including batch axes, taking axis scales into account
+    pub fn axis_resolver(&self) -> Result<SlotResolver, AxisSizeResolutionError>{
+        let scale = |scale: modelrdf::AxisScale| f32::from(scale) as f64;
+        let input_axes = self.inputs.qual_id_axes().map(|(qual_id, axis)| (qual_id, axis.size(), scale(axis.scale())));
+        let output_axes = self.outputs.qual_id_axes().map(|(qual_id, axis)| (qual_id, axis.size(), scale(axis.scale())));
+        SlotResolver::with_scales(input_axes.chain(output_axes))
+    }
     pub fn try_build(inputs: Vec<InputSlot<DATA>>, outputs: Vec<OutputSlot<DATA>>) -> Result<Self, TensorValidationError> {
         let inputs = rdf::NonEmptyList::try_from(inputs).map_err(|_| TensorValidationError::EmptyInputs)?;
         let outputs = rdf::NonEmptyList::try_from(outputs).map_err(|_| TensorValidationError::EmptyOutputs)?;
@@ -265,7 +360,7 @@ impl<DATA: Borrow<NpyArray>> ModelInterface<DATA> {
             .chain(outputs.qual_id_sizes())
             .collect();
 
-        let size_map = SlotResolver::new(axis_sizes)?.solve()?;
+        SlotResolver::new(axis_sizes)?.solve()?;
 
         macro_rules! validate_resolution {( $slots:ident ) => {
             for slot in $slots.iter(){
@@ -278,12 +373,14 @@ impl<DATA: Borrow<NpyArray>> ModelInterface<DATA> {
                             num_described_axes: slot.tensor_meta.axes().len(),
                         });
                     };
-                    if axis.size().is_none(){ // batch i guess?
-                        continue;
+                    // sizes of referencing axes depend on the referenced test tensor, so they are checked on the full shapes below
+                    let own_size: ResolvedAxisSize = match axis.size(){
+                        None | Some(AnyAxisSize::Reference(_)) => continue,
+                        Some(AnyAxisSize::Fixed(fixed)) => fixed.into(),
+                        Some(AnyAxisSize::Parameterized(parameterized)) => parameterized.into(),
                     };
                     let qual_id = QualifiedAxisId{tensor_id: slot.tensor_meta.id.clone(), axis_id: axis.id()};
-                    let resolved = size_map.get(&qual_id).unwrap();
-                    if !resolved.is_compatible_with_extent(*test_tensor_dim_size){
+                    if !own_size.is_compatible_with_extent(*test_tensor_dim_size){
                         return Err(TensorValidationError::IncompatibleAxis {
                             qualified_axis_id: qual_id,
                             test_tensor_dim_index,
@@ -296,6 +393,19 @@ impl<DATA: Borrow<NpyArray>> ModelInterface<DATA> {
         validate_resolution!(inputs);
         validate_resolution!(outputs);
 
+        // tensors without a data description default to unbounded float32 values
+        let default_data: AnyTensorDataDescr = TensorDataDescr::IntervalOrRatio(Default::default()).into();
+        for input in inputs.iter(){
+            let data = input.tensor_meta.data().unwrap_or(&default_data);
+            let channel_axis_index = input.tensor_meta.axes().iter().position(|axis| matches!(axis, InputAxis::Channel(_)));
+            validate_test_tensor_data(&input.tensor_meta.id, data, channel_axis_index, input.test_tensor.borrow())?;
+        }
+        for output in outputs.iter(){
+            let data = output.tensor_meta.data().unwrap_or(&default_data);
+            let channel_axis_index = output.tensor_meta.axes().iter().position(|axis| matches!(axis, OutputAxis::Channel(_)));
+            validate_test_tensor_data(&output.tensor_meta.id, data, channel_axis_index, output.test_tensor.borrow())?;
+        }
+
         for input in inputs.iter(){
             for proc in input.tensor_meta.preprocessing() {
                 let tensor_ref = match proc{
@@ -320,6 +430,148 @@ impl<DATA: Borrow<NpyArray>> ModelInterface<DATA> {
             }
         }
 
-        Ok(Self{inputs, outputs})
+        let interface = Self{inputs, outputs};
+        interface.validate_test_tensor_shapes()?;
+        Ok(interface)
     }
+
+    /// Applies each input's preprocessing to the corresponding entry in `tensors`,
+    /// which must be in the same order as the inputs of this interface
+    pub fn preprocess_inputs<T: Borrow<NpyArray>>(&self, tensors: &[T]) -> Result<Vec<NpyArray>, ProcessingError>{
+        if tensors.len() != self.inputs.len().get(){
+            return Err(ProcessingError::MismatchedNumTensors { expected: self.inputs.len().get(), found: tensors.len() })
+        }
+        let mut sample = Sample::new();
+        for (slot, tensor) in self.inputs.iter().zip(tensors){
+            let axis_ids = slot.tensor_meta.axes().iter().map(|axis| axis.id()).collect();
+            sample.insert(slot.tensor_meta.id.clone(), axis_ids, tensor.borrow());
+        }
+        self.inputs.iter().zip(tensors)
+            .map(|(slot, tensor)| processing::preprocess(&slot.tensor_meta, tensor.borrow(), &sample))
+            .collect()
+    }
+
+    /// Preprocesses the test tensors of the inputs, i.e. what a consumer of the model would feed to the weights
+    pub fn preprocess_test_tensors(&self) -> Result<Vec<NpyArray>, ProcessingError>{
+        let test_tensors: Vec<&NpyArray> = self.inputs.iter().map(|slot| slot.test_tensor.borrow()).collect();
+        self.preprocess_inputs(&test_tensors)
+    }
+
+    /// Applies each output's postprocessing to the corresponding entry in `raw_outputs`.
+    ///
+    /// Tensors referenced by postprocessing steps are resolved from `preprocessed_inputs` and
+    /// `raw_outputs`, which must be in the same order as the inputs and outputs of this interface.
+    pub fn postprocess_outputs<T: Borrow<NpyArray>>(
+        &self, preprocessed_inputs: &[T], raw_outputs: &[T]
+    ) -> Result<Vec<NpyArray>, ProcessingError>{
+        if preprocessed_inputs.len() != self.inputs.len().get(){
+            return Err(ProcessingError::MismatchedNumTensors {
+                expected: self.inputs.len().get(), found: preprocessed_inputs.len()
+            })
+        }
+        if raw_outputs.len() != self.outputs.len().get(){
+            return Err(ProcessingError::MismatchedNumTensors { expected: self.outputs.len().get(), found: raw_outputs.len() })
+        }
+        let mut sample = Sample::new();
+        for (slot, tensor) in self.inputs.iter().zip(preprocessed_inputs){
+            let axis_ids = slot.tensor_meta.axes().iter().map(|axis| axis.id()).collect();
+            sample.insert(slot.tensor_meta.id.clone(), axis_ids, tensor.borrow());
+        }
+        for (slot, tensor) in self.outputs.iter().zip(raw_outputs){
+            let axis_ids = slot.tensor_meta.axes().iter().map(|axis| axis.id()).collect();
+            sample.insert(slot.tensor_meta.id.clone(), axis_ids, tensor.borrow());
+        }
+        self.outputs.iter().zip(raw_outputs)
+            .map(|(slot, tensor)| processing::postprocess(&slot.tensor_meta, tensor.borrow(), &sample))
+            .collect()
+    }
+}
+
+/// Builds an interface from the json descriptions of its tensors (without test tensors), each paired
+/// with the shape of a zero-filled float32 test tensor
+#[cfg(test)]
+pub(crate) fn interface_from_json(
+    inputs: &[(serde_json::Value, &[usize])], outputs: &[(serde_json::Value, &[usize])]
+) -> Result<ModelInterface<NpyArray>, TensorValidationError>{
+    let zeros = |shape: &[usize]| NpyArray::ArrayF32(ndarray::ArrayD::zeros(shape));
+    let inputs = inputs.iter()
+        .map(|(meta, shape)| InputSlot{
+            tensor_meta: serde_json::from_value(meta.clone()).unwrap(), test_tensor: zeros(shape), sample_tensor: None
+        })
+        .collect();
+    let outputs = outputs.iter()
+        .map(|(meta, shape)| OutputSlot{
+            tensor_meta: serde_json::from_value(meta.clone()).unwrap(), test_tensor: zeros(shape), sample_tensor: None
+        })
+        .collect();
+    ModelInterface::try_build(inputs, outputs)
+}
+
+#[test]
+fn test_validate_test_tensor_values(){
+    use serde_json::json;
+
+    let tensor_id: TensorId = "labels".to_owned().try_into().unwrap();
+    let descr = |value: serde_json::Value| -> TensorDataDescr { serde_json::from_value(value).unwrap() };
+    let validate = |descr: &TensorDataDescr, values: &[f64]| validate_test_tensor_values(&tensor_id, descr, values.iter().copied());
+
+    let nominal = descr(json!({"values": [0, 1, 5], "type": "uint8"}));
+    assert!(validate(&nominal, &[0.0, 5.0, 1.0]).is_ok());
+    assert!(matches!(validate(&nominal, &[0.0, 2.0]), Err(TensorValidationError::UnexpectedNominalValue { value: 2.0, .. })));
+
+    // string values are labels for 0, ..., N
+    let labeled = descr(json!({"values": ["background", "cell"], "type": "uint8"}));
+    assert!(validate(&labeled, &[0.0, 1.0]).is_ok());
+    assert!(matches!(validate(&labeled, &[2.0]), Err(TensorValidationError::UnexpectedNominalValue { value: 2.0, .. })));
+
+    let ranged = descr(json!({"type": "float32", "range": [0.0, null]}));
+    assert!(validate(&ranged, &[0.0, 1e9]).is_ok());
+    assert!(matches!(
+        validate(&ranged, &[1.0, -0.5]),
+        Err(TensorValidationError::ValueOutOfRange { value: -0.5, min: Some(0.0), max: None, .. })
+    ));
+}
+
+#[test]
+fn test_validate_test_tensor_data(){
+    use serde_json::json;
+
+    let tensor_id: TensorId = "raw".to_owned().try_into().unwrap();
+    let data = |value: serde_json::Value| -> AnyTensorDataDescr { serde_json::from_value(value).unwrap() };
+    // two channels along axis 1, with values 0..=2 and 10..=12
+    let test_tensor = NpyArray::ArrayU8(ndarray::Array2::from_shape_vec((3, 2), vec![0, 10, 1, 11, 2, 12]).unwrap().into_dyn());
+
+    let per_channel = data(json!([
+        {"type": "uint8", "range": [0, 2]},
+        {"type": "uint8", "range": [10, 12]},
+    ]));
+    validate_test_tensor_data(&tensor_id, &per_channel, Some(1), &test_tensor).unwrap();
+    assert!(matches!(
+        validate_test_tensor_data(&tensor_id, &per_channel, Some(0), &test_tensor),
+        Err(TensorValidationError::ValueOutOfRange { .. })
+    ));
+
+    let float_data = data(json!({"type": "float32"}));
+    assert!(matches!(
+        validate_test_tensor_data(&tensor_id, &float_data, None, &test_tensor),
+        Err(TensorValidationError::MismatchedDataType { expected: DataType::Float32, .. })
+    ));
+
+    // tensors without a data description are expected to hold float32 values
+    let slot = InputSlot{
+        tensor_meta: serde_json::from_value(json!({"id": "raw", "axes": [
+            {"type": "batch"}, {"type": "channel", "channel_names": ["a", "b"]},
+        ]})).unwrap(),
+        test_tensor,
+        sample_tensor: None,
+    };
+    let output = OutputSlot{
+        tensor_meta: serde_json::from_value(json!({"id": "out", "axes": [{"type": "batch"}]})).unwrap(),
+        test_tensor: NpyArray::ArrayF32(ndarray::ArrayD::zeros(ndarray::IxDyn(&[3]))),
+        sample_tensor: None,
+    };
+    assert!(matches!(
+        ModelInterface::try_build(vec![slot], vec![output]),
+        Err(TensorValidationError::MismatchedDataType { expected: DataType::Float32, .. })
+    ));
 }
@@ -0,0 +1,150 @@
+use std::fmt::Display;
+use std::io::Read;
+#[cfg(not(target_arch="wasm32"))]
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+use bioimg_spec::rdf;
+
+use crate::file_source::{FileSource, FileSourceError};
+use crate::zip_archive_ext::SharedZipArchive;
+
+#[derive(thiserror::Error, Debug)]
+pub enum PackageReadError{
+    #[error("{0}")]
+    ZipError(#[from] zip::result::ZipError),
+    #[error("{0}")]
+    IoError(#[from] std::io::Error),
+}
+
+/// Where the files that a model rdf references by relative path are stored: either a packed .zip
+/// archive or an unpacked directory containing the rdf.yaml
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ModelPackage{
+    Zip(SharedZipArchive),
+    #[cfg(not(target_arch="wasm32"))]
+    Dir(Arc<Path>),
+}
+
+impl From<SharedZipArchive> for ModelPackage{
+    fn from(value: SharedZipArchive) -> Self {
+        Self::Zip(value)
+    }
+}
+
+impl Display for ModelPackage{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self{
+            Self::Zip(archive) => write!(f, "{}", archive.identifier()),
+            #[cfg(not(target_arch="wasm32"))]
+            Self::Dir(dir) => write!(f, "{}", dir.to_string_lossy()),
+        }
+    }
+}
+
+/// Where `inner_path` is inside `dir`. Paths with `.` or `..` components, or absolute ones, are refused
+/// so that an rdf can't refer to files outside of its package
+#[cfg(not(target_arch="wasm32"))]
+fn path_in_dir(dir: &Path, inner_path: &str) -> Result<PathBuf, std::io::Error>{
+    let relative = Path::new(inner_path);
+    if !relative.components().all(|comp| matches!(comp, Component::Normal(_))){
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput, format!("'{inner_path}' does not point inside of the model package")
+        ))
+    }
+    Ok(dir.join(relative))
+}
+
+impl ModelPackage{
+    pub fn has_entry(&self, inner_path: &str) -> bool{
+        match self{
+            Self::Zip(archive) => archive.has_entry(inner_path),
+            #[cfg(not(target_arch="wasm32"))]
+            Self::Dir(dir) => path_in_dir(dir, inner_path).is_ok_and(|path| path.is_file()),
+        }
+    }
+
+    pub fn with_entry<F, Out>(&self, inner_path: &str, f: F) -> Result<Out, PackageReadError>
+    where
+        F: FnOnce(&mut dyn Read) -> Out,
+        Out: 'static,
+    {
+        match self{
+            Self::Zip(archive) => Ok(archive.with_entry(inner_path, |entry| f(entry))?),
+            #[cfg(not(target_arch="wasm32"))]
+            Self::Dir(dir) => {
+                let file = std::fs::File::open(path_in_dir(dir, inner_path)?)?;
+                Ok(f(&mut std::io::BufReader::new(file)))
+            },
+        }
+    }
+
+    pub fn read_full_entry(&self, inner_path: &str) -> Result<Vec<u8>, PackageReadError>{
+        let mut data = Vec::<u8>::new();
+        self.with_entry(inner_path, |entry| entry.read_to_end(&mut data).map(|_| data))?
+            .map_err(PackageReadError::from)
+    }
+
+    pub fn entry_sha256(&self, inner_path: &str) -> Result<rdf::Sha256, PackageReadError>{
+        match self{
+            Self::Zip(archive) => Ok(archive.entry_sha256(inner_path)?),
+            #[cfg(not(target_arch="wasm32"))]
+            Self::Dir(_) => {
+                use sha2::Digest;
+                let digest: [u8; 32] = self.with_entry(inner_path, |entry| {
+                    let mut hasher = sha2::Sha256::new();
+                    std::io::copy(entry, &mut hasher).map(|_| hasher.finalize().into())
+                })??;
+                Ok(rdf::Sha256::from(digest))
+            },
+        }
+    }
+
+    /// A `FileSource` pointing at the file at `inner_path`, which must exist
+    pub fn file_source(&self, inner_path: &str) -> Result<FileSource, FileSourceError>{
+        match self{
+            Self::Zip(archive) => {
+                archive.with_entry(inner_path, |_| {}).map_err(|e|{
+                    FileSourceError::ZipError{inner: e, path: inner_path.to_owned()}
+                })?;
+                Ok(FileSource::FileInZipArchive { archive: archive.clone(), inner_path: Arc::from(inner_path) })
+            },
+            #[cfg(not(target_arch="wasm32"))]
+            Self::Dir(dir) => {
+                let path = path_in_dir(dir, inner_path)?;
+                std::fs::metadata(&path).map_err(|inner| FileSourceError::LocalFileError { inner, path })?;
+                Ok(FileSource::FileInDirectory { dir: dir.clone(), inner_path: Arc::from(inner_path) })
+            },
+        }
+    }
+}
+
+#[cfg(not(target_arch="wasm32"))]
+#[test]
+fn test_reading_from_unpacked_package(){
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::create_dir(dir.path().join("inputs")).unwrap();
+    std::fs::write(dir.path().join("inputs/data.txt"), b"hello").unwrap();
+    let package = ModelPackage::Dir(Arc::from(dir.path()));
+
+    assert!(package.has_entry("inputs/data.txt"));
+    assert!(!package.has_entry("inputs"));
+    assert_eq!(package.read_full_entry("inputs/data.txt").unwrap(), b"hello");
+    assert_eq!(
+        package.entry_sha256("inputs/data.txt").unwrap().to_string(),
+        "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824",
+    );
+    let source = package.file_source("inputs/data.txt").unwrap();
+    assert_eq!(source.to_string(), dir.path().join("inputs/data.txt").to_string_lossy());
+    assert!(package.file_source("missing.txt").is_err());
+
+    // files outside of the package can't be reached, even if they exist
+    std::fs::write(dir.path().join("inputs/other.txt"), b"secret").unwrap();
+    let package = ModelPackage::Dir(Arc::from(dir.path().join("inputs").as_path()));
+    for escaping_path in ["../inputs/other.txt", "./other.txt", "/etc/passwd"]{
+        assert!(!package.has_entry(escaping_path), "{escaping_path}");
+        assert!(package.read_full_entry(escaping_path).is_err(), "{escaping_path}");
+        assert!(package.file_source(escaping_path).is_err(), "{escaping_path}");
+    }
+    assert_eq!(package.read_full_entry("other.txt").unwrap(), b"secret");
+}
@@ -0,0 +1,26 @@
+use crate::NpyArray;
+
+#[derive(thiserror::Error, Debug)]
+pub enum ModelRunnerError{
+    #[error("Could not read model weights: {0}")]
+    WeightsReadError(#[from] crate::file_source::FileSourceError),
+    #[error("Expected {expected} tensors, found {found}")]
+    MismatchedNumTensors{expected: usize, found: usize},
+    #[error("Tensor #{tensor_index} has {num_dims} dimensions but {num_axes} axes are described")]
+    MismatchedNumDimensions{tensor_index: usize, num_dims: usize, num_axes: usize},
+    #[error("Tensor #{tensor_index} has elements of type {found} but the network expects {expected}")]
+    MismatchedDataType{tensor_index: usize, expected: String, found: String},
+    #[error("Unsupported tensor data type: {0}")]
+    UnsupportedDataType(String),
+    #[error("Inference backend error: {reason}")]
+    BackendError{reason: String},
+}
+
+/// Something that can execute the network of a model.
+///
+/// Runners only execute the weights: inputs are expected to be already preprocessed
+/// and outputs are returned before postprocessing. Tensors are passed in the same order
+/// as the inputs and outputs of the model, with dimensions in the order of the described axes.
+pub trait ModelRunner{
+    fn run(&mut self, inputs: &[&NpyArray]) -> Result<Vec<NpyArray>, ModelRunnerError>;
+}
@@ -0,0 +1,149 @@
+use std::sync::Arc;
+
+use bioimg_spec::rdf::model::{ModelRdfV0_5, ValidationIssue, ValidationReport};
+
+use crate::model_package::ModelPackage;
+use crate::model_interface::{InputSlot, OutputSlot};
+use crate::model_weights::ModelWeights;
+use crate::url_fetcher::UrlFetcher;
+use crate::zoo_model::{declared_sha256s, read_documentation, read_model_rdf, read_model_rdf_bytes};
+use crate::{CoverImage, FileSource, Icon, ModelInterface, NpyArray};
+
+/// Validates the model in `package`: every problem in its rdf.yaml and, once the description itself is
+/// valid, whatever still keeps the model from loading, e.g. test tensors that don't match their descriptions.
+///
+/// Each part of the model is loaded on its own, so a part that fails to load is reported at its field in
+/// the rdf (e.g. `inputs[0]`) without hiding problems in the other parts. Only inconsistencies between
+/// the inputs and outputs are reported for the description as a whole. Since legacy models are loaded by
+/// upgrading them, their loading problems are reported at the fields of the upgraded description
+pub fn validate_model_package(package: &ModelPackage, fetcher: &dyn UrlFetcher) -> ValidationReport{
+    let model_rdf_bytes = match read_model_rdf_bytes(package){
+        Ok(bytes) => bytes,
+        Err(err) => return ValidationReport{issues: vec![ValidationIssue::error("", err)]},
+    };
+    let mut report = ValidationReport::validate_yaml(&model_rdf_bytes);
+    if !report.is_valid(){
+        return report
+    }
+    match read_model_rdf(package){
+        Ok((model_rdf, _)) => report.issues.extend(loading_issues(model_rdf, package, fetcher)),
+        Err(err) => report.push(ValidationIssue::error("", err)),
+    }
+    report
+}
+
+/// The parts of `model_rdf` that can't be loaded from `package`
+fn loading_issues(model_rdf: ModelRdfV0_5, package: &ModelPackage, fetcher: &dyn UrlFetcher) -> Vec<ValidationIssue>{
+    let mut issues = vec![];
+    for (rdf_path, path, expected) in declared_sha256s(&model_rdf){
+        match package.entry_sha256(&path){
+            Ok(found) if found != expected => issues.push(ValidationIssue::error(
+                rdf_path, format!("Checksum mismatch for {path}: expected sha256 {expected}, found {found}"),
+            )),
+            Ok(_) => (),
+            Err(err) => issues.push(ValidationIssue::error(rdf_path, err)),
+        }
+    }
+    for (idx, cover) in model_rdf.covers.into_iter().enumerate(){
+        if let Err(err) = CoverImage::try_load(cover, package, fetcher){
+            issues.push(ValidationIssue::error(format!("covers[{idx}]"), err));
+        }
+    }
+    for (idx, attachment) in model_rdf.attachments.iter().enumerate(){
+        if let Err(err) = FileSource::from_rdf_file_descr(package.clone(), attachment){
+            issues.push(ValidationIssue::error(format!("attachments[{idx}]"), err));
+        }
+    }
+    if let Some(Err(err)) = model_rdf.icon.map(|icon| Icon::try_load(icon, package, fetcher)){
+        issues.push(ValidationIssue::error("icon", err));
+    }
+    if let Err(err) = read_documentation(&model_rdf.documentation, package, fetcher){
+        issues.push(ValidationIssue::error("documentation", err));
+    }
+    if let Err(err) = ModelWeights::try_from_rdf(model_rdf.weights, package.clone(), fetcher){
+        issues.push(ValidationIssue::error("weights", err));
+    }
+
+    let num_issues = issues.len();
+    let mut input_slots = vec![];
+    for (idx, rdf) in model_rdf.inputs.into_inner().into_iter().enumerate(){
+        match InputSlot::<Arc<NpyArray>>::try_from_rdf(rdf, package.clone(), fetcher){
+            Ok(slot) => input_slots.push(slot),
+            Err(err) => issues.push(ValidationIssue::error(format!("inputs[{idx}]"), err)),
+        }
+    }
+    let mut output_slots = vec![];
+    for (idx, rdf) in model_rdf.outputs.into_inner().into_iter().enumerate(){
+        match OutputSlot::<Arc<NpyArray>>::try_from_rdf(rdf, package.clone(), fetcher){
+            Ok(slot) => output_slots.push(slot),
+            Err(err) => issues.push(ValidationIssue::error(format!("outputs[{idx}]"), err)),
+        }
+    }
+    // inputs and outputs can only be checked against each other once they all loaded
+    if issues.len() != num_issues{
+        return issues
+    }
+    if let Err(err) = ModelInterface::try_build(input_slots, output_slots){
+        issues.push(ValidationIssue::error("", err));
+    }
+    issues
+}
+
+#[cfg(not(target_arch="wasm32"))]
+#[test]
+fn test_validate_model_package(){
+    use bioimg_spec::rdf::model::Severity;
+    use crate::url_fetcher::NoFetcher;
+    use crate::zoo_model::write_relu_model_package;
+
+    let dir = tempfile::tempdir().unwrap();
+    write_relu_model_package(dir.path(), ndarray::arr2(&[[0.0f32, 2.0, 0.0, 4.0]]).into_dyn().into());
+    let package = ModelPackage::Dir(Arc::from(dir.path()));
+    let error_paths = |report: &ValidationReport| -> Vec<String> {
+        report.errors().map(|issue| issue.path.clone()).collect()
+    };
+
+    let report = validate_model_package(&package, &NoFetcher);
+    assert!(report.is_valid(), "{report}");
+
+    // every part that fails to load is reported at its own field
+    std::fs::remove_file(dir.path().join("README.md")).unwrap();
+    std::fs::remove_file(dir.path().join("test_output.npy")).unwrap();
+    let report = validate_model_package(&package, &NoFetcher);
+    assert_eq!(error_paths(&report), ["documentation", "outputs[0]"]);
+
+    // loading is only attempted once the description itself is valid
+    let rdf = std::fs::read_to_string(dir.path().join("rdf.yaml")).unwrap();
+    std::fs::write(dir.path().join("rdf.yaml"), rdf.replace("name: Relu model\n", "")).unwrap();
+    let report = validate_model_package(&package, &NoFetcher);
+    assert_eq!(error_paths(&report), ["name"]);
+
+    // legacy models are validated and loaded through their upgrade
+    write_relu_model_package(dir.path(), ndarray::arr2(&[[0.0f32, 2.0, 0.0, 4.0]]).into_dyn().into());
+    std::fs::write(dir.path().join("rdf.yaml"), r#"
+format_version: 0.4.10
+type: model
+name: Relu model
+description: Zeroes out negative values
+authors: [{name: Jane Doe}]
+cite: [{text: Some paper, doi: 10.1000/xyz123}]
+documentation: README.md
+license: MIT
+timestamp: 2022-03-01T10:00:00
+test_inputs: [test_input.npy]
+test_outputs: [test_output.npy]
+inputs:
+  - {name: raw, axes: bx, data_type: float32, shape: [1, 4]}
+outputs:
+  - {name: activated, axes: bx, data_type: float32, shape: {reference_tensor: raw, scale: [1, 1], offset: [0, 0]}}
+weights:
+  onnx: {source: weights.onnx, opset_version: 13}
+"#).unwrap();
+    let report = validate_model_package(&package, &NoFetcher);
+    assert!(report.is_valid(), "{report}");
+    assert!(report.issues.iter().any(|issue| issue.severity == Severity::Warning && issue.path == "format_version"));
+
+    std::fs::remove_file(dir.path().join("test_input.npy")).unwrap();
+    let report = validate_model_package(&package, &NoFetcher);
+    assert_eq!(error_paths(&report), ["inputs[0]"]);
+}
@@ -3,13 +3,31 @@ use std::io::{Seek, Write};
 use bioimg_spec::rdf;
 use bioimg_spec::rdf::model as modelrdf;
 
-use crate::{conda_env::CondaEnvLoadingError, zip_archive_ext::{RdfFileReferenceReadError, SharedZipArchive}};
+use crate::{conda_env::CondaEnvLoadingError, zip_archive_ext::RdfFileReferenceReadError, model_package::ModelPackage};
+use crate::url_fetcher::{DefaultUrlFetcher, UrlFetcher};
 use crate::{conda_env::CondaEnv, file_source::FileSourceError, zip_writer_ext::ModelZipWriter, zoo_model::ModelPackingError, FileSource};
 
 #[derive(thiserror::Error, Debug)]
 pub enum ModelWeightsError{
     #[error("No model weights provided")]
     NoModels,
+    #[error("Weights '{format}' claim to be converted from '{parent}', which is not among the provided weights")]
+    MissingParent{format: modelrdf::WeightsFormat, parent: modelrdf::WeightsFormat},
+    #[error("Weights '{format}' can't be converted from themselves")]
+    SelfParent{format: modelrdf::WeightsFormat},
+    #[error("Weights parents form a cycle: {}", display_cycle(.cycle))]
+    ParentCycle{cycle: Vec<modelrdf::WeightsFormat>},
+}
+
+fn display_cycle(cycle: &[modelrdf::WeightsFormat]) -> String{
+    let mut out = String::new();
+    for format in cycle.iter().chain(cycle.first()){
+        if !out.is_empty(){
+            out += " -> ";
+        }
+        out += &format.to_string();
+    }
+    out
 }
 
 #[derive(Clone)]
@@ -41,6 +59,73 @@ impl ModelWeights{
     pub fn torchscript(&self) -> Option<&TorchscriptWeights>{
         self.torchscript.as_ref()
     }
+
+    /// The formats of all weights that are present, each with the format it was converted from
+    pub fn formats(&self) -> Vec<(modelrdf::WeightsFormat, Option<modelrdf::WeightsFormat>)>{
+        use modelrdf::WeightsFormat as F;
+        [
+            (F::KerasHdf5, self.keras_hdf5.as_ref().map(|w| &w.weights)),
+            (F::Onnx, self.onnx.as_ref().map(|w| &w.weights)),
+            (F::PytorchStateDict, self.pytorch_state_dict.as_ref().map(|w| &w.weights)),
+            (F::TensorflowJs, self.tensorflow_js.as_ref().map(|w| &w.weights)),
+            (F::TensorflowSavedModelBundle, self.tensorflow_saved_model_bundle.as_ref().map(|w| &w.weights)),
+            (F::Torchscript, self.torchscript.as_ref().map(|w| &w.weights)),
+        ]
+        .into_iter()
+        .filter_map(|(format, base)| base.map(|base| (format, base.parent)))
+        .collect()
+    }
+
+    /// All files backing these weights, including the architecture file of pytorch state dicts
+    pub fn sources(&self) -> Vec<&FileSource>{
+        let mut sources: Vec<&FileSource> = [
+            self.keras_hdf5.as_ref().map(|w| &w.weights.source),
+            self.onnx.as_ref().map(|w| &w.weights.source),
+            self.pytorch_state_dict.as_ref().map(|w| &w.weights.source),
+            self.tensorflow_js.as_ref().map(|w| &w.weights.source),
+            self.tensorflow_saved_model_bundle.as_ref().map(|w| &w.weights.source),
+            self.torchscript.as_ref().map(|w| &w.weights.source),
+        ].into_iter().flatten().collect();
+        if let Some(PytorchArch::FromFile { file_source, .. }) = self.pytorch_state_dict.as_ref().map(|w| &w.architecture){
+            sources.push(file_source);
+        }
+        sources
+    }
+
+    /// Combined size of all weights files, if it can be known without downloading anything
+    pub fn size_hint(&self) -> Option<u64>{
+        self.sources().into_iter().map(|source| source.size_hint()).sum()
+    }
+
+    fn check_parents(&self) -> Result<(), ModelWeightsError>{
+        let formats = self.formats();
+        let parent_of = |format: modelrdf::WeightsFormat| -> Option<modelrdf::WeightsFormat> {
+            formats.iter().find(|(f, _)| *f == format).and_then(|(_, parent)| *parent)
+        };
+        for (format, parent) in formats.iter().copied(){
+            let Some(parent) = parent else {
+                continue
+            };
+            if parent == format{
+                return Err(ModelWeightsError::SelfParent { format })
+            }
+            if !formats.iter().any(|(f, _)| *f == parent){
+                return Err(ModelWeightsError::MissingParent { format, parent })
+            }
+        }
+        for (start, _) in formats.iter().copied(){
+            let mut chain = vec![start];
+            let mut current = start;
+            while let Some(parent) = parent_of(current){
+                if let Some(cycle_start) = chain.iter().position(|f| *f == parent){
+                    return Err(ModelWeightsError::ParentCycle { cycle: chain.split_off(cycle_start) })
+                }
+                chain.push(parent);
+                current = parent;
+            }
+        }
+        Ok(())
+    }
 }
 
 
@@ -61,14 +146,16 @@ impl ModelWeights{
         && torchscript.is_none() {
             return Err(ModelWeightsError::NoModels)
         }
-        Ok(Self{
+        let weights = Self{
             keras_hdf5,
             onnx,
             pytorch_state_dict,
             tensorflow_js,
             tensorflow_saved_model_bundle,
             torchscript,
-        })
+        };
+        weights.check_parents()?;
+        Ok(weights)
     }
 
     pub fn rdf_dump(
@@ -104,35 +191,41 @@ impl ModelWeights{
 
     pub fn try_from_rdf(
         weights_rdf: modelrdf::WeightsDescr,
-        archive: SharedZipArchive,
+        package: ModelPackage,
+        fetcher: &dyn UrlFetcher,
     ) -> Result<Self, ModelWeightsLoadingError>{
         let weights = weights_rdf.into_inner();
-        Ok(Self{
-            keras_hdf5: weights.keras_hdf5
-                .map(|rdf| KerasHdf5Weights::try_from_rdf(rdf, archive.clone()))
+        Ok(Self::new(
+            weights.keras_hdf5
+                .map(|rdf| KerasHdf5Weights::try_from_rdf(rdf, package.clone()))
                 .transpose()?,
-            onnx: weights.onnx
-                .map(|rdf| OnnxWeights::try_from_rdf(rdf, archive.clone()))
+            weights.onnx
+                .map(|rdf| OnnxWeights::try_from_rdf(rdf, package.clone()))
                 .transpose()?,
-            pytorch_state_dict: weights.pytorch_state_dict
-                .map(|rdf| PytorchStateDictWeights::try_from_rdf(rdf, archive.clone()))
+            weights.pytorch_state_dict
+                .map(|rdf| PytorchStateDictWeights::try_from_rdf(rdf, package.clone(), fetcher))
                 .transpose()?,
-            tensorflow_js: weights.tensorflow_js
-                .map(|rdf| TensorflowJsWeights::try_from_rdf(rdf, archive.clone()))
+            weights.tensorflow_js
+                .map(|rdf| TensorflowJsWeights::try_from_rdf(rdf, package.clone()))
                 .transpose()?,
-            tensorflow_saved_model_bundle: weights.tensorflow_saved_model_bundle
-                .map(|rdf| TensorflowSavedModelBundleWeights::try_from_rdf(rdf, archive.clone()))
+            weights.tensorflow_saved_model_bundle
+                .map(|rdf| TensorflowSavedModelBundleWeights::try_from_rdf(rdf, package.clone(), fetcher))
                 .transpose()?,
-            torchscript: weights.torchscript
-                .map(|rdf| TorchscriptWeights::try_from_rdf(rdf, archive))
+            weights.torchscript
+                .map(|rdf| TorchscriptWeights::try_from_rdf(rdf, package))
                 .transpose()?,
-        })
+        )?)
     }
 }
 #[derive(Clone)]
 pub struct WeightsBase{
     pub source: FileSource,
     pub authors: Option<Vec<rdf::Author2>>,
+    /// The weights format these weights were converted from, if any
+    pub parent: Option<modelrdf::WeightsFormat>,
+    pub comment: Option<rdf::BoundedString<0, 512>>,
+    /// The checksum declared for `source`, verified whenever the weights are read rather than on load
+    pub sha256: Option<rdf::Sha256>,
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -142,31 +235,55 @@ pub enum ModelWeightsLoadingError{
     #[error("Could not retrieve file from zip: {0}")]
     RdfFileReferenceReadError(#[from] RdfFileReferenceReadError),
     #[error("Could not parse conda env: {0}")]
-    CondaEnvLoadingError(#[from] CondaEnvLoadingError)
+    CondaEnvLoadingError(#[from] CondaEnvLoadingError),
+    #[error("Inconsistent weights: {0}")]
+    InconsistentWeights(#[from] ModelWeightsError),
 }
 
 impl WeightsBase{
     fn rdf_dump(
         &self,
         zip_file: &mut ModelZipWriter<impl Write + Seek>,
+        format: modelrdf::WeightsFormat,
     ) -> Result<modelrdf::WeightsDescrBase, ModelPackingError> {
+        let file_descr = self.source.dump_as_file_description(zip_file, &format!("weights/{format}/model"))?;
+        match (&self.sha256, &file_descr.sha256){
+            (Some(expected), Some(found)) if expected != found => return Err(ModelPackingError::Sha256Mismatch {
+                path: self.source.to_string(), expected: expected.clone(), found: found.clone()
+            }),
+            _ => (),
+        }
         Ok(modelrdf::WeightsDescrBase{
-            source: self.source.rdf_dump_as_file_reference(zip_file)?,
+            source: file_descr.source,
             authors: self.authors.clone(),
-            parent: None, //FIXME
-            sha256: None, //FIXME
+            parent: self.parent,
+            comment: self.comment.clone(),
+            sha256: file_descr.sha256,
         })
     }
 
     fn try_from_rdf(
         rdf_weights_base: modelrdf::WeightsDescrBase,
-        archive: SharedZipArchive,
+        package: ModelPackage,
     ) -> Result<Self, ModelWeightsLoadingError>{
         Ok(Self{
             authors: rdf_weights_base.authors,
-            source: FileSource::from_rdf_file_reference(archive, &rdf_weights_base.source)?
+            parent: rdf_weights_base.parent,
+            comment: rdf_weights_base.comment,
+            sha256: rdf_weights_base.sha256,
+            source: FileSource::from_rdf_file_reference(package, &rdf_weights_base.source)?
         })
     }
+
+    /// Reads the weights into `buf`, checking them against the declared sha256
+    pub fn read_to_end(&self, buf: &mut Vec<u8>) -> Result<usize, FileSourceError>{
+        self.read_to_end_with_fetcher(buf, &DefaultUrlFetcher::default())
+    }
+
+    pub fn read_to_end_with_fetcher(&self, buf: &mut Vec<u8>, fetcher: &dyn UrlFetcher) -> Result<usize, FileSourceError>{
+        self.source.with_checked_reader(self.sha256.as_ref(), fetcher, |reader| reader.read_to_end(buf))?
+            .map_err(FileSourceError::from)
+    }
 }
 
 #[derive(Clone)]
@@ -178,7 +295,7 @@ impl KerasHdf5Weights{
     fn rdf_dump(
         &self, zip_file: &mut ModelZipWriter<impl Write + Seek>
     ) -> Result<modelrdf::KerasHdf5WeightsDescr, ModelPackingError> {
-        let weights = self.weights.rdf_dump(zip_file)?;
+        let weights = self.weights.rdf_dump(zip_file, modelrdf::WeightsFormat::KerasHdf5)?;
         Ok(modelrdf::KerasHdf5WeightsDescr{
             base: weights,
             tensorflow_version: self.tensorflow_version.clone(),
@@ -186,9 +303,9 @@ impl KerasHdf5Weights{
     }
 
     pub fn try_from_rdf(
-        rdf: modelrdf::KerasHdf5WeightsDescr, archive: SharedZipArchive
+        rdf: modelrdf::KerasHdf5WeightsDescr, package: ModelPackage
     ) -> Result<Self, ModelWeightsLoadingError>{
-        let weights = WeightsBase::try_from_rdf(rdf.base, archive)?;
+        let weights = WeightsBase::try_from_rdf(rdf.base, package)?;
         Ok(Self{
             weights,
             tensorflow_version: rdf.tensorflow_version,
@@ -206,7 +323,7 @@ impl OnnxWeights{
     fn rdf_dump(
         &self, zip_file: &mut ModelZipWriter<impl Write + Seek>
     ) -> Result<modelrdf::OnnxWeightsDescr, ModelPackingError> {
-        let weights = self.weights.rdf_dump(zip_file)?;
+        let weights = self.weights.rdf_dump(zip_file, modelrdf::WeightsFormat::Onnx)?;
         Ok(modelrdf::OnnxWeightsDescr{
             base: weights,
             opset_version: self.opset_version.clone(),
@@ -214,9 +331,9 @@ impl OnnxWeights{
     }
 
     pub fn try_from_rdf(
-        rdf: modelrdf::OnnxWeightsDescr, archive: SharedZipArchive
+        rdf: modelrdf::OnnxWeightsDescr, package: ModelPackage
     ) -> Result<Self, ModelWeightsLoadingError>{
-        let weights = WeightsBase::try_from_rdf(rdf.base, archive)?;
+        let weights = WeightsBase::try_from_rdf(rdf.base, package)?;
         Ok(Self{
             weights,
             opset_version: rdf.opset_version,
@@ -241,7 +358,7 @@ impl PytorchArch{
     ) -> Result<modelrdf::PytorchArchitectureDescr, ModelPackingError> {
         match self{
             Self::FromFile { file_source: file_descr, callable, kwargs } => {
-                let file_descr = file_descr.dump_as_file_description(zip_file)?;
+                let file_descr = file_descr.dump_as_file_description(zip_file, "weights/pytorch_state_dict/architecture")?;
                 Ok(modelrdf::PytorchArchitectureDescr::FromFileDescr(
                     modelrdf::weights::PyTorchArchitectureFromFileDescr{
                         file_descr,
@@ -256,11 +373,11 @@ impl PytorchArch{
         }
     }
 
-    pub fn try_from_rdf(archive: SharedZipArchive, rdf: modelrdf::PytorchArchitectureDescr) -> Result<Self, ModelWeightsLoadingError>{
+    pub fn try_from_rdf(package: ModelPackage, rdf: modelrdf::PytorchArchitectureDescr) -> Result<Self, ModelWeightsLoadingError>{
         match rdf{
             modelrdf::PytorchArchitectureDescr::FromFileDescr(from_file) => {
                 Ok(Self::FromFile {
-                    file_source: FileSource::from_rdf_file_descr(archive, &from_file.file_descr)?,
+                    file_source: FileSource::from_rdf_file_descr(package, &from_file.file_descr)?,
                     callable: from_file.callable,
                     kwargs: from_file.kwargs,
                 })
@@ -285,26 +402,27 @@ impl PytorchStateDictWeights{
         &self, zip_file: &mut ModelZipWriter<impl Write + Seek>
     ) -> Result<modelrdf::PytorchStateDictWeightsDescr, ModelPackingError> {
         Ok(modelrdf::PytorchStateDictWeightsDescr{
-            base: self.weights.rdf_dump(zip_file)?,
+            base: self.weights.rdf_dump(zip_file, modelrdf::WeightsFormat::PytorchStateDict)?,
             architecture: self.architecture.rdf_dump(zip_file)?,
             pytorch_version: self.pytorch_version.clone(),
             dependencies: self.dependencies.as_ref().map(|env|{
-                env.rdf_dump(zip_file)
+                env.rdf_dump(zip_file, "weights/pytorch_state_dict/environment")
             }).transpose()?,
         })
     }
 
     pub fn try_from_rdf(
         rdf: modelrdf::PytorchStateDictWeightsDescr,
-        archive: SharedZipArchive,
+        package: ModelPackage,
+        fetcher: &dyn UrlFetcher,
     ) -> Result<Self, ModelWeightsLoadingError>{
-        let weights = WeightsBase::try_from_rdf(rdf.base, archive.clone())?;
+        let weights = WeightsBase::try_from_rdf(rdf.base, package.clone())?;
         Ok(Self{
             weights,
-            architecture: PytorchArch::try_from_rdf(archive.clone(), rdf.architecture)?,
+            architecture: PytorchArch::try_from_rdf(package.clone(), rdf.architecture)?,
             pytorch_version: rdf.pytorch_version,
             dependencies: rdf.dependencies
-                .map(|value| CondaEnv::try_load_rdf(value, &archive))
+                .map(|value| CondaEnv::try_load_rdf(value, &package, fetcher))
                 .transpose()?
         })
     }
@@ -323,15 +441,15 @@ impl TensorflowJsWeights{
         &self, zip_file: &mut ModelZipWriter<impl Write + Seek>
     ) -> Result<modelrdf::TensorflowJsWeightsDescr, ModelPackingError> {
         Ok(modelrdf::TensorflowJsWeightsDescr{
-            base: self.weights.rdf_dump(zip_file)?,
+            base: self.weights.rdf_dump(zip_file, modelrdf::WeightsFormat::TensorflowJs)?,
             tensorflow_version: self.tensorflow_version.clone(),
         })
     }
 
     pub fn try_from_rdf(
-        rdf: modelrdf::TensorflowJsWeightsDescr, archive: SharedZipArchive,
+        rdf: modelrdf::TensorflowJsWeightsDescr, package: ModelPackage,
     ) -> Result<Self, ModelWeightsLoadingError>{
-        let weights = WeightsBase::try_from_rdf(rdf.base, archive)?;
+        let weights = WeightsBase::try_from_rdf(rdf.base, package)?;
         Ok(Self{
             weights,
             tensorflow_version: rdf.tensorflow_version,
@@ -352,24 +470,25 @@ impl TensorflowSavedModelBundleWeights{
         &self, zip_file: &mut ModelZipWriter<impl Write + Seek>
     ) -> Result<modelrdf::TensorflowSavedModelBundleWeightsDescr, ModelPackingError> {
         Ok(modelrdf::TensorflowSavedModelBundleWeightsDescr{
-            base: self.weights.rdf_dump(zip_file)?,
+            base: self.weights.rdf_dump(zip_file, modelrdf::WeightsFormat::TensorflowSavedModelBundle)?,
             tensorflow_version: self.tensorflow_version.clone(),
             dependencies: self.dependencies.as_ref().map(|env|{
-                env.rdf_dump(zip_file)
+                env.rdf_dump(zip_file, "weights/tensorflow_saved_model_bundle/environment")
             }).transpose()?,
         })
     }
 
     pub fn try_from_rdf(
         rdf: modelrdf::TensorflowSavedModelBundleWeightsDescr,
-        archive: SharedZipArchive,
+        package: ModelPackage,
+        fetcher: &dyn UrlFetcher,
     ) -> Result<Self, ModelWeightsLoadingError>{
-        let weights = WeightsBase::try_from_rdf(rdf.base, archive.clone())?;
+        let weights = WeightsBase::try_from_rdf(rdf.base, package.clone())?;
         Ok(Self{
             weights,
             tensorflow_version: rdf.tensorflow_version,
             dependencies: rdf.dependencies
-                .map(|value| CondaEnv::try_load_rdf(value, &archive))
+                .map(|value| CondaEnv::try_load_rdf(value, &package, fetcher))
                 .transpose()?
         })
     }
@@ -386,19 +505,52 @@ impl TorchscriptWeights {
         &self, zip_file: &mut ModelZipWriter<impl Write + Seek>
     ) -> Result<modelrdf::TorchscriptWeightsDescr, ModelPackingError> {
         Ok(modelrdf::TorchscriptWeightsDescr{
-            base: self.weights.rdf_dump(zip_file)?,
+            base: self.weights.rdf_dump(zip_file, modelrdf::WeightsFormat::Torchscript)?,
             pytorch_version: self.pytorch_version.clone(),
         })
     }
 
     pub fn try_from_rdf(
         rdf: modelrdf::TorchscriptWeightsDescr,
-        archive: SharedZipArchive,
+        package: ModelPackage,
     ) -> Result<Self, ModelWeightsLoadingError>{
-        let weights = WeightsBase::try_from_rdf(rdf.base, archive)?;
+        let weights = WeightsBase::try_from_rdf(rdf.base, package)?;
         Ok(Self{
             weights,
             pytorch_version: rdf.pytorch_version,
         })
     }
 }
+
+#[test]
+fn test_weights_parent_validation(){
+    use modelrdf::WeightsFormat as F;
+
+    let base = |parent: Option<F>| WeightsBase{
+        source: FileSource::Data { data: std::sync::Arc::from(&b"weights"[..]), name: None },
+        authors: None,
+        parent,
+        comment: None,
+        sha256: None,
+    };
+    let version: rdf::Version = "1.13.0".parse().unwrap();
+    let torchscript = |parent| TorchscriptWeights{ weights: base(parent), pytorch_version: version.clone() };
+    let keras = |parent| KerasHdf5Weights{ weights: base(parent), tensorflow_version: version.clone() };
+    let build = |keras_parent, torchscript_parent| {
+        ModelWeights::new(Some(keras(keras_parent)), None, None, None, None, Some(torchscript(torchscript_parent)))
+    };
+
+    assert!(build(None, Some(F::KerasHdf5)).is_ok());
+    assert!(matches!(
+        build(None, Some(F::Onnx)),
+        Err(ModelWeightsError::MissingParent { format: F::Torchscript, parent: F::Onnx })
+    ));
+    assert!(matches!(
+        build(Some(F::KerasHdf5), None),
+        Err(ModelWeightsError::SelfParent { format: F::KerasHdf5 })
+    ));
+    let Err(ModelWeightsError::ParentCycle { cycle }) = build(Some(F::Torchscript), Some(F::KerasHdf5)) else {
+        panic!("Expected a cycle to be detected");
+    };
+    assert_eq!(cycle, vec![F::KerasHdf5, F::Torchscript]);
+}
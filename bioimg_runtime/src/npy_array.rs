@@ -1,6 +1,9 @@
-use ndarray_npy::{ReadNpyError, WriteNpyExt, ReadNpyExt};
+use bioimg_spec::rdf::model::DataType;
+use half::f16;
+use ndarray::{ArrayD, ArrayViewD, IxDyn, ShapeBuilder};
+use ndarray_npy::{ReadNpyError, WriteNpyError, WriteNpyExt, ReadNpyExt, ViewNpyExt};
 use std::{
-    io::{Read, Seek},
+    io::{Read, Write},
     fmt::Display,
     sync::Arc,
 };
@@ -14,45 +17,566 @@ impl Display for UnsupportedNumpyElementType {
     }
 }
 
+#[derive(thiserror::Error, Debug)]
+#[error("Bad npy header: {reason}")]
+pub struct NpyHeaderParsingError{
+    pub reason: String,
+}
+
+impl NpyHeaderParsingError{
+    fn new(reason: impl Into<String>) -> Self{
+        Self{reason: reason.into()}
+    }
+}
+
+const NPY_MAGIC: &[u8] = b"\x93NUMPY";
+
+/// The metadata at the start of a `.npy` file, describing how to interpret the data that follows it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NpyHeader{
+    /// The numpy type descriptor, e.g. `<f4`
+    pub descr: String,
+    pub fortran_order: bool,
+    pub shape: Vec<usize>,
+}
+
+impl NpyHeader{
+    /// Reads the header from the start of `reader`, also returning the raw bytes that were consumed
+    /// so that they can be replayed to a full `.npy` parser
+    pub fn read(reader: &mut impl Read) -> Result<(Self, Vec<u8>), ReadNpyError>{
+        let mut raw = vec![0u8; NPY_MAGIC.len() + 2];
+        reader.read_exact(&mut raw)?;
+        if &raw[..NPY_MAGIC.len()] != NPY_MAGIC{
+            return Err(ReadNpyError::ParseData(Box::new(NpyHeaderParsingError::new("missing magic string"))))
+        }
+        let header_len = match raw[NPY_MAGIC.len()]{
+            1 => {
+                let mut len_bytes = [0u8; 2];
+                reader.read_exact(&mut len_bytes)?;
+                raw.extend_from_slice(&len_bytes);
+                u16::from_le_bytes(len_bytes) as usize
+            },
+            2 | 3 => {
+                let mut len_bytes = [0u8; 4];
+                reader.read_exact(&mut len_bytes)?;
+                raw.extend_from_slice(&len_bytes);
+                u32::from_le_bytes(len_bytes) as usize
+            },
+            version => return Err(ReadNpyError::ParseData(Box::new(
+                NpyHeaderParsingError::new(format!("unsupported format version {version}"))
+            ))),
+        };
+        let header_start = raw.len();
+        raw.resize(header_start + header_len, 0);
+        reader.read_exact(&mut raw[header_start..])?;
+        let header = Self::parse(&String::from_utf8_lossy(&raw[header_start..]))
+            .map_err(|err| ReadNpyError::ParseData(Box::new(err)))?;
+        Ok((header, raw))
+    }
+
+    fn parse(dict: &str) -> Result<Self, NpyHeaderParsingError>{
+        fn value_of<'a>(dict: &'a str, key: &str) -> Result<&'a str, NpyHeaderParsingError>{
+            let key_start = dict.find(&format!("'{key}'"))
+                .ok_or_else(|| NpyHeaderParsingError::new(format!("missing key '{key}'")))?;
+            let after_key = &dict[key_start + key.len() + 2..];
+            let colon = after_key.find(':').ok_or_else(|| NpyHeaderParsingError::new(format!("no value for '{key}'")))?;
+            Ok(after_key[colon + 1..].trim_start())
+        }
+
+        let descr = value_of(dict, "descr")?;
+        let descr = descr.strip_prefix('\'')
+            .and_then(|d| d.split('\'').next())
+            .ok_or_else(|| NpyHeaderParsingError::new("only simple type descriptors are supported"))?
+            .to_owned();
+
+        let fortran_order = value_of(dict, "fortran_order")?;
+        let fortran_order = if fortran_order.starts_with("True") {
+            true
+        } else if fortran_order.starts_with("False") {
+            false
+        } else {
+            return Err(NpyHeaderParsingError::new("bad value for 'fortran_order'"))
+        };
+
+        let shape = value_of(dict, "shape")?;
+        let shape = shape.strip_prefix('(')
+            .and_then(|s| s.split(')').next())
+            .ok_or_else(|| NpyHeaderParsingError::new("bad value for 'shape'"))?
+            .split(',')
+            .map(|extent| extent.trim())
+            .filter(|extent| !extent.is_empty())
+            .map(|extent| extent.trim_end_matches('L').parse::<usize>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| NpyHeaderParsingError::new("bad value for 'shape'"))?;
+
+        Ok(Self{descr, fortran_order, shape})
+    }
+
+    /// The type code of `descr`, without its byte order character
+    pub fn type_code(&self) -> &str{
+        self.descr.trim_start_matches(['<', '>', '|', '='])
+    }
+
+    /// Whether multi-byte elements are stored most significant byte first
+    pub fn is_big_endian(&self) -> bool{
+        self.descr.starts_with('>')
+    }
+
+    /// Whether the elements are stored in the byte order of this machine
+    pub fn is_native_endian(&self) -> bool{
+        match self.descr.chars().next(){
+            Some('>') => cfg!(target_endian = "big"),
+            Some('<') => cfg!(target_endian = "little"),
+            _ => true,
+        }
+    }
+}
+
+/// A single element of any [NpyElement] type, widened so that it holds the exact value
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NpyScalar {
+    Int(i128),
+    Float(f64),
+}
+
+/// An element type that an [NpyArray] can hold
+pub trait NpyElement: Copy + 'static {
+    /// The numpy type code of this element, without the byte order character
+    const TYPE_CODE: &'static str;
+    /// The data type used to describe tensors of this element in a model RDF, if there is one
+    const DATA_TYPE: Option<DataType>;
+
+    fn to_scalar(self) -> NpyScalar;
+    /// Converts `value` into this element type with the semantics of an `as` cast, i.e. floats are
+    /// truncated and saturated while integers wrap around
+    fn from_scalar(value: NpyScalar) -> Self;
+
+    fn read_npy(reader: impl Read) -> Result<ArrayD<Self>, ReadNpyError>;
+    fn write_npy(arr: &ArrayD<Self>, writer: impl Write) -> Result<(), WriteNpyError>;
+    /// Views the elements of the `.npy` file in `buf` in place, if they are stored as this type
+    /// in native byte order and properly aligned
+    fn view_npy(buf: &[u8]) -> Option<ArrayViewD<'_, Self>>;
+
+    fn downcast_ref(arr: &NpyArray) -> Option<&ArrayD<Self>>;
+}
+
 #[rustfmt::skip]
-macro_rules! impl_NpyArray_try_read {( $($element_type:ident),+ ) => { paste::paste! {
+macro_rules! impl_NpyElement_for_primitive {( $($element_type:ident : $type_code:literal => $data_type:ident as $kind:ident),+ ) => {
+    $(
+        impl NpyElement for $element_type {
+            const TYPE_CODE: &'static str = $type_code;
+            const DATA_TYPE: Option<DataType> = Some(DataType::$data_type);
+
+            fn to_scalar(self) -> NpyScalar { NpyScalar::$kind(self as _) }
+            fn from_scalar(value: NpyScalar) -> Self {
+                match value {
+                    NpyScalar::Int(value) => value as $element_type,
+                    NpyScalar::Float(value) => value as $element_type,
+                }
+            }
+
+            fn read_npy(reader: impl Read) -> Result<ArrayD<Self>, ReadNpyError> {
+                ArrayD::<Self>::read_npy(reader)
+            }
+            fn write_npy(arr: &ArrayD<Self>, writer: impl Write) -> Result<(), WriteNpyError> {
+                WriteNpyExt::write_npy(arr, writer)
+            }
+            fn view_npy(buf: &[u8]) -> Option<ArrayViewD<'_, Self>> {
+                ArrayViewD::<Self>::view_npy(buf).ok()
+            }
+
+            fn downcast_ref(arr: &NpyArray) -> Option<&ArrayD<Self>> { arr.try_into().ok() }
+        }
+    )*
+};}
+
+impl_NpyElement_for_primitive!(
+    u8: "u1" => Uint8 as Int, i8: "i1" => Int8 as Int, u16: "u2" => Uint16 as Int, i16: "i2" => Int16 as Int,
+    u32: "u4" => Uint32 as Int, i32: "i4" => Int32 as Int, u64: "u8" => Uint64 as Int, i64: "i8" => Int64 as Int,
+    f32: "f4" => Float32 as Float, f64: "f8" => Float64 as Float
+);
+
+impl NpyElement for bool {
+    const TYPE_CODE: &'static str = "b1";
+    const DATA_TYPE: Option<DataType> = Some(DataType::Bool);
+
+    fn to_scalar(self) -> NpyScalar { NpyScalar::Int(self as i128) }
+    fn from_scalar(value: NpyScalar) -> Self {
+        match value {
+            NpyScalar::Int(value) => value != 0,
+            NpyScalar::Float(value) => value != 0.0,
+        }
+    }
+
+    fn read_npy(reader: impl Read) -> Result<ArrayD<Self>, ReadNpyError> {
+        ArrayD::<Self>::read_npy(reader)
+    }
+    fn write_npy(arr: &ArrayD<Self>, writer: impl Write) -> Result<(), WriteNpyError> {
+        WriteNpyExt::write_npy(arr, writer)
+    }
+    fn view_npy(buf: &[u8]) -> Option<ArrayViewD<'_, Self>> {
+        ArrayViewD::<Self>::view_npy(buf).ok()
+    }
+
+    fn downcast_ref(arr: &NpyArray) -> Option<&ArrayD<Self>> { arr.try_into().ok() }
+}
+
+// ndarray_npy can't handle half-precision floats, so they are (de)serialized by hand
+impl NpyElement for f16 {
+    const TYPE_CODE: &'static str = "f2";
+    const DATA_TYPE: Option<DataType> = None;
+
+    fn to_scalar(self) -> NpyScalar { NpyScalar::Float(f16::to_f64(self)) }
+    fn from_scalar(value: NpyScalar) -> Self {
+        match value {
+            NpyScalar::Int(value) => f16::from_f64(value as f64),
+            NpyScalar::Float(value) => f16::from_f64(value),
+        }
+    }
+
+    fn read_npy(mut reader: impl Read) -> Result<ArrayD<Self>, ReadNpyError> {
+        let (header, _) = NpyHeader::read(&mut reader)?;
+        let big_endian = header.is_big_endian();
+        let num_elements = header.shape.iter().try_fold(1usize, |acc, extent| acc.checked_mul(*extent))
+            .ok_or(ReadNpyError::LengthOverflow)?;
+        let expected_len = num_elements.checked_mul(2).ok_or(ReadNpyError::LengthOverflow)?;
+        // the header is untrusted, so the buffer only grows as far as the data actually goes
+        let mut bytes = Vec::new();
+        (&mut reader).take(expected_len as u64).read_to_end(&mut bytes)?;
+        if bytes.len() < expected_len {
+            return Err(ReadNpyError::MissingData)
+        }
+        let extra_bytes = std::io::copy(&mut reader, &mut std::io::sink())?;
+        if extra_bytes > 0 {
+            return Err(ReadNpyError::ExtraBytes(extra_bytes as usize))
+        }
+        let data: Vec<f16> = bytes.chunks_exact(2)
+            .map(|pair| {
+                let pair = [pair[0], pair[1]];
+                f16::from_bits(if big_endian { u16::from_be_bytes(pair) } else { u16::from_le_bytes(pair) })
+            })
+            .collect();
+        let shape = IxDyn(&header.shape).set_f(header.fortran_order);
+        Ok(ArrayD::from_shape_vec(shape, data).expect("Data length should match shape"))
+    }
+
+    fn write_npy(arr: &ArrayD<Self>, mut writer: impl Write) -> Result<(), WriteNpyError> {
+        let shape = match arr.shape() {
+            [extent] => format!("({extent},)"),
+            shape => format!("({})", shape.iter().map(|extent| extent.to_string()).collect::<Vec<_>>().join(", ")),
+        };
+        let mut dict = format!("{{'descr': '<f2', 'fortran_order': False, 'shape': {shape}, }}");
+        // magic, version and header length are followed by the dict, which must end in a newline so that
+        // the whole header is aligned to 64 bytes
+        let unpadded_len = NPY_MAGIC.len() + 2 + 2 + dict.len() + 1;
+        dict.extend(std::iter::repeat_n(' ', (64 - unpadded_len % 64) % 64));
+        dict.push('\n');
+
+        let mut header = Vec::with_capacity(NPY_MAGIC.len() + 4 + dict.len());
+        header.extend_from_slice(NPY_MAGIC);
+        header.extend_from_slice(&[1, 0]);
+        header.extend_from_slice(&(dict.len() as u16).to_le_bytes());
+        header.extend_from_slice(dict.as_bytes());
+        writer.write_all(&header).map_err(WriteNpyError::Io)?;
+        for value in arr.iter() {
+            writer.write_all(&value.to_bits().to_le_bytes()).map_err(WriteNpyError::Io)?;
+        }
+        Ok(())
+    }
+    // half::f16 is not a ViewElement, so these are always read into memory
+    fn view_npy(_buf: &[u8]) -> Option<ArrayViewD<'_, Self>> { None }
+
+    fn downcast_ref(arr: &NpyArray) -> Option<&ArrayD<Self>> { arr.try_into().ok() }
+}
+
+#[rustfmt::skip]
+macro_rules! impl_NpyArray {( $($variant:ident($element_type:ident)),+ ) => {
     #[derive(Clone)]
     pub enum NpyArray {$(
-        [<Array $element_type:upper>](ndarray::ArrayD<$element_type>),
+        $variant(ArrayD<$element_type>),
     )*}
 
+    $(
+        impl From<ArrayD<$element_type>> for NpyArray {
+            fn from(arr: ArrayD<$element_type>) -> Self {
+                Self::$variant(arr)
+            }
+        }
+
+        impl<'a> TryFrom<&'a NpyArray> for &'a ArrayD<$element_type> {
+            type Error = ();
+            fn try_from(arr: &'a NpyArray) -> Result<Self, Self::Error> {
+                match arr {
+                    NpyArray::$variant(arr) => Ok(arr),
+                    _ => Err(()),
+                }
+            }
+        }
+    )*
+
     impl NpyArray {
+        /// Parses the header once to find the element type, then streams the data straight out of `reader`
         pub fn try_load(mut reader: impl Read) -> Result<Self, ReadNpyError> {
-            let mut data = vec![];
-            reader.read_to_end(&mut data)?; //FIXME: what if too big?
-            let mut cursor = std::io::Cursor::new(data);
+            let (header, raw_header) = NpyHeader::read(&mut reader)?;
+            let full_reader = std::io::Cursor::new(raw_header).chain(reader);
+            let type_code = header.type_code();
             $(
-                cursor.rewind()?;
-                match ndarray::ArrayD::<$element_type>::read_npy(&mut cursor) {
-                    Ok(arr) => return Ok(Self::[<Array $element_type:upper>](arr)),
-                    Err(err) => match err {
-                        ndarray_npy::ReadNpyError::WrongDescriptor(_) => (),
-                        other_err => return Err(other_err),
-                    },
-                };
-            )+
-            return Err(ReadNpyError::ParseData(Box::new(UnsupportedNumpyElementType)))
-        }
-
-        pub fn write_npy<W: std::io::Write>(&self, writer: W) -> Result<(), ndarray_npy::WriteNpyError>{
+                if type_code == <$element_type as NpyElement>::TYPE_CODE {
+                    return Ok(Self::$variant(<$element_type as NpyElement>::read_npy(full_reader)?))
+                }
+            )*
+            Err(ReadNpyError::ParseData(Box::new(UnsupportedNumpyElementType)))
+        }
+
+        /// Whether the `.npy` file in `buf`, whose element type is `type_code`, can be viewed in place
+        #[cfg(not(target_arch="wasm32"))]
+        fn can_view(buf: &[u8], type_code: &str) -> bool {
+            $(
+                if type_code == <$element_type as NpyElement>::TYPE_CODE {
+                    return <$element_type as NpyElement>::view_npy(buf).is_some()
+                }
+            )*
+            false
+        }
+
+        pub fn write_npy<W: Write>(&self, writer: W) -> Result<(), WriteNpyError>{
             match self {$(
-                Self::[<Array $element_type:upper>](arr) => arr.write_npy(writer),
+                Self::$variant(arr) => <$element_type as NpyElement>::write_npy(arr, writer),
             )*}
         }
 
         pub fn shape(&self) -> &[usize] {
             match self {$(
-                Self::[<Array $element_type:upper>](arr) => arr.shape(),
+                Self::$variant(arr) => arr.shape(),
+            )*}
+        }
+
+        /// Size in bytes of the elements, not counting the npy header
+        pub fn nbytes(&self) -> usize {
+            match self {$(
+                Self::$variant(arr) => arr.len() * std::mem::size_of::<$element_type>(),
             )*}
         }
+
+        /// The data type of the elements, or `None` if they can't be described in a model RDF (e.g. float16)
+        pub fn dtype(&self) -> Option<DataType> {
+            match self {$(
+                Self::$variant(_) => <$element_type as NpyElement>::DATA_TYPE,
+            )*}
+        }
+
+        /// The numpy type code of the elements, e.g. `f4`
+        pub fn type_code(&self) -> &'static str {
+            match self {$(
+                Self::$variant(_) => <$element_type as NpyElement>::TYPE_CODE,
+            )*}
+        }
+
+        /// Converts every element to `T` as an `as` cast would (bools become 0 or 1 and vice versa)
+        pub fn cast<T: NpyElement>(&self) -> ArrayD<T> {
+            match self {$(
+                Self::$variant(arr) => arr.mapv(|v| T::from_scalar(v.to_scalar())),
+            )*}
+        }
+
+        /// Converts this array into the variant matching `dtype`
+        pub fn to_dtype(&self, dtype: DataType) -> Self {
+            $(
+                if <$element_type as NpyElement>::DATA_TYPE == Some(dtype) {
+                    return match self.as_array::<$element_type>() {
+                        Some(arr) => Self::$variant(arr.clone()),
+                        None => Self::$variant(self.cast()),
+                    }
+                }
+            )*
+            unreachable!("Every DataType has a matching NpyArray variant")
+        }
+
+        /// Casts an f32 array into the variant matching `dtype`
+        pub fn from_f32_array(arr: ArrayD<f32>, dtype: DataType) -> Self {
+            $(
+                if <$element_type as NpyElement>::DATA_TYPE == Some(dtype) {
+                    return Self::$variant(arr.mapv(|v| <$element_type as NpyElement>::from_scalar(NpyScalar::Float(v as f64))))
+                }
+            )*
+            unreachable!("Every DataType has a matching NpyArray variant")
+        }
     }
-}};}
+};}
 
-impl_NpyArray_try_read!(u8, i8, u16, i16, u32, i32, u64, i64, f32, f64);
+impl_NpyArray!(
+    ArrayBool(bool),
+    ArrayU8(u8), ArrayI8(i8), ArrayU16(u16), ArrayI16(i16), ArrayU32(u32), ArrayI32(i32), ArrayU64(u64), ArrayI64(i64),
+    ArrayF16(f16), ArrayF32(f32), ArrayF64(f64)
+);
+
+impl NpyArray {
+    /// The elements of this array, if they are of type `T`
+    pub fn as_array<T: NpyElement>(&self) -> Option<&ArrayD<T>> {
+        T::downcast_ref(self)
+    }
+
+    /// Converts every element to f32, which is the type processing steps operate on
+    pub fn to_f32_array(&self) -> ArrayD<f32> {
+        self.cast()
+    }
+
+    pub fn into_f32_array(self) -> ArrayD<f32> {
+        match self {
+            Self::ArrayF32(arr) => arr,
+            other => other.to_f32_array(),
+        }
+    }
+}
 
 pub type ArcNpyArray = Arc<NpyArray>;
+
+/// A local `.npy` file whose elements are viewed in place through a memory mapping instead of being
+/// copied into memory. Files that can't be viewed like that (Fortran order, non-native byte order or
+/// float16 elements) are streamed into an [NpyArray] instead
+#[cfg(not(target_arch="wasm32"))]
+pub enum MappedNpyArray {
+    Mapped{mmap: memmap2::Mmap, header: NpyHeader},
+    Loaded(NpyArray),
+}
+
+#[cfg(not(target_arch="wasm32"))]
+impl MappedNpyArray {
+    /// Maps the `.npy` file at `path`. The file must not be modified while it is mapped
+    pub fn open(path: &std::path::Path) -> Result<Self, ReadNpyError> {
+        let file = std::fs::File::open(path)?;
+        let (header, _) = NpyHeader::read(&mut std::io::BufReader::new(&file))?;
+        if !header.fortran_order && header.is_native_endian() {
+            // SAFETY: the mapping is read-only, and the caller must not modify the file while it is mapped
+            let mmap = unsafe { memmap2::Mmap::map(&file)? };
+            if NpyArray::can_view(&mmap, header.type_code()) {
+                return Ok(Self::Mapped{mmap, header})
+            }
+        }
+        // streaming also reports why a file that looked viewable couldn't be viewed, e.g. missing data
+        Ok(Self::Loaded(NpyArray::try_load(std::io::BufReader::new(std::fs::File::open(path)?))?))
+    }
+
+    pub fn shape(&self) -> &[usize] {
+        match self {
+            Self::Mapped { header, .. } => &header.shape,
+            Self::Loaded(arr) => arr.shape(),
+        }
+    }
+
+    /// The numpy type code of the elements, e.g. `f4`
+    pub fn type_code(&self) -> &str {
+        match self {
+            Self::Mapped { header, .. } => header.type_code(),
+            Self::Loaded(arr) => arr.type_code(),
+        }
+    }
+
+    /// The elements of this array, if they are of type `T`
+    pub fn view<T: NpyElement>(&self) -> Option<ArrayViewD<'_, T>> {
+        match self {
+            Self::Mapped { mmap, .. } => T::view_npy(mmap),
+            Self::Loaded(arr) => arr.as_array::<T>().map(|arr| arr.view()),
+        }
+    }
+
+    /// Copies the elements into memory
+    pub fn to_npy_array(&self) -> Result<NpyArray, ReadNpyError> {
+        match self {
+            Self::Mapped { mmap, .. } => NpyArray::try_load(&mmap[..]),
+            Self::Loaded(arr) => Ok(arr.clone()),
+        }
+    }
+}
+
+#[test]
+fn test_npy_header_driven_loading(){
+    let arr = ndarray::Array::from_shape_vec(vec![2, 3], vec![1i16, 2, 3, 4, 5, 6]).unwrap();
+    let mut raw = vec![];
+    NpyArray::ArrayI16(arr.clone()).write_npy(&mut raw).unwrap();
+
+    let (header, _) = NpyHeader::read(&mut raw.as_slice()).unwrap();
+    assert_eq!(header, NpyHeader{descr: "<i2".to_owned(), fortran_order: false, shape: vec![2, 3]});
+
+    let NpyArray::ArrayI16(loaded) = NpyArray::try_load(raw.as_slice()).unwrap() else {
+        panic!("Loaded wrong element type")
+    };
+    assert_eq!(loaded, arr);
+}
+
+#[test]
+fn test_npy_dtypes_and_layouts(){
+    let halves = ndarray::Array::from_shape_vec(vec![3], vec![f16::from_f32(0.5), f16::ONE, f16::from_f32(-2.0)]).unwrap();
+    let mut raw = vec![];
+    NpyArray::from(halves.clone()).write_npy(&mut raw).unwrap();
+    assert_eq!(NpyArray::try_load(raw.as_slice()).unwrap().as_array::<f16>(), Some(&halves));
+
+    // big endian u16, fortran order, i.e. [[1, 2], [3, 4]] stored column by column
+    let dict = "{'descr': '>u2', 'fortran_order': True, 'shape': (2, 2), }\n";
+    let mut raw = b"\x93NUMPY\x01\x00".to_vec();
+    raw.extend_from_slice(&(dict.len() as u16).to_le_bytes());
+    raw.extend_from_slice(dict.as_bytes());
+    raw.extend_from_slice(&[0, 1, 0, 3, 0, 2, 0, 4]);
+    let loaded = NpyArray::try_load(raw.as_slice()).unwrap();
+    assert_eq!(loaded.dtype(), Some(DataType::Uint16));
+    assert_eq!(loaded.as_array::<u16>().unwrap(), &ndarray::arr2(&[[1u16, 2], [3, 4]]).into_dyn());
+
+    let as_bool = loaded.to_dtype(DataType::Bool);
+    assert_eq!(as_bool.as_array::<bool>().unwrap().iter().filter(|v| **v).count(), 4);
+
+    // a header claiming far more halves than the data holds is reported without allocating for all of them
+    let dict = "{'descr': '<f2', 'fortran_order': False, 'shape': (1099511627776,), }\n";
+    let mut raw = b"\x93NUMPY\x01\x00".to_vec();
+    raw.extend_from_slice(&(dict.len() as u16).to_le_bytes());
+    raw.extend_from_slice(dict.as_bytes());
+    raw.extend_from_slice(&[0, 0x3c, 0, 0x40]);
+    assert!(matches!(NpyArray::try_load(raw.as_slice()), Err(ReadNpyError::MissingData)));
+}
+
+#[cfg(not(target_arch="wasm32"))]
+#[test]
+fn test_mapped_npy_array(){
+    let dir = tempfile::tempdir().unwrap();
+    let arr = ndarray::Array::from_shape_vec(vec![2, 3], vec![1.0f32, 2.0, 3.0, 4.0, 5.0, 6.0]).unwrap();
+    let path = dir.path().join("native.npy");
+    NpyArray::from(arr.clone()).write_npy(std::fs::File::create(&path).unwrap()).unwrap();
+    let mapped = MappedNpyArray::open(&path).unwrap();
+    assert!(matches!(mapped, MappedNpyArray::Mapped { .. }));
+    assert_eq!(mapped.shape(), &[2, 3]);
+    assert_eq!(mapped.view::<f32>().unwrap(), arr.view());
+    assert!(mapped.view::<f64>().is_none());
+    assert_eq!(mapped.to_npy_array().unwrap().as_array::<f32>(), Some(&arr));
+
+    // fortran order can't be viewed in place, so it's streamed
+    let dict = "{'descr': '<u2', 'fortran_order': True, 'shape': (2, 2), }\n";
+    let mut raw = b"\x93NUMPY\x01\x00".to_vec();
+    raw.extend_from_slice(&(dict.len() as u16).to_le_bytes());
+    raw.extend_from_slice(dict.as_bytes());
+    raw.extend_from_slice(&[1, 0, 3, 0, 2, 0, 4, 0]);
+    let path = dir.path().join("fortran.npy");
+    std::fs::write(&path, &raw).unwrap();
+    let mapped = MappedNpyArray::open(&path).unwrap();
+    assert!(matches!(mapped, MappedNpyArray::Loaded(_)));
+    assert_eq!(mapped.view::<u16>().unwrap(), ndarray::arr2(&[[1u16, 2], [3, 4]]).into_dyn());
+
+    // truncated files are reported, not mapped
+    let path = dir.path().join("truncated.npy");
+    let mut raw = vec![];
+    NpyArray::from(arr).write_npy(&mut raw).unwrap();
+    std::fs::write(&path, &raw[..raw.len() - 4]).unwrap();
+    assert!(matches!(MappedNpyArray::open(&path), Err(ReadNpyError::MissingData)));
+}
+
+#[test]
+fn test_cast_matches_as_casts(){
+    let wide = NpyArray::ArrayI64(ndarray::arr1(&[(1i64 << 53) + 1, -1, i64::MAX]).into_dyn());
+    assert_eq!(wide.cast::<i64>().into_raw_vec(), vec![(1i64 << 53) + 1, -1, i64::MAX]);
+    assert_eq!(wide.cast::<u64>().into_raw_vec(), vec![(1u64 << 53) + 1, u64::MAX, i64::MAX as u64]);
+    assert_eq!(wide.cast::<u8>().into_raw_vec(), vec![1, 255, 255]);
+    assert_eq!(wide.cast::<f32>().into_raw_vec(), vec![((1i64 << 53) + 1) as f32, -1.0, i64::MAX as f32]);
+
+    let floats = NpyArray::ArrayF64(ndarray::arr1(&[1e20, -2.7, f64::NAN, 0.0]).into_dyn());
+    assert_eq!(floats.cast::<i32>().into_raw_vec(), vec![i32::MAX, -2, 0, 0]);
+    assert_eq!(floats.cast::<u8>().into_raw_vec(), vec![255, 0, 0, 0]);
+    assert_eq!(floats.cast::<bool>().into_raw_vec(), vec![true, true, true, false]);
+}
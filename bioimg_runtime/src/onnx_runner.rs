@@ -0,0 +1,232 @@
+use std::borrow::Borrow;
+use std::collections::HashMap;
+
+use tract_onnx::prelude::*;
+use tract_onnx::tract_hir::infer::Factoid;
+
+use crate::model_runner::{ModelRunner, ModelRunnerError};
+use crate::model_weights::OnnxWeights;
+use crate::{ModelInterface, NpyArray};
+
+fn backend_error(err: impl std::fmt::Display) -> ModelRunnerError{
+    ModelRunnerError::BackendError { reason: err.to_string() }
+}
+
+/// Runs [OnnxWeights] on the CPU via the pure-Rust `tract` engine.
+///
+/// The network is optimized for the concrete shapes of the inputs it is given, so a plan is
+/// built (and cached) for each distinct combination of input shapes.
+pub struct OnnxRunner{
+    model: InferenceModel,
+    input_ndims: Vec<usize>,
+    /// Element types of the inputs, where the network declares them
+    input_dtypes: Vec<Option<DatumType>>,
+    output_ndims: Vec<usize>,
+    plans: HashMap<Vec<Vec<usize>>, TypedSimplePlan<TypedModel>>,
+}
+
+impl OnnxRunner{
+    pub fn try_new<DATA: Borrow<NpyArray>>(
+        weights: &OnnxWeights, interface: &ModelInterface<DATA>
+    ) -> Result<Self, ModelRunnerError>{
+        let mut raw_model = vec![];
+        weights.weights.read_to_end(&mut raw_model)?;
+        let model = tract_onnx::onnx().model_for_read(&mut raw_model.as_slice()).map_err(backend_error)?;
+        let num_model_inputs = model.input_outlets().map_err(backend_error)?.len();
+        if num_model_inputs != interface.inputs().len().get(){
+            return Err(ModelRunnerError::MismatchedNumTensors {
+                expected: interface.inputs().len().get(), found: num_model_inputs
+            })
+        }
+        let input_dtypes = (0..num_model_inputs)
+            .map(|input_idx| model.input_fact(input_idx).map(|fact| fact.datum_type.concretize()))
+            .collect::<TractResult<_>>()
+            .map_err(backend_error)?;
+        Ok(Self{
+            model,
+            input_dtypes,
+            input_ndims: interface.inputs().iter().map(|slot| slot.tensor_meta.axes().len()).collect(),
+            output_ndims: interface.outputs().iter().map(|slot| slot.tensor_meta.axes().len()).collect(),
+            plans: HashMap::new(),
+        })
+    }
+
+    fn plan_for(&mut self, inputs: &[Tensor]) -> Result<&TypedSimplePlan<TypedModel>, ModelRunnerError>{
+        let shapes: Vec<Vec<usize>> = inputs.iter().map(|tensor| tensor.shape().to_vec()).collect();
+        if !self.plans.contains_key(&shapes){
+            let mut model = self.model.clone();
+            for (input_idx, tensor) in inputs.iter().enumerate(){
+                let fact = InferenceFact::dt_shape(tensor.datum_type(), tensor.shape());
+                model = model.with_input_fact(input_idx, fact).map_err(backend_error)?;
+            }
+            let plan = model.into_optimized()
+                .and_then(|model| model.into_runnable())
+                .map_err(backend_error)?;
+            self.plans.insert(shapes.clone(), plan);
+        }
+        Ok(&self.plans[&shapes])
+    }
+}
+
+#[rustfmt::skip]
+macro_rules! impl_tensor_conversions {( $($variant:ident($element_type:ident)),+ ) => {
+    fn to_tract_tensor(array: &NpyArray) -> Result<Tensor, ModelRunnerError>{
+        match array {$(
+            NpyArray::$variant(arr) => {
+                let arr = arr.as_standard_layout();
+                Tensor::from_shape(arr.shape(), arr.as_slice().unwrap()).map_err(backend_error)
+            },
+        )*}
+    }
+
+    fn from_tract_tensor(tensor: Tensor) -> Result<NpyArray, ModelRunnerError>{
+        let shape = ndarray::IxDyn(tensor.shape());
+        $(
+            if tensor.datum_type() == $element_type::datum_type() {
+                let data = tensor.as_slice::<$element_type>().map_err(backend_error)?.to_vec();
+                let arr = ndarray::ArrayD::from_shape_vec(shape, data).map_err(backend_error)?;
+                return Ok(NpyArray::$variant(arr))
+            }
+        )*
+        Err(ModelRunnerError::UnsupportedDataType(format!("{:?}", tensor.datum_type())))
+    }
+};}
+
+impl_tensor_conversions!(
+    ArrayBool(bool),
+    ArrayU8(u8), ArrayI8(i8), ArrayU16(u16), ArrayI16(i16), ArrayU32(u32), ArrayI32(i32), ArrayU64(u64), ArrayI64(i64),
+    ArrayF16(f16), ArrayF32(f32), ArrayF64(f64)
+);
+
+impl ModelRunner for OnnxRunner{
+    fn run(&mut self, inputs: &[&NpyArray]) -> Result<Vec<NpyArray>, ModelRunnerError>{
+        if inputs.len() != self.input_ndims.len(){
+            return Err(ModelRunnerError::MismatchedNumTensors { expected: self.input_ndims.len(), found: inputs.len() })
+        }
+        for (tensor_index, (input, num_axes)) in inputs.iter().zip(&self.input_ndims).enumerate(){
+            if input.shape().len() != *num_axes{
+                return Err(ModelRunnerError::MismatchedNumDimensions {
+                    tensor_index, num_dims: input.shape().len(), num_axes: *num_axes
+                })
+            }
+        }
+        let tensors: Vec<Tensor> = inputs.iter().map(|input| to_tract_tensor(input)).collect::<Result<_, _>>()?;
+        for (tensor_index, (tensor, expected)) in tensors.iter().zip(&self.input_dtypes).enumerate(){
+            if let Some(expected) = expected.filter(|expected| *expected != tensor.datum_type()){
+                return Err(ModelRunnerError::MismatchedDataType {
+                    tensor_index, expected: format!("{expected:?}"), found: format!("{:?}", tensor.datum_type()),
+                })
+            }
+        }
+        let plan = self.plan_for(&tensors)?;
+        let raw_outputs = plan.run(tensors.into_iter().map(TValue::from).collect()).map_err(backend_error)?;
+
+        if raw_outputs.len() != self.output_ndims.len(){
+            return Err(ModelRunnerError::MismatchedNumTensors { expected: self.output_ndims.len(), found: raw_outputs.len() })
+        }
+        raw_outputs.into_iter().zip(&self.output_ndims).enumerate()
+            .map(|(tensor_index, (raw_output, num_axes))|{
+                let output = from_tract_tensor(raw_output.into_tensor())?;
+                if output.shape().len() != *num_axes{
+                    return Err(ModelRunnerError::MismatchedNumDimensions {
+                        tensor_index, num_dims: output.shape().len(), num_axes: *num_axes
+                    })
+                }
+                Ok(output)
+            })
+            .collect()
+    }
+}
+
+/// A network with a single float input `x` and output `y = relu(x)`
+#[cfg(test)]
+pub(crate) fn relu_model_proto() -> tract_onnx::pb::ModelProto{
+    use tract_onnx::pb;
+
+    let float_tensor = |name: &str| pb::ValueInfoProto{
+        name: name.to_owned(),
+        r#type: Some(pb::TypeProto{
+            value: Some(pb::type_proto::Value::TensorType(pb::type_proto::Tensor{
+                elem_type: pb::tensor_proto::DataType::Float as i32,
+                shape: None,
+            })),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    pb::ModelProto{
+        ir_version: 7,
+        opset_import: vec![pb::OperatorSetIdProto{domain: String::new(), version: 13}],
+        graph: Some(pb::GraphProto{
+            node: vec![pb::NodeProto{
+                input: vec!["x".to_owned()],
+                output: vec!["y".to_owned()],
+                op_type: "Relu".to_owned(),
+                ..Default::default()
+            }],
+            input: vec![float_tensor("x")],
+            output: vec![float_tensor("y")],
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+fn relu_runner() -> OnnxRunner{
+    OnnxRunner{
+        model: tract_onnx::onnx().model_for_proto_model(&relu_model_proto()).unwrap(),
+        input_ndims: vec![2],
+        input_dtypes: vec![Some(DatumType::F32)],
+        output_ndims: vec![2],
+        plans: HashMap::new(),
+    }
+}
+
+#[test]
+fn test_onnx_runner_caches_plans_per_input_shape(){
+    let mut runner = relu_runner();
+    let input = NpyArray::ArrayF32(ndarray::ArrayD::from_shape_vec(ndarray::IxDyn(&[2, 2]), vec![-1.0, 2.0, -3.0, 4.0]).unwrap());
+
+    let outputs = runner.run(&[&input]).unwrap();
+    let NpyArray::ArrayF32(output) = &outputs[0] else {
+        panic!("Expected f32 output")
+    };
+    assert_eq!(output.iter().copied().collect::<Vec<_>>(), vec![0.0, 2.0, 0.0, 4.0]);
+    assert_eq!(runner.plans.len(), 1);
+
+    runner.run(&[&input]).unwrap();
+    assert_eq!(runner.plans.len(), 1);
+
+    let wider = NpyArray::ArrayF32(ndarray::ArrayD::zeros(ndarray::IxDyn(&[1, 3])));
+    assert_eq!(runner.run(&[&wider]).unwrap()[0].shape(), &[1, 3]);
+    assert_eq!(runner.plans.len(), 2);
+    assert!(runner.plans.contains_key(&vec![vec![1, 3]]));
+}
+
+#[test]
+fn test_onnx_runner_errors(){
+    let mut runner = relu_runner();
+    let input = NpyArray::ArrayF32(ndarray::ArrayD::zeros(ndarray::IxDyn(&[2, 2])));
+
+    assert!(matches!(
+        runner.run(&[&input, &input]),
+        Err(ModelRunnerError::MismatchedNumTensors { expected: 1, found: 2 })
+    ));
+
+    let volume = NpyArray::ArrayF32(ndarray::ArrayD::zeros(ndarray::IxDyn(&[2, 2, 2])));
+    assert!(matches!(
+        runner.run(&[&volume]),
+        Err(ModelRunnerError::MismatchedNumDimensions { tensor_index: 0, num_dims: 3, num_axes: 2 })
+    ));
+
+    // the graph declares a float input
+    let ints = NpyArray::ArrayI32(ndarray::ArrayD::zeros(ndarray::IxDyn(&[2, 2])));
+    assert!(matches!(runner.run(&[&ints]), Err(ModelRunnerError::MismatchedDataType { tensor_index: 0, .. })));
+    assert!(runner.plans.is_empty());
+
+    assert!(matches!(
+        from_tract_tensor(tensor0(String::from("not a number"))),
+        Err(ModelRunnerError::UnsupportedDataType(_))
+    ));
+}
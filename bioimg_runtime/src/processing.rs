@@ -0,0 +1,512 @@
+use std::borrow::Borrow;
+use std::collections::HashMap;
+
+use bioimg_spec::rdf::model::preprocessing::{
+    BinarizeDescr, ClipDescr, FixedZmuv, PreprocessingEpsilon, ScaleLinearDescr, ScaleRangeDescr, Zmuv,
+};
+use bioimg_spec::rdf::model::input_tensor::InputTensorMetadata;
+use bioimg_spec::rdf::model::output_tensor::OutputTensorMetadata;
+use bioimg_spec::rdf::model::postprocessing::{PostprocessingDescr, ScaleMeanVarianceDescr};
+use bioimg_spec::rdf::model::{AxisId, DataType, PreprocessingDescr, TensorId};
+use ndarray::{ArrayD, IxDyn, Zip};
+
+use crate::NpyArray;
+
+#[derive(thiserror::Error, Debug)]
+pub enum ProcessingError{
+    #[error("Tensor '{tensor_id}' has {num_dims} dimensions but {num_axes} axes are described")]
+    MismatchedNumDimensions{tensor_id: TensorId, num_dims: usize, num_axes: usize},
+    #[error("Expected {expected} tensors, found {found}")]
+    MismatchedNumTensors{expected: usize, found: usize},
+    #[error("Axis '{axis_id}' not found in tensor '{tensor_id}'")]
+    AxisNotFound{tensor_id: TensorId, axis_id: AxisId},
+    #[error("Expected 1 or {extent} values along axis '{axis_id}' of tensor '{tensor_id}', found {found}")]
+    MismatchedNumValues{tensor_id: TensorId, axis_id: AxisId, extent: usize, found: usize},
+    #[error("Tensor '{0}' is not available for reference")]
+    MissingReferenceTensor(TensorId),
+    #[error("Statistics of tensor '{reference}' can't be broadcast to the shape of tensor '{target}'")]
+    IncompatibleReference{reference: TensorId, target: TensorId},
+}
+
+struct SampleMember<'a>{
+    axis_ids: Vec<AxisId>,
+    data: &'a NpyArray,
+}
+
+/// The named tensors that processing steps can refer to via their `reference_tensor` field
+#[derive(Default)]
+pub struct Sample<'a>{
+    members: HashMap<TensorId, SampleMember<'a>>,
+}
+
+impl<'a> Sample<'a>{
+    pub fn new() -> Self{
+        Self::default()
+    }
+    pub fn insert(&mut self, tensor_id: TensorId, axis_ids: Vec<AxisId>, data: &'a NpyArray){
+        self.members.insert(tensor_id, SampleMember{axis_ids, data});
+    }
+}
+
+/// An f32 tensor whose dimensions are labeled by the axis ids in the tensor description
+struct LabeledTensor<'a>{
+    tensor_id: &'a TensorId,
+    axis_ids: &'a [AxisId],
+    data: ArrayD<f32>,
+}
+
+impl From<LabeledTensor<'_>> for NpyArray{
+    fn from(value: LabeledTensor<'_>) -> Self {
+        NpyArray::ArrayF32(value.data)
+    }
+}
+
+impl LabeledTensor<'_>{
+    fn axis_index(&self, axis_id: &AxisId) -> Result<usize, ProcessingError>{
+        self.axis_ids.iter().position(|id| id == axis_id).ok_or_else(|| ProcessingError::AxisNotFound {
+            tensor_id: self.tensor_id.clone(), axis_id: axis_id.clone()
+        })
+    }
+
+    fn reduced_indices<'b>(
+        &self, axes: Option<impl IntoIterator<Item=&'b AxisId>>
+    ) -> Result<Vec<usize>, ProcessingError>{
+        match axes{
+            None => Ok((0..self.axis_ids.len()).collect()),
+            Some(axes) => axes.into_iter().map(|axis_id| self.axis_index(axis_id)).collect(),
+        }
+    }
+
+    /// Lays out per-entry parameters along `axis_id`, so they broadcast against the tensor
+    fn along_axis(&self, axis_id: &AxisId, values: Vec<f32>) -> Result<ArrayD<f32>, ProcessingError>{
+        let axis_index = self.axis_index(axis_id)?;
+        let extent = self.data.shape()[axis_index];
+        if values.len() != 1 && values.len() != extent{
+            return Err(ProcessingError::MismatchedNumValues {
+                tensor_id: self.tensor_id.clone(), axis_id: axis_id.clone(), extent, found: values.len()
+            })
+        }
+        let mut shape = vec![1; self.data.ndim()];
+        shape[axis_index] = values.len();
+        Ok(ArrayD::from_shape_vec(IxDyn(&shape), values).unwrap())
+    }
+
+    /// Applies `f` to every element, pairing it with the broadcast element of each parameter array
+    fn map_with(&mut self, params: &[&ArrayD<f32>], f: impl Fn(f32, &[f32]) -> f32){
+        let shape = self.data.raw_dim();
+        let broadcast: Vec<_> = params.iter().map(|p| p.broadcast(shape.clone()).unwrap()).collect();
+        let mut param_values = vec![0.0; params.len()];
+        for (idx, value) in self.data.indexed_iter_mut(){
+            for (param_value, param) in param_values.iter_mut().zip(broadcast.iter()){
+                *param_value = param[&idx];
+            }
+            *value = f(*value, &param_values);
+        }
+    }
+
+    fn mean_and_std<'b>(
+        &self, axes: Option<impl IntoIterator<Item=&'b AxisId>>
+    ) -> Result<(ArrayD<f32>, ArrayD<f32>), ProcessingError>{
+        let reduced = self.reduced_indices(axes)?;
+        let means = reduce(&self.data, &reduced, |values| mean(values) as f32);
+        let stds = reduce(&self.data, &reduced, |values| {
+            let mean = mean(values);
+            let variance = values.iter().map(|v| (*v as f64 - mean).powi(2)).sum::<f64>() / values.len() as f64;
+            variance.sqrt() as f32
+        });
+        Ok((means, stds))
+    }
+}
+
+fn mean(values: &[f32]) -> f64{
+    values.iter().map(|v| *v as f64).sum::<f64>() / values.len() as f64
+}
+
+/// Linearly interpolated percentile, like numpy's default `percentile` method
+fn percentile(sorted_values: &[f32], percent: f32) -> f32{
+    let Some(last) = sorted_values.len().checked_sub(1) else {
+        return f32::NAN
+    };
+    let position = (percent as f64 / 100.0) * last as f64;
+    let lower = position.floor() as usize;
+    let upper = position.ceil() as usize;
+    let fraction = (position - lower as f64) as f32;
+    sorted_values[lower] + (sorted_values[upper] - sorted_values[lower]) * fraction
+}
+
+/// Reduces `data` over the dimensions in `reduced`, keeping them as dimensions of extent 1
+/// so that the result broadcasts against `data`
+fn reduce(data: &ArrayD<f32>, reduced: &[usize], f: impl Fn(&mut [f32]) -> f32) -> ArrayD<f32>{
+    let shape = data.shape();
+    let kept: Vec<usize> = (0..shape.len()).filter(|dim| !reduced.contains(dim)).collect();
+    let group_size: usize = reduced.iter().map(|dim| shape[*dim]).product();
+    let out_shape: Vec<usize> = (0..shape.len())
+        .map(|dim| if reduced.contains(&dim) { 1 } else { shape[dim] })
+        .collect();
+
+    let permutation: Vec<usize> = kept.iter().chain(reduced.iter()).copied().collect();
+    let mut grouped: Vec<f32> = data.view().permuted_axes(permutation).iter().copied().collect();
+    let values: Vec<f32> = if group_size == 0 {
+        vec![f32::NAN; out_shape.iter().product()]
+    } else {
+        grouped.chunks_mut(group_size).map(f).collect()
+    };
+    ArrayD::from_shape_vec(IxDyn(&out_shape), values).unwrap()
+}
+
+/// Reorders statistics computed over a tensor labeled `from` so that they broadcast against
+/// a tensor labeled `to`. Axes missing from `to` must have been reduced to extent 1
+fn align_stats(stats: ArrayD<f32>, from: &[AxisId], to: &[AxisId]) -> Option<ArrayD<f32>>{
+    let mut permutation: Vec<usize> = Vec::with_capacity(from.len());
+    let mut aligned_shape: Vec<usize> = Vec::with_capacity(to.len());
+    for axis_id in to{
+        match from.iter().position(|from_id| from_id == axis_id){
+            Some(from_idx) => {
+                permutation.push(from_idx);
+                aligned_shape.push(stats.shape()[from_idx]);
+            },
+            None => aligned_shape.push(1),
+        }
+    }
+    for from_idx in 0..from.len(){
+        if permutation.contains(&from_idx){
+            continue
+        }
+        if stats.shape()[from_idx] != 1{
+            return None
+        }
+        permutation.push(from_idx);
+    }
+    let permuted = stats.permuted_axes(permutation).as_standard_layout().into_owned();
+    permuted.into_shape(IxDyn(&aligned_shape)).ok()
+}
+
+fn binarize(tensor: LabeledTensor<'_>, descr: &BinarizeDescr) -> Result<NpyArray, ProcessingError>{
+    let thresholds = match descr{
+        BinarizeDescr::Simple(descr) => ArrayD::from_elem(IxDyn(&[]), descr.threshold),
+        BinarizeDescr::AlongAxis(descr) => {
+            tensor.along_axis(descr.axis.borrow(), descr.threshold.iter().copied().collect())?
+        },
+    };
+    let thresholds = thresholds.broadcast(tensor.data.raw_dim()).unwrap();
+    let mut out = ArrayD::<bool>::from_elem(tensor.data.raw_dim(), false);
+    Zip::from(&mut out).and(&tensor.data).and(&thresholds).for_each(|out, value, threshold|{
+        *out = value > threshold;
+    });
+    Ok(NpyArray::ArrayBool(out))
+}
+
+fn clip(tensor: &mut LabeledTensor<'_>, descr: &ClipDescr){
+    tensor.data.mapv_inplace(|v| v.clamp(descr.min(), descr.max()));
+}
+
+fn ensure_dtype(tensor: LabeledTensor<'_>, dtype: DataType) -> NpyArray{
+    NpyArray::from_f32_array(tensor.data, dtype)
+}
+
+fn scale_linear(tensor: &mut LabeledTensor<'_>, descr: &ScaleLinearDescr) -> Result<(), ProcessingError>{
+    match descr{
+        ScaleLinearDescr::Simple(descr) => {
+            tensor.data.mapv_inplace(|v| v * descr.gain + descr.offset);
+        },
+        ScaleLinearDescr::AlongAxis(descr) => {
+            let axis_id: &AxisId = descr.axis.borrow();
+            let gains = tensor.along_axis(axis_id, descr.gain_offsets.iter().map(|(gain, _)| *gain).collect())?;
+            let offsets = tensor.along_axis(axis_id, descr.gain_offsets.iter().map(|(_, offset)| *offset).collect())?;
+            tensor.map_with(&[&gains, &offsets], |v, params| v * params[0] + params[1]);
+        },
+    }
+    Ok(())
+}
+
+fn sigmoid(tensor: &mut LabeledTensor<'_>){
+    tensor.data.mapv_inplace(|v| 1.0 / (1.0 + (-v).exp()));
+}
+
+fn fixed_zmuv(tensor: &mut LabeledTensor<'_>, descr: &FixedZmuv) -> Result<(), ProcessingError>{
+    let eps: f32 = PreprocessingEpsilon::default().into();
+    match descr{
+        FixedZmuv::Simple(descr) => {
+            let std: f32 = descr.std.into();
+            tensor.data.mapv_inplace(|v| (v - descr.mean) / (std + eps));
+        },
+        FixedZmuv::AlongAxis(descr) => {
+            let axis_id: &AxisId = descr.axis.borrow();
+            let means = tensor.along_axis(axis_id, descr.mean_and_std.iter().map(|ms| ms.mean).collect())?;
+            let stds = tensor.along_axis(axis_id, descr.mean_and_std.iter().map(|ms| f32::from(ms.std)).collect())?;
+            tensor.map_with(&[&means, &stds], |v, params| (v - params[0]) / (params[1] + eps));
+        },
+    }
+    Ok(())
+}
+
+fn zmuv(tensor: &mut LabeledTensor<'_>, descr: &Zmuv) -> Result<(), ProcessingError>{
+    let eps: f32 = descr.eps.into();
+    let (mean, std) = tensor.mean_and_std(descr.axes.as_ref().map(|axes| axes.iter()))?;
+    tensor.map_with(&[&mean, &std], |v, params| (v - params[0]) / (params[1] + eps));
+    Ok(())
+}
+
+/// Computes statistics over the sample member `reference_id` with `compute_stats`, and aligns
+/// them so that they broadcast against `tensor`
+fn reference_stats<const N: usize>(
+    tensor: &LabeledTensor<'_>,
+    reference_id: &TensorId,
+    sample: &Sample<'_>,
+    compute_stats: impl Fn(&LabeledTensor<'_>) -> Result<[ArrayD<f32>; N], ProcessingError>,
+) -> Result<[ArrayD<f32>; N], ProcessingError>{
+    if reference_id == tensor.tensor_id{
+        return compute_stats(tensor)
+    }
+    let member = sample.members.get(reference_id)
+        .ok_or_else(|| ProcessingError::MissingReferenceTensor(reference_id.clone()))?;
+    let reference = LabeledTensor{
+        tensor_id: reference_id,
+        axis_ids: &member.axis_ids,
+        data: member.data.to_f32_array(),
+    };
+    let incompatible = || ProcessingError::IncompatibleReference {
+        reference: reference_id.clone(), target: tensor.tensor_id.clone()
+    };
+    let stats = compute_stats(&reference)?;
+    let mut aligned_stats = Vec::with_capacity(N);
+    for stat in stats{
+        let aligned = align_stats(stat, reference.axis_ids, tensor.axis_ids).ok_or_else(incompatible)?;
+        if aligned.broadcast(tensor.data.raw_dim()).is_none(){
+            return Err(incompatible())
+        }
+        aligned_stats.push(aligned);
+    }
+    Ok(aligned_stats.try_into().unwrap())
+}
+
+fn scale_range(tensor: &mut LabeledTensor<'_>, descr: &ScaleRangeDescr, sample: &Sample<'_>) -> Result<(), ProcessingError>{
+    let reference_id = descr.reference_tensor.as_ref().unwrap_or(tensor.tensor_id);
+    let [lower, upper] = reference_stats(tensor, reference_id, sample, |source|{
+        let reduced = source.reduced_indices(descr.axes.as_ref())?;
+        let sort = |values: &mut [f32]| values.sort_by(|a, b| a.total_cmp(b));
+        let lower = reduce(&source.data, &reduced, |values| { sort(values); percentile(values, descr.percentiles.min()) });
+        let upper = reduce(&source.data, &reduced, |values| { sort(values); percentile(values, descr.percentiles.max()) });
+        Ok([lower, upper])
+    })?;
+    let eps: f32 = descr.eps.into();
+    tensor.map_with(&[&lower, &upper], |v, params| (v - params[0]) / (params[1] - params[0] + eps));
+    Ok(())
+}
+
+fn scale_mean_variance(
+    tensor: &mut LabeledTensor<'_>, descr: &ScaleMeanVarianceDescr, sample: &Sample<'_>
+) -> Result<(), ProcessingError>{
+    let axes = descr.axes.as_ref().map(|axes| axes.iter());
+    let [ref_mean, ref_std] = reference_stats(tensor, &descr.reference_tensor, sample, |source|{
+        let (means, stds) = source.mean_and_std(axes.clone())?;
+        Ok([means, stds])
+    })?;
+    let (mean, std) = tensor.mean_and_std(axes)?;
+    let eps: f32 = descr.eps.into();
+    tensor.map_with(&[&mean, &std, &ref_mean, &ref_std], |v, params| {
+        (v - params[0]) / (params[1] + eps) * (params[3] + eps) + params[2]
+    });
+    Ok(())
+}
+
+fn axis_ids_checked(
+    tensor_id: &TensorId, axis_ids: impl Iterator<Item=AxisId>, tensor: &NpyArray
+) -> Result<Vec<AxisId>, ProcessingError>{
+    let axis_ids: Vec<AxisId> = axis_ids.collect();
+    if tensor.shape().len() != axis_ids.len(){
+        return Err(ProcessingError::MismatchedNumDimensions {
+            tensor_id: tensor_id.clone(), num_dims: tensor.shape().len(), num_axes: axis_ids.len()
+        })
+    }
+    Ok(axis_ids)
+}
+
+/// Applies the preprocessing steps described in `meta` to `tensor`, in order.
+///
+/// Steps operate on f32 values; the data type only changes via `ensure_dtype` and `binarize`.
+/// Tensors referenced by the steps (e.g. in `scale_range`) are looked up in `sample`.
+pub fn preprocess(meta: &InputTensorMetadata, tensor: &NpyArray, sample: &Sample<'_>) -> Result<NpyArray, ProcessingError>{
+    let axis_ids = axis_ids_checked(&meta.id, meta.axes().iter().map(|axis| axis.id()), tensor)?;
+    let mut current = tensor.clone();
+    for step in meta.preprocessing(){
+        let mut labeled = LabeledTensor{tensor_id: &meta.id, axis_ids: &axis_ids, data: current.into_f32_array()};
+        current = match step{
+            PreprocessingDescr::Binarize(descr) => binarize(labeled, descr)?,
+            PreprocessingDescr::EnsureDtype(descr) => ensure_dtype(labeled, descr.dtype),
+            PreprocessingDescr::Clip(descr) => { clip(&mut labeled, descr); labeled.into() },
+            PreprocessingDescr::ScaleLinear(descr) => { scale_linear(&mut labeled, descr)?; labeled.into() },
+            PreprocessingDescr::Sigmoid(_) => { sigmoid(&mut labeled); labeled.into() },
+            PreprocessingDescr::FixedZeroMeanUnitVariance(descr) => { fixed_zmuv(&mut labeled, descr)?; labeled.into() },
+            PreprocessingDescr::ZeroMeanUnitVariance(descr) => { zmuv(&mut labeled, descr)?; labeled.into() },
+            PreprocessingDescr::ScaleRange(descr) => { scale_range(&mut labeled, descr, sample)?; labeled.into() },
+        };
+    }
+    Ok(current)
+}
+
+/// Applies the postprocessing steps described in `meta` to the raw network output `tensor`, in order.
+///
+/// Steps that reference other tensors (`scale_range`, `scale_mean_variance`) look them up in `sample`,
+/// which would usually contain the preprocessed inputs and the raw outputs of the network.
+pub fn postprocess(meta: &OutputTensorMetadata, tensor: &NpyArray, sample: &Sample<'_>) -> Result<NpyArray, ProcessingError>{
+    let axis_ids = axis_ids_checked(&meta.id, meta.axes().iter().map(|axis| axis.id()), tensor)?;
+    let mut current = tensor.clone();
+    for step in meta.postprocessing(){
+        let mut labeled = LabeledTensor{tensor_id: &meta.id, axis_ids: &axis_ids, data: current.into_f32_array()};
+        current = match step{
+            PostprocessingDescr::Binarize(descr) => binarize(labeled, descr)?,
+            PostprocessingDescr::EnsureDtype(descr) => ensure_dtype(labeled, descr.dtype),
+            PostprocessingDescr::Clip(descr) => { clip(&mut labeled, descr); labeled.into() },
+            PostprocessingDescr::ScaleLinear(descr) => { scale_linear(&mut labeled, descr)?; labeled.into() },
+            PostprocessingDescr::Sigmoid(_) => { sigmoid(&mut labeled); labeled.into() },
+            PostprocessingDescr::FixedZeroMeanUnitVariance(descr) => { fixed_zmuv(&mut labeled, descr)?; labeled.into() },
+            PostprocessingDescr::ZeroMeanUnitVariance(descr) => { zmuv(&mut labeled, descr)?; labeled.into() },
+            PostprocessingDescr::ScaleRange(descr) => { scale_range(&mut labeled, descr, sample)?; labeled.into() },
+            PostprocessingDescr::ScaleMeanVarianceDescr(descr) => {
+                scale_mean_variance(&mut labeled, descr, sample)?;
+                labeled.into()
+            },
+        };
+    }
+    Ok(current)
+}
+
+#[test]
+fn test_reduce_over_subset_of_axes(){
+    // shape (channel=2, x=3)
+    let data = ArrayD::from_shape_vec(IxDyn(&[2, 3]), vec![1.0, 2.0, 3.0, 10.0, 20.0, 30.0]).unwrap();
+    let means = reduce(&data, &[1], |values| mean(values) as f32);
+    assert_eq!(means.shape(), &[2, 1]);
+    assert_eq!(means.iter().copied().collect::<Vec<_>>(), vec![2.0, 20.0]);
+
+    let maxes = reduce(&data, &[0], |values| values.iter().copied().fold(f32::MIN, f32::max));
+    assert_eq!(maxes.shape(), &[1, 3]);
+    assert_eq!(maxes.iter().copied().collect::<Vec<_>>(), vec![10.0, 20.0, 30.0]);
+
+    let mut sorted: Vec<f32> = vec![4.0, 1.0, 3.0, 2.0];
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    assert_eq!(percentile(&sorted, 50.0), 2.5);
+    assert_eq!(percentile(&sorted, 100.0), 4.0);
+}
+
+#[test]
+fn test_scale_mean_variance_against_reference(){
+    let id = |raw: &str| serde_json::from_value::<TensorId>(serde_json::json!(raw)).unwrap();
+    let axis = |raw: &str| serde_json::from_value::<AxisId>(serde_json::json!(raw)).unwrap();
+    let descr: ScaleMeanVarianceDescr = serde_json::from_value(
+        serde_json::json!({"reference_tensor": "reference", "axes": ["x"], "eps": 0.1})
+    ).unwrap();
+
+    // the reference has its axes in a different order: (x=2, channel=2)
+    let reference = NpyArray::ArrayF32(ArrayD::from_shape_vec(IxDyn(&[2, 2]), vec![0.0, 2.0, 4.0, 2.0]).unwrap());
+    let mut sample = Sample::new();
+    sample.insert(id("reference"), vec![axis("x"), axis("channel")], &reference);
+
+    // target is (channel=2, x=2); channel 0 has mean 2, std 1 and channel 1 has mean 20, std 10
+    let tensor_id = id("target");
+    let axis_ids = [axis("channel"), axis("x")];
+    let data = ArrayD::from_shape_vec(IxDyn(&[2, 2]), vec![1.0, 3.0, 10.0, 30.0]).unwrap();
+    let mut tensor = LabeledTensor{tensor_id: &tensor_id, axis_ids: &axis_ids, data};
+    scale_mean_variance(&mut tensor, &descr, &sample).unwrap();
+
+    // reference channel 0 has mean 2, std 2; channel 1 has mean 2, std 0
+    // out = (v - mean) / (std + eps) * (ref_std + eps) + ref_mean
+    let expected = [
+        (1.0 - 2.0) / 1.1 * 2.1 + 2.0,
+        (3.0 - 2.0) / 1.1 * 2.1 + 2.0,
+        (10.0 - 20.0) / 10.1 * 0.1 + 2.0,
+        (30.0 - 20.0) / 10.1 * 0.1 + 2.0,
+    ];
+    for (value, expected) in tensor.data.iter().zip(expected){
+        assert!((value - expected).abs() < 1e-5, "{value} != {expected}");
+    }
+
+    let empty_sample = Sample::new();
+    assert!(matches!(
+        scale_mean_variance(&mut tensor, &descr, &empty_sample),
+        Err(ProcessingError::MissingReferenceTensor(_))
+    ));
+}
+
+#[cfg(test)]
+fn preprocess_json(steps: serde_json::Value, axes: serde_json::Value, data: ArrayD<f32>) -> NpyArray{
+    let meta: InputTensorMetadata = serde_json::from_value(
+        serde_json::json!({"id": "raw", "axes": axes, "preprocessing": steps})
+    ).unwrap();
+    preprocess(&meta, &NpyArray::ArrayF32(data), &Sample::new()).unwrap()
+}
+
+#[cfg(test)]
+fn assert_close(found: &NpyArray, expected: &[f32]){
+    let found = found.as_array::<f32>().unwrap();
+    assert_eq!(found.len(), expected.len());
+    for (value, expected) in found.iter().zip(expected){
+        assert!((value - expected).abs() < 1e-5, "{value} != {expected}");
+    }
+}
+
+#[test]
+fn test_preprocess_along_channel_axis(){
+    use serde_json::json;
+
+    let axes = json!([
+        {"type": "batch"}, {"type": "channel", "channel_names": ["a", "b"]}, {"type": "space", "id": "x", "size": 3},
+    ]);
+    // shape (batch=1, channel=2, x=3)
+    let data = ArrayD::from_shape_vec(IxDyn(&[1, 2, 3]), vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0]).unwrap();
+
+    let scaled = preprocess_json(
+        json!([{"id": "scale_linear", "kwargs": {"axis": "channel", "gain": [2.0, -1.0], "offset": [1.0, 10.0]}}]),
+        axes.clone(),
+        data.clone(),
+    );
+    assert_close(&scaled, &[3.0, 5.0, 7.0, 6.0, 5.0, 4.0]);
+
+    let binarized = preprocess_json(
+        json!([{"id": "binarize", "kwargs": {"axis": "channel", "threshold": [2.5, 4.5]}}]), axes, data,
+    );
+    let expected = ArrayD::from_shape_vec(IxDyn(&[1, 2, 3]), vec![false, false, true, false, true, true]).unwrap();
+    assert_eq!(binarized.as_array::<bool>(), Some(&expected));
+}
+
+#[test]
+fn test_preprocess_with_statistics_over_some_axes(){
+    use serde_json::json;
+
+    let axes = json!([
+        {"type": "batch"}, {"type": "channel", "channel_names": ["a", "b"]}, {"type": "space", "id": "x", "size": 3},
+    ]);
+    // channel a has mean 2 and std sqrt(2/3); channel b is constant
+    let data = ArrayD::from_shape_vec(IxDyn(&[1, 2, 3]), vec![1.0, 2.0, 3.0, 4.0, 4.0, 4.0]).unwrap();
+    let std = (2.0f32 / 3.0).sqrt();
+    let normalized = preprocess_json(
+        json!([{"id": "zero_mean_unit_variance", "kwargs": {"axes": ["batch", "x"], "eps": 0.1}}]), axes.clone(), data.clone(),
+    );
+    assert_close(&normalized, &[-1.0 / (std + 0.1), 0.0, 1.0 / (std + 0.1), 0.0, 0.0, 0.0]);
+    // the default epsilon still keeps constant channels finite
+    let normalized = preprocess_json(
+        json!([{"id": "zero_mean_unit_variance", "kwargs": {"axes": ["batch", "x"]}}]), axes, data,
+    );
+    assert_close(&normalized, &[-1.0 / (std + 1e-6), 0.0, 1.0 / (std + 1e-6), 0.0, 0.0, 0.0]);
+
+    // each sample of shape (batch=2, x=3) is scaled between its own median and maximum
+    let axes = json!([{"type": "batch"}, {"type": "space", "id": "x", "size": 3}]);
+    let data = ArrayD::from_shape_vec(IxDyn(&[2, 3]), vec![0.0, 5.0, 10.0, 30.0, 10.0, 20.0]).unwrap();
+    let scaled = preprocess_json(
+        json!([{"id": "scale_range", "kwargs": {"axes": ["x"], "min_percentile": 50.0, "max_percentile": 100.0}}]),
+        axes,
+        data,
+    );
+    assert_close(&scaled, &[-1.0, 0.0, 1.0, 1.0, -1.0, 0.0]);
+}
+
+#[test]
+fn test_preprocess_ensure_dtype(){
+    use serde_json::json;
+
+    let axes = json!([{"type": "batch"}, {"type": "space", "id": "x", "size": 3}]);
+    let data = ArrayD::from_shape_vec(IxDyn(&[1, 3]), vec![0.0, 127.6, 255.0]).unwrap();
+    let converted = preprocess_json(json!([{"id": "ensure_dtype", "kwargs": {"dtype": "uint8"}}]), axes, data);
+    assert_eq!(converted.as_array::<u8>().unwrap().iter().copied().collect::<Vec<_>>(), [0, 127, 255]);
+}
@@ -0,0 +1,225 @@
+use std::io::{Cursor, Seek, Write};
+use std::sync::Arc;
+
+use bioimg_spec::rdf;
+use bioimg_spec::rdf::model::{AxisType, DataType, TensorId};
+use image::codecs::png::PngEncoder;
+use ndarray::{ArrayD, Axis};
+
+use crate::file_source::FileSourceError;
+use crate::tensor_import::{ImportedTensor, TensorImportError};
+use crate::model_package::ModelPackage;
+use crate::url_fetcher::UrlFetcher;
+use crate::zip_writer_ext::ModelZipWriter;
+use crate::zoo_model::ModelPackingError;
+use crate::{FileSource, NpyArray};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SampleTensorFormat{
+    Png,
+    Tiff,
+    Jpeg,
+}
+
+impl SampleTensorFormat{
+    pub fn extension(&self) -> &'static str{
+        match self{
+            Self::Png => "png",
+            Self::Tiff => "tif",
+            Self::Jpeg => "jpg",
+        }
+    }
+
+    fn detect(bytes: &[u8]) -> Option<Self>{
+        const TIFF_MAGICS: [&[u8]; 4] = [b"II*\0", b"MM\0*", b"II+\0", b"MM\0+"];
+        if bytes.starts_with(b"\x89PNG\r\n\x1a\n"){
+            Some(Self::Png)
+        } else if TIFF_MAGICS.iter().any(|magic| bytes.starts_with(magic)){
+            Some(Self::Tiff)
+        } else if bytes.starts_with(&[0xFF, 0xD8, 0xFF]){
+            Some(Self::Jpeg)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum SampleTensorParsingError{
+    #[error("Sample tensors must be PNG, TIFF or JPEG images")]
+    UnsupportedFormat,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum SampleTensorLoadingError{
+    #[error(transparent)]
+    FileSourceError(#[from] FileSourceError),
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
+    #[error(transparent)]
+    ParsingError(#[from] SampleTensorParsingError),
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum SampleTensorGenerationError{
+    #[error("Test tensor has {num_dims} dimensions but there are {num_axes} axes")]
+    MismatchedNumAxes{num_dims: usize, num_axes: usize},
+    #[error("A sample image needs at least 2 space axes")]
+    NotEnoughSpaceAxes,
+    #[error(transparent)]
+    ImageError(#[from] image::ImageError),
+    #[error(transparent)]
+    TiffError(#[from] tiff::TiffError),
+}
+
+/// An image file illustrating what a tensor looks like, meant for humans rather than for testing the model.
+/// The encoded bytes are kept as they are so that re-packaging a model doesn't alter its samples
+#[derive(Clone)]
+pub struct SampleTensor{
+    bytes: Arc<[u8]>,
+    format: SampleTensorFormat,
+}
+
+impl SampleTensor{
+    pub fn try_from_bytes(bytes: impl Into<Arc<[u8]>>) -> Result<Self, SampleTensorParsingError>{
+        let bytes: Arc<[u8]> = bytes.into();
+        let format = SampleTensorFormat::detect(&bytes).ok_or(SampleTensorParsingError::UnsupportedFormat)?;
+        Ok(Self{bytes, format})
+    }
+
+    pub fn bytes(&self) -> &[u8]{
+        &self.bytes
+    }
+
+    pub fn format(&self) -> SampleTensorFormat{
+        self.format
+    }
+
+    /// Decodes the image into its pixel data
+    pub fn decode(&self) -> Result<ImportedTensor, TensorImportError>{
+        ImportedTensor::try_load(Cursor::new(&*self.bytes))
+    }
+
+    pub fn try_load(
+        package: ModelPackage, rdf: &rdf::FileDescription, fetcher: &dyn UrlFetcher,
+    ) -> Result<Self, SampleTensorLoadingError>{
+        let source = FileSource::from_rdf_file_descr(package, rdf)?;
+        let bytes = source.with_checked_reader(rdf.sha256.as_ref(), fetcher, |reader| {
+            let mut bytes = Vec::<u8>::new();
+            reader.read_to_end(&mut bytes).map(|_| bytes)
+        })??;
+        Ok(Self::try_from_bytes(bytes)?)
+    }
+
+    pub fn dump(
+        &self,
+        zip_file: &mut ModelZipWriter<impl Write + Seek>,
+        tensor_dir: &str,
+        tensor_id: &TensorId,
+    ) -> Result<rdf::FileDescription, ModelPackingError>{
+        let extension = self.format.extension();
+        let zip_path = zip_file.entry_path(
+            &format!("{tensor_dir}/sample_tensor.{extension}"),
+            &format!("_{tensor_id}_sample_tensor.{extension}"),
+        );
+        let (_, sha256) = zip_file.write_file(&zip_path, |writer| writer.write_all(&self.bytes))?;
+        Ok(rdf::FileDescription{source: zip_path.into(), sha256: Some(sha256)})
+    }
+
+    /// Renders a 2D view of `test_tensor`: the last two space axes become the image plane, the middle
+    /// slice is taken along any other space axis and the first entry along every other axis. A channel axis
+    /// with 3 entries is rendered as RGB.
+    ///
+    /// 8 and 16 bit unsigned data is stored losslessly as PNG, everything else as a float32 TIFF
+    pub fn from_test_tensor(test_tensor: &NpyArray, axis_types: &[AxisType]) -> Result<Self, SampleTensorGenerationError>{
+        let shape = test_tensor.shape();
+        if shape.len() != axis_types.len(){
+            return Err(SampleTensorGenerationError::MismatchedNumAxes { num_dims: shape.len(), num_axes: axis_types.len() })
+        }
+        let space_axes: Vec<usize> = axis_types.iter().enumerate()
+            .filter(|(_, axis_type)| **axis_type == AxisType::Space)
+            .map(|(idx, _)| idx)
+            .collect();
+        let [.., y_axis, x_axis] = space_axes.as_slice() else {
+            return Err(SampleTensorGenerationError::NotEnoughSpaceAxes)
+        };
+        let (y_axis, x_axis) = (*y_axis, *x_axis);
+        let rgb_axis = axis_types.iter().position(|axis_type| *axis_type == AxisType::Channel)
+            .filter(|idx| shape[*idx] == 3);
+
+        let mut plane: ArrayD<f32> = test_tensor.to_f32_array();
+        for (axis_idx, axis_type) in axis_types.iter().enumerate().rev(){
+            if axis_idx == y_axis || axis_idx == x_axis || Some(axis_idx) == rgb_axis{
+                continue
+            }
+            let index = if *axis_type == AxisType::Space { shape[axis_idx] / 2 } else { 0 };
+            plane = plane.index_axis_move(Axis(axis_idx), index);
+        }
+        // remaining axes are in their original order; move them into (y, x, [c])
+        let mut kept: Vec<usize> = [Some(y_axis), Some(x_axis), rgb_axis].into_iter().flatten().collect();
+        kept.sort();
+        let order: Vec<usize> = [Some(y_axis), Some(x_axis), rgb_axis].into_iter().flatten()
+            .map(|axis_idx| kept.iter().position(|k| *k == axis_idx).unwrap())
+            .collect();
+        let plane = plane.permuted_axes(order);
+        let (height, width) = (plane.shape()[0] as u32, plane.shape()[1] as u32);
+        let values: Vec<f32> = plane.as_standard_layout().iter().copied().collect();
+
+        let mut bytes = Vec::<u8>::new();
+        let format = match test_tensor.dtype(){
+            Some(DataType::Uint8 | DataType::Bool) => {
+                let is_bool = test_tensor.dtype() == Some(DataType::Bool);
+                let raw: Vec<u8> = values.iter()
+                    .map(|v| if is_bool && *v != 0.0 { 255 } else { *v as u8 })
+                    .collect();
+                let img = match rgb_axis{
+                    Some(_) => image::DynamicImage::ImageRgb8(image::ImageBuffer::from_raw(width, height, raw).unwrap()),
+                    None => image::DynamicImage::ImageLuma8(image::ImageBuffer::from_raw(width, height, raw).unwrap()),
+                };
+                img.write_with_encoder(PngEncoder::new(&mut bytes))?;
+                SampleTensorFormat::Png
+            },
+            Some(DataType::Uint16) => {
+                let raw: Vec<u16> = values.iter().map(|v| *v as u16).collect();
+                let img = match rgb_axis{
+                    Some(_) => image::DynamicImage::ImageRgb16(image::ImageBuffer::from_raw(width, height, raw).unwrap()),
+                    None => image::DynamicImage::ImageLuma16(image::ImageBuffer::from_raw(width, height, raw).unwrap()),
+                };
+                img.write_with_encoder(PngEncoder::new(&mut bytes))?;
+                SampleTensorFormat::Png
+            },
+            _ => {
+                use tiff::encoder::{colortype, TiffEncoder};
+                let mut encoder = TiffEncoder::new(Cursor::new(&mut bytes))?;
+                match rgb_axis{
+                    Some(_) => encoder.write_image::<colortype::RGB32Float>(width, height, &values)?,
+                    None => encoder.write_image::<colortype::Gray32Float>(width, height, &values)?,
+                }
+                SampleTensorFormat::Tiff
+            },
+        };
+        Ok(Self{bytes: Arc::from(bytes), format})
+    }
+}
+
+#[test]
+fn test_generating_sample_from_test_tensor(){
+    use ndarray::Array;
+
+    let data = Array::from_shape_fn((1, 3, 4, 5, 6), |(_, c, z, y, x)| (c * 1000 + z * 100 + y * 10 + x) as u16).into_dyn();
+    let axis_types = [AxisType::Batch, AxisType::Channel, AxisType::Space, AxisType::Space, AxisType::Space];
+    let sample = SampleTensor::from_test_tensor(&NpyArray::from(data), &axis_types).unwrap();
+    assert_eq!(sample.format(), SampleTensorFormat::Png);
+
+    let decoded = sample.decode().unwrap();
+    assert_eq!(decoded.data.shape(), &[5, 6, 3]);
+    let pixels: &ArrayD<u16> = (&decoded.data).try_into().unwrap();
+    assert_eq!(pixels[[4, 5, 2]], 2245);
+
+    let float_data = Array::from_shape_fn((5, 6), |(y, x)| (y * 6 + x) as f32 / 2.0).into_dyn();
+    let sample = SampleTensor::from_test_tensor(&NpyArray::from(float_data), &[AxisType::Space, AxisType::Space]).unwrap();
+    assert_eq!(sample.format(), SampleTensorFormat::Tiff);
+    let decoded = sample.decode().unwrap();
+    let pixels: &ArrayD<f32> = (&decoded.data).try_into().unwrap();
+    assert_eq!(pixels[[4, 5]], 14.5);
+}
@@ -0,0 +1,275 @@
+use std::borrow::Borrow;
+use std::collections::HashMap;
+
+use bioimg_spec::rdf::model::{AxisId, QualifiedAxisId, ResolvedAxisSize, TensorId};
+
+use crate::axis_size_resolver::{AxisSizeResolutionError, ResolvedAxisSizeExt, SlotResolver};
+use crate::model_interface::{VecInputSlotExt, VecOutputSlotExt};
+use crate::{ModelInterface, NpyArray};
+
+#[derive(thiserror::Error, Debug)]
+pub enum ShapeInferenceError{
+    #[error("Expected {expected} input shapes, found {found}")]
+    MismatchedNumTensors{expected: usize, found: usize},
+    #[error("Shape {shape:?} of input '{tensor_id}' does not match its {num_axes} axes")]
+    MismatchedNumDimensions{tensor_id: TensorId, shape: Vec<usize>, num_axes: usize},
+    #[error("Extent {extent} is not valid for axis '{qualified_axis_id}'")]
+    IncompatibleExtent{qualified_axis_id: QualifiedAxisId, extent: usize},
+    #[error("Axis '{qualified_axis_id}' has extent {extent}, but its reference requires {expected}")]
+    MismatchedReference{qualified_axis_id: QualifiedAxisId, extent: usize, expected: usize},
+    #[error("Extent of axis '{0}' is only known after running the model")]
+    UnknownUntilRun(QualifiedAxisId),
+    #[error("Inputs have different batch sizes ({0} and {1})")]
+    MismatchedBatchSizes(usize, usize),
+    #[error("Axis '{qualified_axis_id}' has extent {extent}, which leaves nothing once its halo of {halo} is cropped from both sides")]
+    HaloTooLarge{qualified_axis_id: QualifiedAxisId, extent: usize, halo: usize},
+    #[error(transparent)]
+    AxisSizeResolutionError(#[from] AxisSizeResolutionError),
+}
+
+/// The concrete shape of a tensor, along with the halo that should be cropped from each side of each axis
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TensorShape{
+    pub tensor_id: TensorId,
+    pub axis_ids: Vec<AxisId>,
+    pub shape: Vec<usize>,
+    pub halo: Vec<usize>,
+}
+
+impl TensorShape{
+    /// The shape of the tensor after cropping away the halo
+    pub fn valid_shape(&self) -> Vec<usize>{
+        self.shape.iter().zip(&self.halo).map(|(extent, halo)| extent.saturating_sub(2 * halo)).collect()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ConcreteShapes{
+    pub inputs: Vec<TensorShape>,
+    pub outputs: Vec<TensorShape>,
+}
+
+/// Where the extent of a non-referencing axis comes from
+enum ExtentSource<'a>{
+    /// Extents of the input tensors, as provided by the caller
+    InputShapes(HashMap<QualifiedAxisId, usize>),
+    /// Number of steps for each parameterized axis (missing means 0)
+    ParameterizedSteps(&'a HashMap<QualifiedAxisId, usize>),
+}
+
+struct ShapeSolver<'a>{
+    resolver: SlotResolver,
+    source: ExtentSource<'a>,
+    batch_size: usize,
+}
+
+impl ShapeSolver<'_>{
+    fn extent_of(&self, qual_id: &QualifiedAxisId) -> Result<usize, ShapeInferenceError>{
+        let chain = self.resolver.trace(qual_id)?;
+        let root = chain.root();
+        let root_extent = match (chain.root_size(), &self.source){
+            (None, _) => self.batch_size,
+            (Some(ResolvedAxisSize::Fixed(fixed)), _) => usize::from(*fixed),
+            (Some(ResolvedAxisSize::Parameterized(_)), ExtentSource::InputShapes(extents)) => match extents.get(root){
+                Some(extent) => *extent,
+                None => return Err(ShapeInferenceError::UnknownUntilRun(root.clone())),
+            },
+            (Some(ResolvedAxisSize::Parameterized(size)), ExtentSource::ParameterizedSteps(steps)) => {
+                let n = steps.get(root).copied().unwrap_or(0);
+                usize::from(size.min) + n * usize::from(size.step)
+            },
+        };
+        let extent = chain.extent_from_root(root_extent);
+
+        let given = match &self.source{
+            ExtentSource::InputShapes(extents) => extents.get(qual_id).copied(),
+            ExtentSource::ParameterizedSteps(_) => None,
+        };
+        match (given, chain.root_size()){
+            (Some(given), _) if chain.is_reference() && given != extent => {
+                Err(ShapeInferenceError::MismatchedReference { qualified_axis_id: qual_id.clone(), extent: given, expected: extent })
+            },
+            (Some(given), Some(size)) if !chain.is_reference() && !size.is_compatible_with_extent(given) => {
+                Err(ShapeInferenceError::IncompatibleExtent { qualified_axis_id: qual_id.clone(), extent: given })
+            },
+            _ => Ok(extent),
+        }
+    }
+}
+
+/// Records the extent of every axis of the tensor `tensor_id`, whose axes are given as their id and
+/// whether they are a batch axis, checking that all batch axes agree on the batch size
+fn record_extents(
+    extents: &mut HashMap<QualifiedAxisId, usize>,
+    batch_size: &mut Option<usize>,
+    tensor_id: &TensorId,
+    axes: impl ExactSizeIterator<Item=(AxisId, bool)>,
+    shape: &[usize],
+) -> Result<(), ShapeInferenceError>{
+    if shape.len() != axes.len(){
+        return Err(ShapeInferenceError::MismatchedNumDimensions {
+            tensor_id: tensor_id.clone(), shape: shape.to_vec(), num_axes: axes.len()
+        })
+    }
+    for ((axis_id, is_batch), extent) in axes.zip(shape.iter()){
+        if is_batch{
+            match batch_size{
+                Some(size) if *size != *extent => return Err(ShapeInferenceError::MismatchedBatchSizes(*size, *extent)),
+                _ => *batch_size = Some(*extent),
+            }
+        }
+        extents.insert(QualifiedAxisId{tensor_id: tensor_id.clone(), axis_id}, *extent);
+    }
+    Ok(())
+}
+
+impl<DATA: Borrow<NpyArray>> ModelInterface<DATA>{
+    fn solve_shapes(&self, source: ExtentSource<'_>, batch_size: usize) -> Result<ConcreteShapes, ShapeInferenceError>{
+        let solver = ShapeSolver{resolver: self.axis_resolver()?, source, batch_size};
+
+        let mut inputs = Vec::with_capacity(self.inputs().len().get());
+        for slot in self.inputs().iter(){
+            let axis_ids: Vec<AxisId> = slot.tensor_meta.axes().iter().map(|axis| axis.id()).collect();
+            let shape = axis_ids.iter()
+                .map(|axis_id| solver.extent_of(&QualifiedAxisId{tensor_id: slot.tensor_meta.id.clone(), axis_id: axis_id.clone()}))
+                .collect::<Result<_, _>>()?;
+            let halo = vec![0; axis_ids.len()];
+            inputs.push(TensorShape{tensor_id: slot.tensor_meta.id.clone(), axis_ids, shape, halo});
+        }
+        let mut outputs = Vec::with_capacity(self.outputs().len().get());
+        for slot in self.outputs().iter(){
+            let axis_ids: Vec<AxisId> = slot.tensor_meta.axes().iter().map(|axis| axis.id()).collect();
+            let shape = axis_ids.iter()
+                .map(|axis_id| solver.extent_of(&QualifiedAxisId{tensor_id: slot.tensor_meta.id.clone(), axis_id: axis_id.clone()}))
+                .collect::<Result<_, _>>()?;
+            let halo = slot.tensor_meta.axes().iter()
+                .map(|axis| axis.halo().map(|halo| u64::from(halo) as usize).unwrap_or(0))
+                .collect();
+            outputs.push(TensorShape{tensor_id: slot.tensor_meta.id.clone(), axis_ids, shape, halo});
+        }
+        Ok(ConcreteShapes{inputs, outputs})
+    }
+
+    /// Computes the shapes of all tensors given the shapes of the inputs (in the same order
+    /// as the inputs of this interface), checking that those shapes are valid for the input axes
+    pub fn infer_shapes(&self, input_shapes: &[&[usize]]) -> Result<ConcreteShapes, ShapeInferenceError>{
+        if input_shapes.len() != self.inputs().len().get(){
+            return Err(ShapeInferenceError::MismatchedNumTensors {
+                expected: self.inputs().len().get(), found: input_shapes.len()
+            })
+        }
+        let mut extents = HashMap::new();
+        let mut batch_size: Option<usize> = None;
+        for (slot, shape) in self.inputs().iter().zip(input_shapes){
+            let axes = slot.tensor_meta.axes().iter().map(|axis| (axis.id(), axis.size().is_none()));
+            record_extents(&mut extents, &mut batch_size, &slot.tensor_meta.id, axes, shape)?;
+        }
+        self.solve_shapes(ExtentSource::InputShapes(extents), batch_size.unwrap_or(1))
+    }
+
+    /// Checks the shapes of all test tensors against each other: every axis that references another one must
+    /// have the extent computed from the test tensor of the referenced axis (with scales and offsets applied),
+    /// every other axis must be compatible with its own size, and every halo must fit inside its output
+    pub fn validate_test_tensor_shapes(&self) -> Result<(), ShapeInferenceError>{
+        let mut extents = HashMap::new();
+        let mut batch_size: Option<usize> = None;
+        for slot in self.inputs().iter(){
+            let axes = slot.tensor_meta.axes().iter().map(|axis| (axis.id(), axis.size().is_none()));
+            record_extents(&mut extents, &mut batch_size, &slot.tensor_meta.id, axes, slot.test_tensor.borrow().shape())?;
+        }
+        for slot in self.outputs().iter(){
+            let axes = slot.tensor_meta.axes().iter().map(|axis| (axis.id(), axis.size().is_none()));
+            record_extents(&mut extents, &mut batch_size, &slot.tensor_meta.id, axes, slot.test_tensor.borrow().shape())?;
+        }
+        // checked in the order of the descriptions, so that the first mismatch is the one reported
+        let qual_ids: Vec<QualifiedAxisId> = self.inputs().qual_id_axes().map(|(qual_id, _)| qual_id)
+            .chain(self.outputs().qual_id_axes().map(|(qual_id, _)| qual_id))
+            .collect();
+        let solver = ShapeSolver{
+            resolver: self.axis_resolver()?, source: ExtentSource::InputShapes(extents), batch_size: batch_size.unwrap_or(1)
+        };
+        for qual_id in &qual_ids{
+            solver.extent_of(qual_id)?;
+        }
+        for slot in self.outputs().iter(){
+            for (axis, extent) in slot.tensor_meta.axes().iter().zip(slot.test_tensor.borrow().shape()){
+                let halo = axis.halo().map(|halo| u64::from(halo) as usize).unwrap_or(0);
+                if halo > 0 && 2 * halo >= *extent{
+                    return Err(ShapeInferenceError::HaloTooLarge {
+                        qualified_axis_id: QualifiedAxisId{tensor_id: slot.tensor_meta.id.clone(), axis_id: axis.id()},
+                        extent: *extent,
+                        halo,
+                    })
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Computes the shapes of all tensors when each parameterized axis is `min + n * step`,
+    /// with `n` taken from `steps` (defaulting to 0)
+    pub fn shapes_for_steps(
+        &self, steps: &HashMap<QualifiedAxisId, usize>, batch_size: usize
+    ) -> Result<ConcreteShapes, ShapeInferenceError>{
+        self.solve_shapes(ExtentSource::ParameterizedSteps(steps), batch_size)
+    }
+}
+
+#[test]
+fn test_infer_shapes_through_reference_chains(){
+    use serde_json::json;
+
+    let qual_id = |tensor_id: &str, axis_id: &str| serde_json::from_value::<QualifiedAxisId>(
+        json!({"tensor_id": tensor_id, "axis_id": axis_id})
+    ).unwrap();
+    let inputs = [
+        (json!({"id": "raw", "axes": [{"type": "batch"}, {"type": "space", "id": "x", "size": {"min": 8, "step": 4}}]}), &[1usize, 8][..]),
+        (json!({"id": "aux", "axes": [{"type": "batch"}, {"type": "space", "id": "x", "size": {"tensor_id": "raw", "axis_id": "x"}}]}), &[1, 8]),
+    ];
+    // out.x follows mid.x, which is a third of raw.x plus one
+    let mid = json!({"id": "mid", "axes": [
+        {"type": "batch"}, {"type": "space", "id": "x", "size": {"tensor_id": "raw", "axis_id": "x", "offset": 1}, "scale": 3.0},
+    ]});
+    let out = json!({"id": "out", "axes": [
+        {"type": "batch"}, {"type": "space", "id": "x", "size": {"tensor_id": "mid", "axis_id": "x"}, "scale": 1.5},
+    ]});
+    // int(8 / 3) + 1 = 3, then twice that
+    let interface = crate::model_interface::interface_from_json(&inputs, &[(mid.clone(), &[1, 3]), (out.clone(), &[1, 6])]).unwrap();
+    assert!(matches!(
+        crate::model_interface::interface_from_json(&inputs, &[(mid.clone(), &[1, 8]), (out.clone(), &[1, 16])]),
+        Err(crate::model_interface::TensorValidationError::ShapeInferenceError(ShapeInferenceError::MismatchedReference {
+            qualified_axis_id, extent: 8, expected: 3
+        })) if qualified_axis_id == qual_id("mid", "x")
+    ));
+
+    let shapes = interface.infer_shapes(&[&[2, 20], &[2, 20]]).unwrap();
+    // int(20 / 3) + 1 = 7, then twice that
+    assert_eq!(shapes.outputs[0].shape, [2, 7]);
+    assert_eq!(shapes.outputs[1].shape, [2, 14]);
+    let shapes = interface.infer_shapes(&[&[1, 16], &[1, 16]]).unwrap();
+    // int(16 / 3) + 1 = 6
+    assert_eq!(shapes.outputs[0].shape, [1, 6]);
+    assert_eq!(shapes.outputs[1].shape, [1, 12]);
+
+    assert!(matches!(
+        interface.infer_shapes(&[&[1, 10], &[1, 10]]),
+        Err(ShapeInferenceError::IncompatibleExtent { qualified_axis_id, extent: 10 }) if qualified_axis_id == qual_id("raw", "x")
+    ));
+    assert!(matches!(
+        interface.infer_shapes(&[&[1, 20], &[1, 16]]),
+        Err(ShapeInferenceError::MismatchedReference { qualified_axis_id, extent: 16, expected: 20 }) if qualified_axis_id == qual_id("aux", "x")
+    ));
+    assert!(matches!(interface.infer_shapes(&[&[1, 20], &[2, 20]]), Err(ShapeInferenceError::MismatchedBatchSizes(1, 2))));
+
+    let free = json!({"id": "free", "axes": [{"type": "batch"}, {"type": "space", "id": "x", "size": {"min": 4, "step": 2}}]});
+    let interface = crate::model_interface::interface_from_json(&inputs, &[(mid, &[1, 3]), (out, &[1, 6]), (free, &[1, 4])]).unwrap();
+    assert!(matches!(
+        interface.infer_shapes(&[&[1, 20], &[1, 20]]),
+        Err(ShapeInferenceError::UnknownUntilRun(qualified_axis_id)) if qualified_axis_id == qual_id("free", "x")
+    ));
+    let steps = HashMap::from([(qual_id("raw", "x"), 1), (qual_id("free", "x"), 2)]);
+    let shapes = interface.shapes_for_steps(&steps, 3).unwrap();
+    assert_eq!(shapes.inputs[0].shape, [3, 12]);
+    assert_eq!(shapes.outputs[0].shape, [3, 5]);
+    assert_eq!(shapes.outputs[2].shape, [3, 8]);
+}
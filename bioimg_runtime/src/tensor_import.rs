@@ -0,0 +1,369 @@
+use std::io::{BufRead, Read, Seek, SeekFrom};
+
+use ndarray::{ArrayD, IxDyn};
+use tiff::decoder::{Decoder as TiffDecoder, DecodingResult, Limits};
+use tiff::tags::Tag;
+
+use crate::NpyArray;
+
+#[derive(thiserror::Error, Debug)]
+pub enum TensorImportError{
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
+    #[error("Could not read npy data: {0}")]
+    NpyError(#[from] ndarray_npy::ReadNpyError),
+    #[error("Could not read TIFF data: {0}")]
+    TiffError(#[from] tiff::TiffError),
+    #[error("Could not read image: {0}")]
+    ImageError(#[from] image::ImageError),
+    #[error("File contains no images")]
+    NoImages,
+    #[error("Not all pages of the TIFF file have the same dimensions and sample type")]
+    InconsistentPages,
+    #[error("Image metadata does not match the image data: {reason}")]
+    BadMetadata{reason: String},
+}
+
+impl From<ndarray::ShapeError> for TensorImportError{
+    fn from(value: ndarray::ShapeError) -> Self {
+        Self::BadMetadata { reason: value.to_string() }
+    }
+}
+
+/// The physical extent of a single pixel along some axis, e.g. `0.5 µm`
+#[derive(Clone, Debug, PartialEq)]
+pub struct PhysicalSize{
+    pub value: f64,
+    pub unit: Option<String>,
+}
+
+/// A tensor read from an image file, along with what could be detected about its axes
+pub struct ImportedTensor{
+    pub data: NpyArray,
+    /// One letter per dimension of `data` (out of `t`, `c`, `z`, `y` and `x`, or `?` if unknown), in C-order.
+    /// Singleton time, channel and z dimensions are squeezed away
+    pub dim_order: String,
+    /// The size of a pixel along each dimension in `dim_order`, if known
+    pub pixel_sizes: Vec<Option<PhysicalSize>>,
+}
+
+const NPY_MAGIC: &[u8] = b"\x93NUMPY";
+const TIFF_MAGICS: [&[u8]; 4] = [b"II*\0", b"MM\0*", b"II+\0", b"MM\0+"];
+
+fn starts_with_any(header: &[u8], magics: &[&[u8]]) -> bool{
+    magics.iter().any(|magic| header.starts_with(magic))
+}
+
+impl ImportedTensor{
+    /// The file extensions that can be imported, for use in file pickers
+    pub const EXTENSIONS: &'static [&'static str] = &["npy", "tif", "tiff", "png", "jpg", "jpeg"];
+
+    /// Reads a `.npy`, TIFF (including multi-page, ImageJ and OME-TIFF), PNG or JPEG file, detecting
+    /// the format from its contents
+    pub fn try_load<R: BufRead + Seek>(mut reader: R) -> Result<Self, TensorImportError>{
+        let header = Self::peek_header(&mut reader)?;
+        if header.starts_with(NPY_MAGIC){
+            let data = NpyArray::try_load(reader)?;
+            return Ok(Self::from_npy(data))
+        }
+        if starts_with_any(&header, &TIFF_MAGICS){
+            return Self::try_load_tiff(reader)
+        }
+        let img = image::io::Reader::new(reader).with_guessed_format()?.decode()?;
+        Ok(Self::from_dynamic_image(img))
+    }
+
+    #[cfg(not(target_arch="wasm32"))]
+    pub fn try_load_path(path: &std::path::Path) -> Result<Self, TensorImportError>{
+        Self::try_load(std::io::BufReader::new(std::fs::File::open(path)?))
+    }
+
+    fn peek_header(reader: &mut (impl Read + Seek)) -> Result<Vec<u8>, TensorImportError>{
+        let mut header = Vec::with_capacity(8);
+        reader.by_ref().take(8).read_to_end(&mut header)?;
+        reader.seek(SeekFrom::Start(0))?;
+        Ok(header)
+    }
+
+    fn from_npy(data: NpyArray) -> Self{
+        // npy files carry no axis information
+        let ndim = data.shape().len();
+        Self{data, dim_order: "?".repeat(ndim), pixel_sizes: vec![None; ndim]}
+    }
+
+    fn from_dynamic_image(img: image::DynamicImage) -> Self{
+        use image::DynamicImage as Img;
+        let (width, height) = (img.width() as usize, img.height() as usize);
+        let num_channels = img.color().channel_count() as usize;
+        let shape = if num_channels == 1 { vec![height, width] } else { vec![height, width, num_channels] };
+        let ix = IxDyn(&shape);
+        let data: NpyArray = match img{
+            Img::ImageLuma8(img) => ArrayD::from_shape_vec(ix, img.into_raw()).map(NpyArray::from),
+            Img::ImageLumaA8(img) => ArrayD::from_shape_vec(ix, img.into_raw()).map(NpyArray::from),
+            Img::ImageRgb8(img) => ArrayD::from_shape_vec(ix, img.into_raw()).map(NpyArray::from),
+            Img::ImageRgba8(img) => ArrayD::from_shape_vec(ix, img.into_raw()).map(NpyArray::from),
+            Img::ImageLuma16(img) => ArrayD::from_shape_vec(ix, img.into_raw()).map(NpyArray::from),
+            Img::ImageLumaA16(img) => ArrayD::from_shape_vec(ix, img.into_raw()).map(NpyArray::from),
+            Img::ImageRgb16(img) => ArrayD::from_shape_vec(ix, img.into_raw()).map(NpyArray::from),
+            Img::ImageRgba16(img) => ArrayD::from_shape_vec(ix, img.into_raw()).map(NpyArray::from),
+            Img::ImageRgb32F(img) => ArrayD::from_shape_vec(ix, img.into_raw()).map(NpyArray::from),
+            Img::ImageRgba32F(img) => ArrayD::from_shape_vec(ix, img.into_raw()).map(NpyArray::from),
+            other => {
+                let shape = [height, width, 4];
+                ArrayD::from_shape_vec(IxDyn(&shape), other.into_rgba32f().into_raw()).map(NpyArray::from)
+            },
+        }.expect("Image buffer should match its own dimensions");
+        let dim_order = if data.shape().len() == 2 { "yx" } else { "yxc" }.to_owned();
+        let pixel_sizes = vec![None; dim_order.len()];
+        Self{data, dim_order, pixel_sizes}
+    }
+
+    fn try_load_tiff(reader: impl Read + Seek) -> Result<Self, TensorImportError>{
+        let mut decoder = TiffDecoder::new(reader)?.with_limits(Limits::unlimited());
+        let (width, height) = decoder.dimensions()?;
+        let samples_per_pixel = decoder.find_tag_unsigned::<usize>(Tag::SamplesPerPixel)?.unwrap_or(1);
+        let description = decoder.get_tag_ascii_string(Tag::ImageDescription).ok();
+        let resolution = TiffResolution::read(&mut decoder)?;
+
+        let mut pages = vec![decoder.read_image()?];
+        while decoder.more_images(){
+            decoder.next_image()?;
+            if decoder.dimensions()? != (width, height){
+                return Err(TensorImportError::InconsistentPages)
+            }
+            pages.push(decoder.read_image()?);
+        }
+
+        let layout = match description.as_deref(){
+            Some(desc) if desc.contains("<OME") => PageLayout::from_ome(desc, resolution)?,
+            Some(desc) if desc.starts_with("ImageJ=") => PageLayout::from_imagej(desc, resolution),
+            _ => PageLayout::plain(pages.len(), resolution),
+        };
+        let num_pages: usize = layout.page_dims.iter().map(|(_, extent, _)| extent).product();
+        if num_pages != pages.len(){
+            return Err(TensorImportError::BadMetadata {
+                reason: format!("expected {num_pages} pages, found {}", pages.len())
+            })
+        }
+
+        let mut shape = Vec::new();
+        let mut dim_order = String::new();
+        let mut pixel_sizes = Vec::new();
+        for (dim, extent, size) in layout.page_dims{
+            if extent == 1{
+                continue
+            }
+            shape.push(extent);
+            dim_order.push(dim);
+            pixel_sizes.push(size);
+        }
+        shape.extend([height as usize, width as usize]);
+        dim_order.push_str("yx");
+        pixel_sizes.extend([layout.y_size, layout.x_size]);
+        if samples_per_pixel > 1{
+            if dim_order.contains('c'){
+                return Err(TensorImportError::BadMetadata {
+                    reason: "both interleaved samples and separate channel pages are present".to_owned()
+                })
+            }
+            shape.push(samples_per_pixel);
+            dim_order.push('c');
+            pixel_sizes.push(None);
+        }
+
+        let data = stack_pages(pages, &shape)?;
+        Ok(Self{data, dim_order, pixel_sizes})
+    }
+}
+
+fn stack_pages(pages: Vec<DecodingResult>, shape: &[usize]) -> Result<NpyArray, TensorImportError>{
+    macro_rules! stack {
+        ($($variant:ident),*) => {
+            match pages.first(){
+                $(Some(DecodingResult::$variant(_)) => {
+                    let mut data = Vec::new();
+                    for page in pages{
+                        let DecodingResult::$variant(page) = page else {
+                            return Err(TensorImportError::InconsistentPages)
+                        };
+                        data.extend(page);
+                    }
+                    NpyArray::from(ArrayD::from_shape_vec(IxDyn(shape), data)?)
+                },)*
+                None => return Err(TensorImportError::NoImages),
+            }
+        };
+    }
+    Ok(stack!(U8, U16, U32, U64, I8, I16, I32, I64, F32, F64))
+}
+
+/// Pixel sizes as declared by the baseline TIFF resolution tags
+#[derive(Default)]
+struct TiffResolution{
+    x_size: Option<f64>,
+    y_size: Option<f64>,
+    /// Micrometers per resolution unit, or None if the resolution has no absolute unit
+    um_per_unit: Option<f64>,
+}
+
+impl TiffResolution{
+    fn read(decoder: &mut TiffDecoder<impl Read + Seek>) -> Result<Self, TensorImportError>{
+        fn pixel_size(value: Option<tiff::decoder::ifd::Value>) -> Option<f64>{
+            let pixels_per_unit = match value?{
+                tiff::decoder::ifd::Value::Rational(num, denom) => num as f64 / denom as f64,
+                tiff::decoder::ifd::Value::RationalBig(num, denom) => num as f64 / denom as f64,
+                _ => return None,
+            };
+            (pixels_per_unit.is_finite() && pixels_per_unit > 0.0).then(|| 1.0 / pixels_per_unit)
+        }
+        let um_per_unit = match decoder.find_tag_unsigned::<u16>(Tag::ResolutionUnit)?.unwrap_or(2){
+            2 => Some(25_400.0),
+            3 => Some(10_000.0),
+            _ => None,
+        };
+        Ok(Self{
+            x_size: pixel_size(decoder.find_tag(Tag::XResolution)?),
+            y_size: pixel_size(decoder.find_tag(Tag::YResolution)?),
+            um_per_unit,
+        })
+    }
+
+    fn in_micrometers(&self, size: Option<f64>) -> Option<PhysicalSize>{
+        Some(PhysicalSize{value: size? * self.um_per_unit?, unit: Some("µm".to_owned())})
+    }
+}
+
+/// How the pages of a TIFF file map onto the non-spatial dimensions of the tensor
+struct PageLayout{
+    /// Dimensions spanned by the pages, slowest varying first
+    page_dims: Vec<(char, usize, Option<PhysicalSize>)>,
+    x_size: Option<PhysicalSize>,
+    y_size: Option<PhysicalSize>,
+}
+
+impl PageLayout{
+    /// Pages without any metadata are assumed to be z-slices
+    fn plain(num_pages: usize, resolution: TiffResolution) -> Self{
+        Self{
+            page_dims: vec![('z', num_pages, None)],
+            x_size: resolution.in_micrometers(resolution.x_size),
+            y_size: resolution.in_micrometers(resolution.y_size),
+        }
+    }
+
+    /// ImageJ hyperstacks store pages in czt order, with channels varying fastest
+    fn from_imagej(description: &str, resolution: TiffResolution) -> Self{
+        let value_of = |key: &str| description.lines()
+            .find_map(|line| line.strip_prefix(key)?.strip_prefix('='))
+            .map(str::trim);
+        let extent_of = |key: &str| value_of(key).and_then(|v| v.parse::<usize>().ok()).unwrap_or(1);
+        let (channels, slices, frames) = (extent_of("channels"), extent_of("slices"), extent_of("frames"));
+        let (slices, frames) = match (value_of("images").and_then(|v| v.parse::<usize>().ok()), channels * slices * frames){
+            // plain stacks only declare `images`
+            (Some(images), 1) => (images, 1),
+            _ => (slices, frames),
+        };
+        let unit = value_of("unit").map(|unit| match unit{
+            "micron" | "\\u00B5m" => "µm".to_owned(),
+            unit => unit.to_owned(),
+        });
+        let sized = |size: Option<f64>| -> Option<PhysicalSize>{
+            match &unit{
+                Some(unit) => Some(PhysicalSize{value: size?, unit: Some(unit.clone())}),
+                None => resolution.in_micrometers(size),
+            }
+        };
+        let z_size = value_of("spacing").and_then(|v| v.parse::<f64>().ok()).and_then(|v| sized(Some(v)));
+        let t_size = value_of("finterval").and_then(|v| v.parse::<f64>().ok())
+            .map(|value| PhysicalSize{value, unit: Some(value_of("tunit").unwrap_or("sec").to_owned())});
+        Self{
+            page_dims: vec![('t', frames, t_size), ('z', slices, z_size), ('c', channels, None)],
+            x_size: sized(resolution.x_size),
+            y_size: sized(resolution.y_size),
+        }
+    }
+
+    /// OME-TIFF declares its layout in the `Pixels` element of the OME-XML in the first page's description
+    fn from_ome(description: &str, resolution: TiffResolution) -> Result<Self, TensorImportError>{
+        let bad_metadata = |reason: &str| TensorImportError::BadMetadata{reason: reason.to_owned()};
+        let pixels_start = description.find("<Pixels ").ok_or_else(|| bad_metadata("missing Pixels element"))?;
+        let pixels = &description[pixels_start..];
+        let pixels = &pixels[..pixels.find('>').unwrap_or(pixels.len())];
+        let attribute = |name: &str| -> Option<&str>{
+            let needle = format!(" {name}=\"");
+            let start = pixels.find(&needle)? + needle.len();
+            let len = pixels[start..].find('"')?;
+            Some(&pixels[start..start + len])
+        };
+        let dimension_order = attribute("DimensionOrder").unwrap_or("XYCZT");
+        let samples_per_pixel = description.find("SamplesPerPixel=\"")
+            .and_then(|idx| description[idx + "SamplesPerPixel=\"".len()..].split('"').next())
+            .and_then(|v| v.parse::<usize>().ok())
+            .unwrap_or(1);
+        let physical_size = |dim: char| -> Option<PhysicalSize>{
+            let value = attribute(&format!("PhysicalSize{}", dim.to_ascii_uppercase()))?.parse::<f64>().ok()?;
+            let unit = attribute(&format!("PhysicalSize{}Unit", dim.to_ascii_uppercase())).unwrap_or("µm");
+            Some(PhysicalSize{value, unit: Some(unit.to_owned())})
+        };
+        let mut page_dims = Vec::new();
+        for dim in dimension_order.chars().rev().map(|c| c.to_ascii_lowercase()){
+            if dim == 'x' || dim == 'y'{
+                continue
+            }
+            let extent = attribute(&format!("Size{}", dim.to_ascii_uppercase()))
+                .and_then(|v| v.parse::<usize>().ok())
+                .ok_or_else(|| bad_metadata(&format!("missing or bad Size{}", dim.to_ascii_uppercase())))?;
+            let (extent, size) = match dim{
+                // interleaved channels are not stored in separate pages
+                'c' => (extent / samples_per_pixel.max(1), None),
+                't' => (extent, attribute("TimeIncrement").and_then(|v| v.parse::<f64>().ok()).map(|value| {
+                    PhysicalSize{value, unit: Some(attribute("TimeIncrementUnit").unwrap_or("s").to_owned())}
+                })),
+                _ => (extent, physical_size(dim)),
+            };
+            page_dims.push((dim, extent, size));
+        }
+        Ok(Self{
+            page_dims,
+            x_size: physical_size('x').or_else(|| resolution.in_micrometers(resolution.x_size)),
+            y_size: physical_size('y').or_else(|| resolution.in_micrometers(resolution.y_size)),
+        })
+    }
+}
+
+#[test]
+fn test_importing_multipage_tiff(){
+    use std::io::Cursor;
+    use tiff::encoder::{colortype, TiffEncoder};
+
+    let description = concat!(
+        r#"<?xml version="1.0"?><OME><Image ID="Image:0"><Pixels DimensionOrder="XYZCT" Type="uint16" "#,
+        r#"SizeX="3" SizeY="2" SizeZ="4" SizeC="2" SizeT="1" PhysicalSizeX="0.5" PhysicalSizeY="0.5" "#,
+        r#"PhysicalSizeZ="2.0"><Channel SamplesPerPixel="1"/></Pixels></Image></OME>"#,
+    );
+    let mut buffer = Cursor::new(Vec::<u8>::new());
+    {
+        let mut encoder = TiffEncoder::new(&mut buffer).unwrap();
+        for page_idx in 0..8u16{
+            let data: Vec<u16> = (0..6).map(|i| page_idx * 10 + i).collect();
+            let mut image = encoder.new_image::<colortype::Gray16>(3, 2).unwrap();
+            if page_idx == 0{
+                image.encoder().write_tag(Tag::ImageDescription, description).unwrap();
+            }
+            image.write_data(&data).unwrap();
+        }
+    }
+    buffer.set_position(0);
+
+    let imported = ImportedTensor::try_load(buffer).unwrap();
+    assert_eq!(imported.dim_order, "czyx");
+    assert_eq!(imported.data.shape(), &[2, 4, 2, 3]);
+    assert_eq!(
+        imported.pixel_sizes[1],
+        Some(PhysicalSize{value: 2.0, unit: Some("µm".to_owned())})
+    );
+    let array: &ArrayD<u16> = (&imported.data).try_into().unwrap();
+    // XYZCT stores z-slices of the first channel before moving to the next channel
+    assert_eq!(array[[1, 0, 0, 1]], 41);
+    assert_eq!(array[[0, 3, 1, 2]], 35);
+}
@@ -0,0 +1,171 @@
+use std::borrow::Borrow;
+use std::fmt::Display;
+
+use bioimg_spec::rdf::model::TensorId;
+
+use crate::model_runner::{ModelRunner, ModelRunnerError};
+use crate::processing::ProcessingError;
+use crate::{ModelInterface, NpyArray};
+
+#[derive(thiserror::Error, Debug)]
+pub enum TestModelError{
+    #[error("Could not process tensors: {0}")]
+    ProcessingError(#[from] ProcessingError),
+    #[error("Could not run model: {0}")]
+    ModelRunnerError(#[from] ModelRunnerError),
+    #[error("None of the weight formats in this model can be run")]
+    NoRunnableWeights,
+}
+
+/// How far computed outputs may be from the expected test tensors.
+///
+/// A value is considered a mismatch if `|computed - expected| > absolute + relative * |expected|`
+#[derive(Clone, Copy, Debug)]
+pub struct Tolerance{
+    pub absolute: f64,
+    pub relative: f64,
+}
+
+impl Default for Tolerance{
+    fn default() -> Self {
+        Self{absolute: 1.5e-4, relative: 1e-4}
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum OutputComparison{
+    MismatchedShape{expected: Vec<usize>, computed: Vec<usize>},
+    Compared{
+        max_absolute_error: f64,
+        num_mismatched: usize,
+        num_elements: usize,
+    },
+}
+
+impl OutputComparison{
+    pub fn compare(expected: &NpyArray, computed: &NpyArray, tolerance: Tolerance) -> Self{
+        if expected.shape() != computed.shape(){
+            return Self::MismatchedShape { expected: expected.shape().to_vec(), computed: computed.shape().to_vec() }
+        }
+        // compared in f64 so that f64 and wide integer outputs aren't rounded to f32 first
+        let expected = expected.cast::<f64>();
+        let computed = computed.cast::<f64>();
+        let mut max_absolute_error: f64 = 0.0;
+        let mut num_mismatched = 0;
+        for (&expected, &computed) in expected.iter().zip(computed.iter()){
+            let error = (computed - expected).abs();
+            if error.is_nan() || error > tolerance.absolute + tolerance.relative * expected.abs(){
+                num_mismatched += 1;
+            }
+            max_absolute_error = max_absolute_error.max(error);
+        }
+        Self::Compared { max_absolute_error, num_mismatched, num_elements: expected.len() }
+    }
+
+    pub fn passed(&self) -> bool{
+        matches!(self, Self::Compared { num_mismatched: 0, .. })
+    }
+
+    pub fn mismatched_fraction(&self) -> f64{
+        match self{
+            Self::MismatchedShape { .. } => 1.0,
+            Self::Compared { num_elements: 0, .. } => 0.0,
+            Self::Compared { num_mismatched, num_elements, .. } => *num_mismatched as f64 / *num_elements as f64,
+        }
+    }
+}
+
+impl Display for OutputComparison{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self{
+            Self::MismatchedShape { expected, computed } => {
+                write!(f, "expected shape {expected:?} but computed {computed:?}")
+            },
+            Self::Compared { max_absolute_error, num_mismatched, num_elements } => {
+                write!(
+                    f, "max error {max_absolute_error:e}, {num_mismatched}/{num_elements} ({:.2}%) values mismatched",
+                    self.mismatched_fraction() * 100.0
+                )
+            }
+        }
+    }
+}
+
+/// The result of running a model on its input test tensors and comparing
+/// the results with its output test tensors
+#[derive(Clone, Debug)]
+pub struct TestModelReport{
+    pub tolerance: Tolerance,
+    pub outputs: Vec<(TensorId, OutputComparison)>,
+}
+
+impl TestModelReport{
+    pub fn passed(&self) -> bool{
+        self.outputs.iter().all(|(_, comparison)| comparison.passed())
+    }
+}
+
+impl Display for TestModelReport{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let verdict = if self.passed() { "passed" } else { "failed" };
+        writeln!(
+            f, "Test model {verdict} (absolute tolerance: {}, relative tolerance: {})",
+            self.tolerance.absolute, self.tolerance.relative,
+        )?;
+        for (tensor_id, comparison) in &self.outputs{
+            let mark = if comparison.passed() { "✓" } else { "✗" };
+            writeln!(f, "  {mark} {tensor_id}: {comparison}")?;
+        }
+        Ok(())
+    }
+}
+
+impl<DATA: Borrow<NpyArray>> ModelInterface<DATA>{
+    /// Preprocesses `inputs`, runs them through `runner` and postprocesses the results
+    pub fn predict<T: Borrow<NpyArray>>(
+        &self, runner: &mut dyn ModelRunner, inputs: &[T]
+    ) -> Result<Vec<NpyArray>, TestModelError>{
+        let preprocessed = self.preprocess_inputs(inputs)?;
+        let raw_outputs = runner.run(&preprocessed.iter().collect::<Vec<_>>())?;
+        Ok(self.postprocess_outputs(&preprocessed, &raw_outputs)?)
+    }
+
+    /// Runs the model on its input test tensors and compares the results with the output test tensors
+    pub fn test_model(&self, runner: &mut dyn ModelRunner, tolerance: Tolerance) -> Result<TestModelReport, TestModelError>{
+        let test_inputs: Vec<&NpyArray> = self.inputs().iter().map(|slot| slot.test_tensor.borrow()).collect();
+        let computed_outputs = self.predict(runner, &test_inputs)?;
+        let outputs = self.outputs().iter().zip(computed_outputs.iter())
+            .map(|(slot, computed)|{
+                let comparison = OutputComparison::compare(slot.test_tensor.borrow(), computed, tolerance);
+                (slot.tensor_meta.id.clone(), comparison)
+            })
+            .collect();
+        Ok(TestModelReport{tolerance, outputs})
+    }
+}
+
+#[test]
+fn test_output_comparison(){
+    let array = |values: Vec<f64>| NpyArray::ArrayF64(ndarray::ArrayD::from_shape_vec(ndarray::IxDyn(&[2, 2]), values).unwrap());
+    let tolerance = Tolerance{absolute: 0.1, relative: 0.01};
+    let expected = array(vec![1.0, -2.0, 100.0, 0.0]);
+
+    let comparison = OutputComparison::compare(&expected, &array(vec![1.05, -2.0, 100.9, 0.0]), tolerance);
+    assert!(comparison.passed(), "{comparison}");
+
+    let comparison = OutputComparison::compare(&expected, &array(vec![1.2, -2.0, 101.2, f64::NAN]), tolerance);
+    assert!(matches!(comparison, OutputComparison::Compared { num_mismatched: 3, num_elements: 4, .. }));
+    assert_eq!(comparison.mismatched_fraction(), 0.75);
+
+    let flat = NpyArray::ArrayF64(ndarray::ArrayD::zeros(ndarray::IxDyn(&[4])));
+    let comparison = OutputComparison::compare(&expected, &flat, tolerance);
+    assert!(!comparison.passed());
+    assert!(matches!(&comparison, OutputComparison::MismatchedShape { expected, computed } if expected == &[2, 2] && computed == &[4]));
+    assert_eq!(comparison.mismatched_fraction(), 1.0);
+
+    // these differ by one, which f32 can't represent at this magnitude
+    let expected = NpyArray::ArrayI64(ndarray::arr1(&[16_777_217i64]).into_dyn());
+    let computed = NpyArray::ArrayI64(ndarray::arr1(&[16_777_216i64]).into_dyn());
+    let exact = Tolerance{absolute: 0.0, relative: 0.0};
+    assert!(!OutputComparison::compare(&expected, &computed, exact).passed());
+}
@@ -0,0 +1,337 @@
+use std::borrow::Borrow;
+use std::ops::Range;
+
+use bioimg_spec::rdf::model::{
+    AnyAxisSize, AxisId, AxisType, InputAxis, OutputAxis, ParameterizedAxisSize, QualifiedAxisId, ResolvedAxisSize, TensorId
+};
+
+use crate::axis_size_resolver::{AxisSizeResolutionError, ReferenceChain, ResolvedAxisSizeExt, SlotResolver};
+use crate::{ModelInterface, NpyArray};
+
+#[derive(thiserror::Error, Debug)]
+pub enum TilingError{
+    #[error("No input tensor with id '{0}'")]
+    NoSuchInput(TensorId),
+    #[error("Image has {num_dims} dimensions but tensor '{tensor_id}' has {num_axes} axes")]
+    MismatchedNumDimensions{tensor_id: TensorId, num_dims: usize, num_axes: usize},
+    #[error("Axis '{qualified_axis_id}' can't have extent {extent}")]
+    IncompatibleExtent{qualified_axis_id: QualifiedAxisId, extent: usize},
+    #[error("Tiles of extent {tile_extent} along '{qualified_axis_id}' leave no room inside a halo of {halo}")]
+    TileTooSmallForHalo{qualified_axis_id: QualifiedAxisId, tile_extent: usize, halo: usize},
+    #[error("Output axis '{0}' does not follow the tiled input, so its tiles can't be stitched")]
+    UntileableOutputAxis(QualifiedAxisId),
+    #[error("Tiling input axes whose size references another axis ('{0}') is not supported")]
+    ReferencedInputAxis(QualifiedAxisId),
+    #[error(transparent)]
+    AxisSizeResolutionError(#[from] AxisSizeResolutionError),
+}
+
+/// How a single dimension of the input image is cut into tiles
+#[derive(Clone, Debug)]
+pub struct AxisTiling{
+    pub axis_id: AxisId,
+    /// Extent of this dimension in the full image
+    pub image_extent: usize,
+    /// Extent of this dimension in every tile fed to the model
+    pub tile_extent: usize,
+    /// Number of pixels on each side of a tile that overlap with neighbouring tiles
+    pub halo: usize,
+}
+
+impl AxisTiling{
+    /// Distance between the starts of consecutive tiles, i.e. the extent of the valid region of each tile
+    pub fn stride(&self) -> usize{
+        self.tile_extent - 2 * self.halo
+    }
+    pub fn num_tiles(&self) -> usize{
+        self.image_extent.div_ceil(self.stride()).max(1)
+    }
+}
+
+/// Where a processed output tile goes in the stitched output tensor
+#[derive(Clone, Debug)]
+pub struct OutputTileRegion{
+    pub tensor_id: TensorId,
+    /// Shape of the output tile as produced by the model
+    pub tile_shape: Vec<usize>,
+    /// The region of the output tile to keep, after cropping halos
+    pub crop: Vec<Range<usize>>,
+    /// Where the cropped region is written to in the full output tensor
+    pub destination: Vec<Range<usize>>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Tile{
+    /// Position of this tile in the grid of tiles, one entry per input axis
+    pub grid_position: Vec<usize>,
+    /// The region of the image this tile covers. It can extend beyond the image
+    /// borders, in which case the tile must be padded by `padding`
+    pub input_region: Vec<Range<isize>>,
+    /// How many pixels must be padded (before, after) along each axis of the tile
+    pub padding: Vec<(usize, usize)>,
+    pub outputs: Vec<OutputTileRegion>,
+}
+
+#[derive(Clone, Debug)]
+pub struct TilingPlan{
+    pub input_id: TensorId,
+    pub axes: Vec<AxisTiling>,
+    /// Full shape of each stitched output tensor
+    pub output_shapes: Vec<(TensorId, Vec<usize>)>,
+    /// All tiles in stitching order (row-major over the input axes). Output crops do not overlap,
+    /// so writing them in this order produces the full output tensors
+    pub tiles: Vec<Tile>,
+}
+
+/// How an output axis follows an axis of the tiled input through a chain of size references
+struct OutputAxisMapping{
+    input_axis_index: usize,
+    chain: ReferenceChain,
+    halo: usize,
+}
+
+impl OutputAxisMapping{
+    /// Output extent corresponding to `extent` pixels of the input, ignoring reference offsets.
+    /// Truncated like the sizes of referencing axes
+    fn scaled(&self, extent: usize) -> usize{
+        (extent as f64 * self.chain.ratio()) as usize
+    }
+}
+
+fn map_output_axis(
+    resolver: &SlotResolver,
+    input_axes: &[InputAxis],
+    input_id: &TensorId,
+    output_id: &TensorId,
+    axis: &OutputAxis,
+) -> Result<Option<OutputAxisMapping>, TilingError>{
+    let chain = resolver.trace(&QualifiedAxisId{tensor_id: output_id.clone(), axis_id: axis.id()})?;
+    if !chain.is_reference() || &chain.root().tensor_id != input_id{
+        return Ok(None)
+    }
+    let input_axis_index = input_axes.iter().position(|input_axis| input_axis.id() == chain.root().axis_id)
+        .expect("The resolver only follows references to existing axes");
+    let halo = axis.halo().map(|halo| u64::from(halo) as usize).unwrap_or(0);
+    Ok(Some(OutputAxisMapping{input_axis_index, chain, halo}))
+}
+
+fn is_tileable(axis: &InputAxis) -> bool{
+    matches!(axis.axis_type(), AxisType::Space | AxisType::Time)
+}
+
+/// Picks the extent of tiles along an axis: a single tile if one fits within `max_extent`,
+/// otherwise the largest valid extent not exceeding `max_extent`
+fn pick_tile_extent(size: &ParameterizedAxisSize, needed_extent: usize, max_extent: usize) -> usize{
+    let min = usize::from(size.min);
+    let step = usize::from(size.step);
+    let single_tile_extent = min + needed_extent.saturating_sub(min).div_ceil(step) * step;
+    if single_tile_extent <= max_extent{
+        return single_tile_extent
+    }
+    min + max_extent.saturating_sub(min) / step * step
+}
+
+/// Plans how to process an image of `image_shape` with the input `input_id` of `interface` in tiles.
+///
+/// Tiles along space and time axes are at most `max_tile_shape` large (where the axis size allows it)
+/// and overlap by the largest halo of the outputs whose axes follow them. All other axes are processed
+/// whole, so `image_shape` must already be valid for them.
+pub fn plan_tiling<DATA: Borrow<NpyArray>>(
+    interface: &ModelInterface<DATA>,
+    input_id: &TensorId,
+    image_shape: &[usize],
+    max_tile_shape: &[usize],
+) -> Result<TilingPlan, TilingError>{
+    let input = interface.inputs().iter().find(|slot| &slot.tensor_meta.id == input_id)
+        .ok_or_else(|| TilingError::NoSuchInput(input_id.clone()))?;
+    let input_axes = input.tensor_meta.axes();
+    for shape in [image_shape, max_tile_shape]{
+        if shape.len() != input_axes.len(){
+            return Err(TilingError::MismatchedNumDimensions {
+                tensor_id: input_id.clone(), num_dims: shape.len(), num_axes: input_axes.len()
+            })
+        }
+    }
+    let qual_id = |axis_id: AxisId| QualifiedAxisId{tensor_id: input_id.clone(), axis_id};
+    let ensure_compatible = |size: ResolvedAxisSize, axis_id: AxisId| -> Result<usize, TilingError>{
+        let extent = image_shape[input_axes.iter().position(|ax| ax.id() == axis_id).unwrap()];
+        if !size.is_compatible_with_extent(extent){
+            return Err(TilingError::IncompatibleExtent { qualified_axis_id: qual_id(axis_id), extent })
+        }
+        Ok(extent)
+    };
+
+    let resolver = interface.axis_resolver()?;
+    let mut output_mappings: Vec<Vec<Option<OutputAxisMapping>>> = vec![];
+    for output in interface.outputs().iter(){
+        let mut mappings = vec![];
+        for axis in output.tensor_meta.axes().iter(){
+            let mapping = map_output_axis(&resolver, input_axes, input_id, &output.tensor_meta.id, axis)?;
+            let follows_tiled_axis = mapping.as_ref()
+                .map(|mapping| is_tileable(&input_axes[mapping.input_axis_index]))
+                .unwrap_or(false);
+            if !follows_tiled_axis && matches!(axis.axis_type(), AxisType::Space | AxisType::Time){
+                return Err(TilingError::UntileableOutputAxis(
+                    QualifiedAxisId{tensor_id: output.tensor_meta.id.clone(), axis_id: axis.id()}
+                ))
+            }
+            mappings.push(mapping);
+        }
+        output_mappings.push(mappings);
+    }
+
+    let mut axes: Vec<AxisTiling> = Vec::with_capacity(input_axes.len());
+    for (axis_index, axis) in input_axes.iter().enumerate(){
+        let image_extent = image_shape[axis_index];
+        let halo = output_mappings.iter().flatten().flatten()
+            .filter(|mapping| mapping.input_axis_index == axis_index)
+            .map(|mapping| (mapping.halo as f64 / mapping.chain.ratio()).ceil() as usize)
+            .max()
+            .unwrap_or(0);
+        let tile_extent = match axis.size(){
+            None => image_extent,
+            Some(AnyAxisSize::Reference(_)) => return Err(TilingError::ReferencedInputAxis(qual_id(axis.id()))),
+            Some(AnyAxisSize::Fixed(fixed)) if is_tileable(axis) => usize::from(fixed),
+            Some(AnyAxisSize::Parameterized(size)) if is_tileable(axis) => {
+                pick_tile_extent(&size, image_extent + 2 * halo, max_tile_shape[axis_index])
+            },
+            Some(AnyAxisSize::Fixed(fixed)) => ensure_compatible(ResolvedAxisSize::Fixed(fixed), axis.id())?,
+            Some(AnyAxisSize::Parameterized(size)) => ensure_compatible(ResolvedAxisSize::Parameterized(size), axis.id())?,
+        };
+        if tile_extent <= 2 * halo{
+            return Err(TilingError::TileTooSmallForHalo { qualified_axis_id: qual_id(axis.id()), tile_extent, halo })
+        }
+        axes.push(AxisTiling{axis_id: axis.id(), image_extent, tile_extent, halo});
+    }
+
+    let batch_extent = input_axes.iter().position(|axis| axis.axis_type() == AxisType::Batch)
+        .map(|axis_index| image_shape[axis_index])
+        .unwrap_or(1);
+    let mut output_shapes: Vec<(TensorId, Vec<usize>)> = Vec::with_capacity(output_mappings.len());
+    for (output, mappings) in interface.outputs().iter().zip(&output_mappings){
+        let mut shape = Vec::with_capacity(mappings.len());
+        for (mapping, axis) in mappings.iter().zip(output.tensor_meta.axes().iter()){
+            shape.push(match (mapping, axis.size()){
+                (Some(mapping), _) => mapping.scaled(axes[mapping.input_axis_index].image_extent),
+                (None, None) => batch_extent,
+                (None, Some(AnyAxisSize::Fixed(fixed))) => usize::from(fixed),
+                (None, Some(_)) => return Err(TilingError::UntileableOutputAxis(
+                    QualifiedAxisId{tensor_id: output.tensor_meta.id.clone(), axis_id: axis.id()}
+                )),
+            });
+        }
+        output_shapes.push((output.tensor_meta.id.clone(), shape));
+    }
+
+    let mut tiles = vec![];
+    let mut grid_position = vec![0usize; axes.len()];
+    'grid: loop{
+        let mut input_region = Vec::with_capacity(axes.len());
+        let mut padding = Vec::with_capacity(axes.len());
+        for (axis, position) in axes.iter().zip(&grid_position){
+            let start = (position * axis.stride()) as isize - axis.halo as isize;
+            let end = start + axis.tile_extent as isize;
+            input_region.push(start..end);
+            padding.push(((-start).max(0) as usize, (end - axis.image_extent as isize).max(0) as usize));
+        }
+        let outputs = interface.outputs().iter().zip(&output_mappings).zip(&output_shapes)
+            .map(|((output, mappings), (_, output_shape))|{
+                let mut tile_shape = Vec::with_capacity(mappings.len());
+                let mut crop = Vec::with_capacity(mappings.len());
+                let mut destination = Vec::with_capacity(mappings.len());
+                for (mapping, output_extent) in mappings.iter().zip(output_shape){
+                    let Some(mapping) = mapping else {
+                        tile_shape.push(*output_extent);
+                        crop.push(0..*output_extent);
+                        destination.push(0..*output_extent);
+                        continue
+                    };
+                    let axis = &axes[mapping.input_axis_index];
+                    let position = grid_position[mapping.input_axis_index];
+                    let valid_start = position * axis.stride();
+                    let valid_end = (valid_start + axis.stride()).min(axis.image_extent);
+                    // pixels added by reference offsets come after the scaled tile, and are cropped away
+                    tile_shape.push(mapping.chain.extent_from_root(axis.tile_extent));
+                    let crop_start = mapping.scaled(axis.halo);
+                    crop.push(crop_start..crop_start + mapping.scaled(valid_end) - mapping.scaled(valid_start));
+                    destination.push(mapping.scaled(valid_start)..mapping.scaled(valid_end));
+                }
+                OutputTileRegion{tensor_id: output.tensor_meta.id.clone(), tile_shape, crop, destination}
+            })
+            .collect();
+        tiles.push(Tile{grid_position: grid_position.clone(), input_region, padding, outputs});
+
+        for axis_index in (0..axes.len()).rev(){
+            grid_position[axis_index] += 1;
+            if grid_position[axis_index] < axes[axis_index].num_tiles(){
+                continue 'grid
+            }
+            grid_position[axis_index] = 0;
+        }
+        break
+    }
+
+    Ok(TilingPlan{input_id: input_id.clone(), axes, output_shapes, tiles})
+}
+
+#[test]
+fn test_pick_tile_extent(){
+    let size = ParameterizedAxisSize{min: 16.try_into().unwrap(), step: 8.try_into().unwrap()};
+    // whole image fits in a single tile
+    assert_eq!(pick_tile_extent(&size, 30, 64), 32);
+    // largest valid extent under the limit
+    assert_eq!(pick_tile_extent(&size, 1000, 63), 56);
+    // limit below min still produces a valid extent
+    assert_eq!(pick_tile_extent(&size, 1000, 4), 16);
+
+    let axis = AxisTiling{axis_id: "x".to_owned().try_into().unwrap(), image_extent: 100, tile_extent: 56, halo: 8};
+    assert_eq!(axis.stride(), 40);
+    assert_eq!(axis.num_tiles(), 3);
+}
+
+#[test]
+fn test_plan_tiling_with_halo_scale_and_offset(){
+    use serde_json::json;
+
+    let interface = crate::model_interface::interface_from_json(
+        &[(
+            json!({"id": "raw", "axes": [
+                {"type": "batch"},
+                {"type": "space", "id": "y", "size": {"min": 16, "step": 8}},
+                {"type": "space", "id": "x", "size": {"min": 16, "step": 8}},
+            ]}),
+            &[1, 16, 16],
+        )],
+        &[(
+            // twice the resolution along y, with a halo of 8 output pixels; 2 extra pixels along x
+            json!({"id": "mask", "axes": [
+                {"type": "batch"},
+                {"type": "space", "id": "y", "size": {"tensor_id": "raw", "axis_id": "y"}, "scale": 0.5, "halo": 8},
+                {"type": "space", "id": "x", "size": {"tensor_id": "raw", "axis_id": "x", "offset": 2}},
+            ]}),
+            &[1, 32, 18],
+        )],
+    ).unwrap();
+    let input_id: TensorId = serde_json::from_value(json!("raw")).unwrap();
+    let plan = plan_tiling(&interface, &input_id, &[1, 40, 20], &[1, 32, 32]).unwrap();
+
+    // the output halo of 8 is 4 input pixels, so y is cut into tiles of 32 that advance by 24
+    let tiling: Vec<(usize, usize)> = plan.axes.iter().map(|axis| (axis.tile_extent, axis.halo)).collect();
+    assert_eq!(tiling, [(1, 0), (32, 4), (24, 0)]);
+    assert_eq!(plan.output_shapes[0].1, [1, 80, 20]);
+    assert_eq!(plan.tiles.len(), 2);
+
+    let first = &plan.tiles[0];
+    assert_eq!(first.input_region, [0..1, -4..28, 0..24]);
+    assert_eq!(first.padding, [(0, 0), (4, 0), (0, 4)]);
+    assert_eq!(first.outputs[0].tile_shape, [1, 64, 26]);
+    assert_eq!(first.outputs[0].crop, [0..1, 8..56, 0..20]);
+    assert_eq!(first.outputs[0].destination, [0..1, 0..48, 0..20]);
+
+    let second = &plan.tiles[1];
+    assert_eq!(second.grid_position, [0, 1, 0]);
+    assert_eq!(second.input_region, [0..1, 20..52, 0..24]);
+    assert_eq!(second.padding, [(0, 0), (0, 12), (0, 4)]);
+    assert_eq!(second.outputs[0].crop, [0..1, 8..40, 0..20]);
+    assert_eq!(second.outputs[0].destination, [0..1, 48..80, 0..20]);
+}
@@ -0,0 +1,452 @@
+use std::collections::HashMap;
+use std::io::Read;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use bioimg_spec::rdf::HttpUrl;
+
+#[derive(thiserror::Error, Debug)]
+pub enum FetchError{
+    #[error("Fetching remote files is not supported here (tried to fetch {url})")]
+    Unsupported{url: HttpUrl},
+    #[error("Could not fetch {url}: {reason}")]
+    Failed{url: HttpUrl, reason: String},
+    #[error("Unexpected status ({status}) when requesting {url}")]
+    UnexpectedStatus{url: HttpUrl, status: u16},
+    #[error("{url} is larger than the maximum allowed size of {max_size} bytes")]
+    TooLarge{url: HttpUrl, max_size: u64},
+    #[error("Download of {url} was cancelled")]
+    Cancelled{url: HttpUrl},
+}
+
+impl FetchError{
+    /// Recovers a `FetchError` that had to be smuggled through a `std::io::Error`, e.g. by a `BoundedReader`
+    pub fn try_from_io(err: std::io::Error) -> Result<Self, std::io::Error>{
+        if !err.get_ref().is_some_and(|inner| inner.is::<FetchError>()){
+            return Err(err)
+        }
+        Ok(*err.into_inner().unwrap().downcast::<FetchError>().unwrap())
+    }
+
+    pub fn from_io(url: &HttpUrl, err: std::io::Error) -> Self{
+        Self::try_from_io(err).unwrap_or_else(|err| Self::Failed { url: url.clone(), reason: err.to_string() })
+    }
+}
+
+/// A flag that can be shared with a running download to ask it to stop
+#[derive(Clone, Default, Debug)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken{
+    pub fn new() -> Self{
+        Self::default()
+    }
+    pub fn cancel(&self){
+        self.0.store(true, Ordering::Relaxed)
+    }
+    pub fn is_cancelled(&self) -> bool{
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Limits applied by fetchers to every download
+#[derive(Clone, Debug)]
+pub struct FetchOptions{
+    /// Downloads that would go beyond this many bytes fail with `FetchError::TooLarge`
+    pub max_size: Option<u64>,
+    /// Maximum time to wait for a connection or for the next chunk of data
+    pub timeout: Option<Duration>,
+    /// How many more times to try a download that failed due to network issues or server errors
+    pub retries: u32,
+    /// How long to wait before the first retry. The wait doubles with every further retry
+    pub retry_delay: Duration,
+    pub cancellation: CancellationToken,
+}
+
+impl Default for FetchOptions{
+    fn default() -> Self {
+        Self{
+            max_size: Some(Self::DEFAULT_MAX_SIZE),
+            timeout: Some(Duration::from_secs(30)),
+            retries: 2,
+            retry_delay: Duration::from_millis(500),
+            cancellation: CancellationToken::new(),
+        }
+    }
+}
+
+impl FetchOptions{
+    /// Large enough for the weights of most models, while keeping a bad URL from filling up memory or disk
+    pub const DEFAULT_MAX_SIZE: u64 = 4 * 1024 * 1024 * 1024;
+
+    fn check_cancelled(&self, url: &HttpUrl) -> Result<(), FetchError>{
+        if self.cancellation.is_cancelled(){
+            return Err(FetchError::Cancelled { url: url.clone() })
+        }
+        Ok(())
+    }
+
+    fn check_size(&self, url: &HttpUrl, size: u64) -> Result<(), FetchError>{
+        match self.max_size{
+            Some(max_size) if size > max_size => Err(FetchError::TooLarge { url: url.clone(), max_size }),
+            _ => Ok(()),
+        }
+    }
+
+    /// How long to wait before retrying after `attempt` (starting at 0) failed
+    pub fn backoff(&self, attempt: u32) -> Duration{
+        self.retry_delay.saturating_mul(2u32.saturating_pow(attempt))
+    }
+
+    /// Wraps `reader` so that reading from it enforces `max_size` and `cancellation`
+    pub fn bound<R: Read>(&self, url: &HttpUrl, reader: R) -> BoundedReader<R>{
+        BoundedReader{inner: reader, url: url.clone(), options: self.clone(), bytes_read: 0}
+    }
+}
+
+/// A reader over a download that fails as soon as it grows too large or gets cancelled.
+/// The errors it produces wrap a `FetchError` (see `FetchError::from_io`)
+pub struct BoundedReader<R>{
+    inner: R,
+    url: HttpUrl,
+    options: FetchOptions,
+    bytes_read: u64,
+}
+
+impl<R: Read> Read for BoundedReader<R>{
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.options.check_cancelled(&self.url).map_err(std::io::Error::other)?;
+        let num_read = self.inner.read(buf)?;
+        self.bytes_read += num_read as u64;
+        self.options.check_size(&self.url, self.bytes_read).map_err(std::io::Error::other)?;
+        Ok(num_read)
+    }
+}
+
+/// Retrieves the contents of files that are referenced by URL instead of by a path inside the package.
+///
+/// When loading a model, only files that must be parsed right away (documentation, covers, icons,
+/// conda environments, test and sample tensors) are fetched; weights, attachments and other opaque files are kept as
+/// `FileSource::HttpUrl` and only downloaded when read or packed.
+/// Closures with the signature of `fetch` can be used as fetchers.
+pub trait UrlFetcher{
+    /// Opens a stream over the contents of `url`
+    fn open(&self, url: &HttpUrl) -> Result<Box<dyn Read + Send>, FetchError>;
+
+    fn fetch(&self, url: &HttpUrl) -> Result<Vec<u8>, FetchError>{
+        let mut bytes = Vec::<u8>::new();
+        self.open(url)?.read_to_end(&mut bytes).map_err(|e| FetchError::from_io(url, e))?;
+        Ok(bytes)
+    }
+}
+
+impl<F> UrlFetcher for F
+where
+    F: Fn(&HttpUrl) -> Result<Vec<u8>, FetchError>
+{
+    fn open(&self, url: &HttpUrl) -> Result<Box<dyn Read + Send>, FetchError> {
+        Ok(Box::new(std::io::Cursor::new(self(url)?)))
+    }
+    fn fetch(&self, url: &HttpUrl) -> Result<Vec<u8>, FetchError> {
+        self(url)
+    }
+}
+
+/// A fetcher that refuses every URL
+#[derive(Default, Clone, Copy, Debug)]
+pub struct NoFetcher;
+
+impl UrlFetcher for NoFetcher{
+    fn open(&self, url: &HttpUrl) -> Result<Box<dyn Read + Send>, FetchError> {
+        Err(FetchError::Unsupported { url: url.clone() })
+    }
+}
+
+/// Serves files from memory, meant to stand in for a real server in tests
+#[derive(Default, Clone, Debug)]
+pub struct InMemoryFetcher{
+    pub files: HashMap<String, Arc<[u8]>>,
+    pub options: FetchOptions,
+}
+
+impl InMemoryFetcher{
+    pub fn with_file(mut self, url: &str, contents: impl Into<Arc<[u8]>>) -> Self{
+        self.files.insert(url.to_owned(), contents.into());
+        self
+    }
+}
+
+impl UrlFetcher for InMemoryFetcher{
+    fn open(&self, url: &HttpUrl) -> Result<Box<dyn Read + Send>, FetchError> {
+        self.options.check_cancelled(url)?;
+        let Some(contents) = self.files.get(url.as_str()) else {
+            return Err(FetchError::UnexpectedStatus { url: url.clone(), status: 404 })
+        };
+        Ok(Box::new(self.options.bound(url, std::io::Cursor::new(contents.clone()))))
+    }
+}
+
+/// Downloads files with blocking HTTP GET requests
+#[cfg(not(target_arch="wasm32"))]
+#[derive(Clone)]
+pub struct UreqFetcher{
+    agent: ureq::Agent,
+    options: FetchOptions,
+}
+
+#[cfg(not(target_arch="wasm32"))]
+impl Default for UreqFetcher{
+    fn default() -> Self {
+        Self::new(FetchOptions::default())
+    }
+}
+
+#[cfg(not(target_arch="wasm32"))]
+impl UreqFetcher{
+    pub fn new(options: FetchOptions) -> Self{
+        let mut builder = ureq::AgentBuilder::new();
+        if let Some(timeout) = options.timeout{
+            builder = builder.timeout_connect(timeout).timeout_read(timeout);
+        }
+        Self{agent: builder.build(), options}
+    }
+}
+
+#[cfg(not(target_arch="wasm32"))]
+impl UrlFetcher for UreqFetcher{
+    fn open(&self, url: &HttpUrl) -> Result<Box<dyn Read + Send>, FetchError> {
+        let mut attempt = 0;
+        let response = loop {
+            self.options.check_cancelled(url)?;
+            let retry_error = match self.agent.get(url.as_str()).call(){
+                Ok(response) => break response,
+                Err(ureq::Error::Status(status, _)) if status < 500 => {
+                    return Err(FetchError::UnexpectedStatus { url: url.clone(), status })
+                },
+                Err(ureq::Error::Status(status, _)) => FetchError::UnexpectedStatus { url: url.clone(), status },
+                Err(err) => FetchError::Failed { url: url.clone(), reason: err.to_string() },
+            };
+            if attempt >= self.options.retries{
+                return Err(retry_error)
+            }
+            std::thread::sleep(self.options.backoff(attempt));
+            attempt += 1;
+        };
+        if let Some(size) = response.header("Content-Length").and_then(|raw| raw.parse::<u64>().ok()){
+            self.options.check_size(url, size)?;
+        }
+        Ok(Box::new(self.options.bound(url, response.into_reader())))
+    }
+}
+
+/// Downloads files with the browser's `fetch` API. The browser can't block on a download, so files
+/// have to be downloaded with [BrowserFetcher::prefetch] before a model is loaded or packed;
+/// `open` only serves files that were prefetched and fails with `FetchError::Unsupported` otherwise
+#[cfg(target_arch="wasm32")]
+#[derive(Clone, Default)]
+pub struct BrowserFetcher{
+    pub options: FetchOptions,
+    downloaded: Arc<std::sync::Mutex<HashMap<String, Arc<[u8]>>>>,
+}
+
+#[cfg(target_arch="wasm32")]
+impl BrowserFetcher{
+    pub fn new(options: FetchOptions) -> Self{
+        Self{options, downloaded: Default::default()}
+    }
+
+    /// Downloads every url in `urls` that wasn't downloaded yet, so that `open` can serve it
+    pub async fn prefetch<'u>(&self, urls: impl IntoIterator<Item=&'u HttpUrl>) -> Result<(), FetchError>{
+        for url in urls{
+            if self.downloaded.lock().unwrap().contains_key(url.as_str()){
+                continue
+            }
+            let bytes = self.download(url).await?;
+            self.downloaded.lock().unwrap().insert(url.as_str().to_owned(), bytes.into());
+        }
+        Ok(())
+    }
+
+    async fn download(&self, url: &HttpUrl) -> Result<Vec<u8>, FetchError>{
+        let mut attempt = 0;
+        loop {
+            self.options.check_cancelled(url)?;
+            let retry_error = match self.try_download(url).await{
+                Ok(bytes) => return Ok(bytes),
+                Err(err @ FetchError::UnexpectedStatus { status, .. }) if status < 500 => return Err(err),
+                Err(err @ (FetchError::UnexpectedStatus{..} | FetchError::Failed{..})) => err,
+                Err(err) => return Err(err),
+            };
+            if attempt >= self.options.retries{
+                return Err(retry_error)
+            }
+            wasm_bindgen_futures::JsFuture::from(sleep_promise(self.options.backoff(attempt))).await
+                .map_err(|err| FetchError::Failed { url: url.clone(), reason: format!("{err:?}") })?;
+            attempt += 1;
+        }
+    }
+
+    /// Waits for `promise`, failing if it takes longer than `FetchOptions::timeout`
+    async fn await_with_timeout(&self, url: &HttpUrl, promise: js_sys::Promise) -> Result<wasm_bindgen::JsValue, FetchError>{
+        let failed = |err: wasm_bindgen::JsValue| FetchError::Failed { url: url.clone(), reason: format!("{err:?}") };
+        let Some(timeout) = self.options.timeout else {
+            return wasm_bindgen_futures::JsFuture::from(promise).await.map_err(failed)
+        };
+        // the timer resolves to `undefined`, which neither a response nor a chunk ever is
+        let raced = js_sys::Promise::race(&js_sys::Array::of2(&promise, &sleep_promise(timeout)));
+        let value = wasm_bindgen_futures::JsFuture::from(raced).await.map_err(failed)?;
+        if value.is_undefined(){
+            return Err(FetchError::Failed { url: url.clone(), reason: format!("timed out after {timeout:?}") })
+        }
+        Ok(value)
+    }
+
+    async fn try_download(&self, url: &HttpUrl) -> Result<Vec<u8>, FetchError>{
+        use wasm_bindgen::JsCast;
+
+        let failed = |err: wasm_bindgen::JsValue| FetchError::Failed { url: url.clone(), reason: format!("{err:?}") };
+        let window = web_sys::window().ok_or_else(|| FetchError::Unsupported { url: url.clone() })?;
+        let init = web_sys::RequestInit::new();
+        init.set_method("GET");
+        let request = web_sys::Request::new_with_str_and_init(url.as_str(), &init).map_err(failed)?;
+        let response: web_sys::Response = self.await_with_timeout(url, window.fetch_with_request(&request)).await?
+            .dyn_into().map_err(failed)?;
+        if !response.ok(){
+            return Err(FetchError::UnexpectedStatus { url: url.clone(), status: response.status() })
+        }
+        let content_length = response.headers().get("Content-Length").map_err(failed)?
+            .and_then(|raw| raw.parse::<u64>().ok());
+        if let Some(size) = content_length{
+            self.options.check_size(url, size)?;
+        }
+        let Some(body) = response.body() else {
+            return Ok(vec![])
+        };
+        let reader: web_sys::ReadableStreamDefaultReader = body.get_reader().dyn_into().map_err(|obj| failed(obj.into()))?;
+        let mut bytes = Vec::<u8>::new();
+        loop {
+            if let Err(err) = self.options.check_cancelled(url){
+                _ = reader.cancel();
+                return Err(err)
+            }
+            let chunk = self.await_with_timeout(url, reader.read()).await?;
+            let done = js_sys::Reflect::get(&chunk, &"done".into()).map_err(failed)?;
+            if done.as_bool().unwrap_or(false){
+                break
+            }
+            let value: js_sys::Uint8Array = js_sys::Reflect::get(&chunk, &"value".into()).map_err(failed)?
+                .dyn_into().map_err(failed)?;
+            bytes.extend(value.to_vec());
+            if let Err(err) = self.options.check_size(url, bytes.len() as u64){
+                _ = reader.cancel();
+                return Err(err)
+            }
+        }
+        Ok(bytes)
+    }
+}
+
+/// A promise that resolves to `undefined` after `duration`
+#[cfg(target_arch="wasm32")]
+fn sleep_promise(duration: Duration) -> js_sys::Promise{
+    use wasm_bindgen::JsCast;
+    js_sys::Promise::new(&mut |resolve, _reject| {
+        // looked up on the global object so that this also works in web workers
+        let set_timeout: js_sys::Function = js_sys::Reflect::get(&js_sys::global(), &"setTimeout".into())
+            .expect("setTimeout should be available")
+            .unchecked_into();
+        _ = set_timeout.call2(&wasm_bindgen::JsValue::NULL, &resolve, &(duration.as_millis() as f64).into());
+    })
+}
+
+#[cfg(target_arch="wasm32")]
+impl UrlFetcher for BrowserFetcher{
+    fn open(&self, url: &HttpUrl) -> Result<Box<dyn Read + Send>, FetchError> {
+        self.options.check_cancelled(url)?;
+        let Some(contents) = self.downloaded.lock().unwrap().get(url.as_str()).cloned() else {
+            return Err(FetchError::Unsupported { url: url.clone() })
+        };
+        Ok(Box::new(self.options.bound(url, std::io::Cursor::new(contents))))
+    }
+}
+
+/// The fetcher used when none is specified
+#[cfg(not(target_arch="wasm32"))]
+pub type DefaultUrlFetcher = UreqFetcher;
+#[cfg(target_arch="wasm32")]
+pub type DefaultUrlFetcher = BrowserFetcher;
+
+#[test]
+fn test_retry_backoff(){
+    let options = FetchOptions{retry_delay: Duration::from_millis(100), ..Default::default()};
+    assert_eq!(options.backoff(0), Duration::from_millis(100));
+    assert_eq!(options.backoff(3), Duration::from_millis(800));
+    // the multiplier saturates instead of overflowing
+    assert_eq!(options.backoff(40), options.backoff(32));
+}
+
+#[cfg(not(target_arch="wasm32"))]
+#[test]
+fn test_packing_url_sources_from_local_server(){
+    use std::io::{BufRead, BufReader, Write};
+    use crate::FileSource;
+    use crate::zip_archive_ext::SharedZipArchive;
+    use crate::zip_writer_ext::ModelZipWriter;
+
+    let payload: Vec<u8> = (0..5000u32).map(|i| (i % 251) as u8).collect();
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let served_payload = payload.clone();
+    std::thread::spawn(move || {
+        for stream in listener.incoming(){
+            let mut stream = stream.unwrap();
+            let mut request_line = String::new();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            reader.read_line(&mut request_line).unwrap();
+            loop {
+                let mut header = String::new();
+                if reader.read_line(&mut header).unwrap() <= 2 {
+                    break
+                }
+            }
+            if request_line.starts_with("GET /weights.bin "){
+                write!(stream, "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n", served_payload.len()).unwrap();
+                stream.write_all(&served_payload).unwrap();
+            } else {
+                write!(stream, "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n").unwrap();
+            }
+        }
+    });
+
+    let url = |path: &str| -> HttpUrl { format!("http://127.0.0.1:{port}/{path}").try_into().unwrap() };
+    let pack = |source: FileSource, options: FetchOptions| {
+        let mut sink = std::io::Cursor::new(Vec::<u8>::new());
+        let packing_options = crate::zip_writer_ext::PackingOptions{
+            fetcher: Box::new(UreqFetcher::new(options)),
+            ..Default::default()
+        };
+        let mut writer = ModelZipWriter::with_options(&mut sink, packing_options);
+        let descr = source.dump_as_file_description(&mut writer, "weights")?;
+        writer.finish()?;
+        let archive = SharedZipArchive::from_raw_data(sink.into_inner(), "packed".to_owned());
+        let bioimg_spec::rdf::FileReference::Path(path) = descr.source else {
+            panic!("Packed files should be referenced by path");
+        };
+        Ok::<_, crate::zoo_model::ModelPackingError>(archive.read_full_entry(&String::from(&path)).unwrap())
+    };
+
+    let weights = FileSource::HttpUrl(Arc::new(url("weights.bin")));
+    assert_eq!(FetchOptions::default().max_size, Some(FetchOptions::DEFAULT_MAX_SIZE));
+    assert_eq!(pack(weights.clone(), FetchOptions::default()).unwrap(), payload);
+
+    let small = FetchOptions{max_size: Some(1000), ..Default::default()};
+    assert!(pack(weights.clone(), small).unwrap_err().to_string().contains("maximum allowed size"));
+
+    let cancelled = FetchOptions::default();
+    cancelled.cancellation.cancel();
+    assert!(pack(weights, cancelled).unwrap_err().to_string().contains("cancelled"));
+
+    let missing = FileSource::HttpUrl(Arc::new(url("missing.bin")));
+    assert!(pack(missing, FetchOptions::default()).unwrap_err().to_string().contains("404"));
+}
@@ -2,6 +2,9 @@ use std::{fmt::{Debug, Display}, io::{Read, Seek}, path::{Path, PathBuf}, sync::
 
 use bioimg_spec::rdf;
 
+use crate::model_package::{ModelPackage, PackageReadError};
+use crate::url_fetcher::{FetchError, UrlFetcher};
+
 pub trait SeekReadSend: Seek + Read + Send{}
 impl<T: Seek + Read + Send> SeekReadSend for T{}
 
@@ -111,6 +114,15 @@ impl SharedZipArchive{
         _ = f.read_to_end(&mut data)?;
         Ok(data)
     }
+    pub fn entry_sha256(&self, entry_path: &str) -> Result<rdf::Sha256, zip::result::ZipError>{
+        use sha2::Digest;
+        let mut archive_guard = self.archive.lock().unwrap();
+        let mut f = archive_guard.by_name(entry_path)?;
+        let mut hasher = sha2::Sha256::new();
+        std::io::copy(&mut f, &mut hasher)?;
+        let digest: [u8; 32] = hasher.finalize().into();
+        Ok(rdf::Sha256::from(digest))
+    }
     pub fn has_entry(&self, name: &str) -> bool{
         self.archive.lock().unwrap().by_name(name).is_ok()
     }
@@ -128,29 +140,35 @@ impl SharedZipArchive{
 #[derive(thiserror::Error, Debug)]
 pub enum RdfFileReferenceReadError{
     #[error("{0}")]
-    ZipError(#[from] zip::result::ZipError),
-    #[error("Url file reference not supported yet")]
-    UrlFileReferenceNotSupportedYet,
+    PackageReadError(#[from] PackageReadError),
+    #[error(transparent)]
+    FetchError(#[from] FetchError),
 }
 
 pub trait RdfFileReferenceExt{
+    /// Reads the referenced file from `package`, or via `fetcher` if it is a URL
     fn try_read<F, Out>(
-        &self, archive: &SharedZipArchive, reader: F
+        &self, package: &ModelPackage, fetcher: &dyn UrlFetcher, reader: F
     ) -> Result<Out, RdfFileReferenceReadError>
     where
-        F: FnOnce(&mut zip::read::ZipFile<'_, BoxDynSeekReadSend>) -> Out,
+        F: FnOnce(&mut dyn Read) -> Out,
         Out: 'static;
 }
 impl RdfFileReferenceExt for rdf::FileReference{
-    fn try_read<F, Out>(&self, archive: &SharedZipArchive, reader: F) -> Result<Out, RdfFileReferenceReadError>
+    fn try_read<F, Out>(
+        &self, package: &ModelPackage, fetcher: &dyn UrlFetcher, reader: F
+    ) -> Result<Out, RdfFileReferenceReadError>
     where
-        F: FnOnce(&mut zip::read::ZipFile<'_, BoxDynSeekReadSend>) -> Out,
+        F: FnOnce(&mut dyn Read) -> Out,
         Out: 'static,
     {
         let inner_path: String = match self{
-            rdf::FileReference::Url(_) => return Err(RdfFileReferenceReadError::UrlFileReferenceNotSupportedYet),
+            rdf::FileReference::Url(url) => {
+                let bytes = fetcher.fetch(url)?;
+                return Ok(reader(&mut std::io::Cursor::new(bytes)))
+            },
             rdf::FileReference::Path(path) => path.into(),
         };
-        Ok(archive.with_entry(&inner_path, reader)?)
+        Ok(package.with_entry(&inner_path, reader)?)
     }
 }
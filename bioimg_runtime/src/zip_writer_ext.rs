@@ -1,32 +1,401 @@
+use std::collections::HashSet;
 use std::io::{Seek, Write};
 
-use bioimg_spec::rdf::FsPath;
+use bioimg_spec::rdf::{self, FsPath};
+use sha2::Digest;
 
+use crate::url_fetcher::{CancellationToken, DefaultUrlFetcher, UrlFetcher};
 use crate::zoo_model::ModelPackingError;
 
+/// How far along the packing of a model is
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PackingProgress{
+    /// The part of the model being written, e.g. "weights"
+    pub component: String,
+    pub bytes_written: u64,
+    /// Estimated size of all files in the package, if it could be determined up front
+    pub total_bytes: Option<u64>,
+}
+
+/// Gets notified as a model is packed. Closures taking a `&PackingProgress` can be used as observers
+pub trait PackingObserver{
+    fn on_progress(&self, progress: &PackingProgress);
+}
+
+impl<F: Fn(&PackingProgress)> PackingObserver for F{
+    fn on_progress(&self, progress: &PackingProgress) {
+        self(progress)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compression{
+    Stored,
+    /// Deflate with a level from 0 (fastest) to 9 (smallest), or a default level if `None`
+    Deflated{level: Option<i64>},
+}
+
+impl Compression{
+    fn file_options(self, file_options: zip::write::SimpleFileOptions) -> zip::write::SimpleFileOptions{
+        match self{
+            Self::Stored => file_options.compression_method(zip::CompressionMethod::Stored),
+            Self::Deflated { level } => file_options
+                .compression_method(zip::CompressionMethod::Deflated)
+                .compression_level(level),
+        }
+    }
+}
+
+/// The compression used for each kind of file in a package, picked by file extension
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CompressionOptions{
+    /// rdf.yaml, documentation, environment files and other text
+    pub text: Compression,
+    /// .npy test tensors
+    pub tensors: Compression,
+    /// Weights, images and anything else, which are often already compressed
+    pub other: Compression,
+}
+
+impl Default for CompressionOptions{
+    fn default() -> Self {
+        Self{
+            text: Compression::Deflated { level: None },
+            tensors: Compression::Deflated { level: None },
+            other: Compression::Stored,
+        }
+    }
+}
+
+impl CompressionOptions{
+    pub fn stored() -> Self{
+        Self{text: Compression::Stored, tensors: Compression::Stored, other: Compression::Stored}
+    }
+
+    /// Compresses every file as much as possible, at the cost of speed
+    pub fn smallest() -> Self{
+        let best = Compression::Deflated { level: Some(9) };
+        Self{text: best, tensors: best, other: best}
+    }
+
+    pub fn for_path(&self, path: &str) -> Compression{
+        const TEXT_EXTENSIONS: [&str; 7] = ["yaml", "yml", "md", "txt", "json", "py", "csv"];
+        let extension = path.rsplit_once('.').map(|(_, ext)| ext.to_lowercase()).unwrap_or_default();
+        if TEXT_EXTENSIONS.contains(&extension.as_str()){
+            self.text
+        } else if extension == "npy"{
+            self.tensors
+        } else {
+            self.other
+        }
+    }
+}
+
+pub struct PackingOptions{
+    /// Used to download any remote files that are part of the model
+    pub fetcher: Box<dyn UrlFetcher>,
+    pub observer: Option<Box<dyn PackingObserver>>,
+    /// Makes packing stop with `ModelPackingError::Cancelled` as soon as possible
+    pub cancellation: CancellationToken,
+    pub compression: CompressionOptions,
+    /// Names files after the role they play in the model instead of random UUIDs and uses fixed
+    /// timestamps, so that packing the same model twice produces byte-identical archives
+    pub deterministic: bool,
+}
+
+impl Default for PackingOptions{
+    fn default() -> Self {
+        Self{
+            fetcher: Box::new(DefaultUrlFetcher::default()),
+            observer: None,
+            cancellation: CancellationToken::new(),
+            compression: CompressionOptions::default(),
+            deterministic: false,
+        }
+    }
+}
+
+struct ProgressTracker{
+    progress: PackingProgress,
+    last_reported: u64,
+    observer: Option<Box<dyn PackingObserver>>,
+}
+
+impl ProgressTracker{
+    /// Avoids flooding observers with a notification for every small write
+    const REPORT_INTERVAL: u64 = 1024 * 1024;
+
+    fn report(&mut self){
+        self.last_reported = self.progress.bytes_written;
+        if let Some(observer) = &self.observer{
+            observer.on_progress(&self.progress);
+        }
+    }
+
+    fn advance(&mut self, num_bytes: u64){
+        self.progress.bytes_written += num_bytes;
+        if self.progress.bytes_written - self.last_reported >= Self::REPORT_INTERVAL{
+            self.report();
+        }
+    }
+}
+
+/// Computes the sha256 of all bytes that get written through it
+struct HashingWriter<'w>{
+    inner: &'w mut dyn Write,
+    hasher: sha2::Sha256,
+    tracker: &'w mut ProgressTracker,
+    cancellation: &'w CancellationToken,
+}
+
+impl Write for HashingWriter<'_>{
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if self.cancellation.is_cancelled(){
+            return Err(std::io::Error::other("Packing was cancelled"))
+        }
+        let num_written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..num_written]);
+        self.tracker.advance(num_written as u64);
+        Ok(num_written)
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
 // Hides the ZipWriter to enforce correct usage
-pub struct ModelZipWriter<W: Write + Seek>(zip::ZipWriter<W>);
+pub struct ModelZipWriter<W: Write + Seek>{
+    zip: zip::ZipWriter<W>,
+    fetcher: Box<dyn UrlFetcher>,
+    tracker: ProgressTracker,
+    cancellation: CancellationToken,
+    compression: CompressionOptions,
+    deterministic: bool,
+    used_paths: HashSet<String>,
+}
 
 impl<W: Write + Seek> ModelZipWriter<W> {
     pub fn new(zip_sink: W) -> Self {
-        Self(zip::ZipWriter::new(zip_sink))
+        Self::with_options(zip_sink, PackingOptions::default())
+    }
+
+    /// Creates a writer that uses `fetcher` to download the remote files that get packed into it
+    pub fn with_fetcher(zip_sink: W, fetcher: impl UrlFetcher + 'static) -> Self {
+        Self::with_options(zip_sink, PackingOptions{fetcher: Box::new(fetcher), ..Default::default()})
+    }
+
+    pub fn with_options(zip_sink: W, options: PackingOptions) -> Self {
+        Self{
+            zip: zip::ZipWriter::new(zip_sink),
+            fetcher: options.fetcher,
+            tracker: ProgressTracker{
+                progress: PackingProgress{component: String::new(), bytes_written: 0, total_bytes: None},
+                last_reported: 0,
+                observer: options.observer,
+            },
+            cancellation: options.cancellation,
+            compression: options.compression,
+            deterministic: options.deterministic,
+            // the rdf is always written last, so its name must not be taken by anything else
+            used_paths: HashSet::from(["rdf.yaml".to_owned()]),
+        }
+    }
+
+    pub fn is_deterministic(&self) -> bool{
+        self.deterministic
+    }
+
+    /// Picks where to store a file that plays `role` in the model, e.g. `inputs/raw/test_tensor.npy`.
+    ///
+    /// In deterministic mode the role itself is used as the path, with a numeric suffix added to the
+    /// file name if it was already taken. Otherwise the file gets a random name ending in `random_suffix`
+    pub fn entry_path(&mut self, role: &str, random_suffix: &str) -> FsPath{
+        self.preferred_entry_path(None, role, random_suffix)
+    }
+
+    /// Like `entry_path`, but keeps `preferred_path` if it is a valid relative path, e.g. the path a file had
+    /// in the package it was imported from. If it was already taken, a numeric suffix is added to its file name
+    pub fn preferred_entry_path(&mut self, preferred_path: Option<&str>, role: &str, random_suffix: &str) -> FsPath{
+        let preferred_path = preferred_path.filter(|path| {
+            FsPath::try_from(path.to_string())
+                .is_ok_and(|path| path.components().iter().all(|comp| &**comp != "." && &**comp != ".."))
+        });
+        match (preferred_path, self.deterministic){
+            (Some(path), _) => self.claim_path(path),
+            (None, true) => self.claim_path(role),
+            (None, false) => FsPath::unique_suffixed(random_suffix),
+        }
+    }
+
+    fn claim_path(&mut self, path: &str) -> FsPath{
+        let (dir, file_name) = match path.rsplit_once('/'){
+            Some((dir, file_name)) => (Some(dir), file_name),
+            None => (None, path),
+        };
+        let (stem, extension) = match file_name.rsplit_once('.'){
+            Some((stem, extension)) if !stem.is_empty() => (stem, Some(extension)),
+            _ => (file_name, None),
+        };
+        let mut candidate = path.to_owned();
+        let mut counter = 1;
+        while self.used_paths.contains(&candidate){
+            counter += 1;
+            candidate = match dir{
+                Some(dir) => format!("{dir}/{stem}_{counter}"),
+                None => format!("{stem}_{counter}"),
+            };
+            if let Some(extension) = extension{
+                candidate = format!("{candidate}.{extension}");
+            }
+        }
+        self.used_paths.insert(candidate.clone());
+        FsPath::try_from(candidate).expect("Claimed paths should always be valid")
+    }
+
+    pub fn fetcher(&self) -> &dyn UrlFetcher{
+        self.fetcher.as_ref()
+    }
+
+    pub fn set_total_bytes(&mut self, total_bytes: Option<u64>){
+        self.tracker.progress.total_bytes = total_bytes;
     }
 
-    pub fn write_file<F, Out, E>(&mut self, path: &FsPath, f: F) -> Result<Out, ModelPackingError>
+    /// Marks the start of a new part of the model in the progress reports
+    pub fn start_component(&mut self, component: impl Into<String>){
+        self.tracker.progress.component = component.into();
+        self.tracker.report();
+    }
+
+    /// Writes a file into the archive, returning whatever `f` produces and the sha256 of the written bytes
+    pub fn write_file<F, Out, E>(&mut self, path: &FsPath, f: F) -> Result<(Out, rdf::Sha256), ModelPackingError>
     where
         //FIXME: using W as a param keeps Seek, so using dyn to remove it
         F: FnOnce(&mut dyn Write) -> Result<Out, E>,
         E: Into<ModelPackingError>,
     {
-        let file_options = zip::write::SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+        if self.cancellation.is_cancelled(){
+            return Err(ModelPackingError::Cancelled)
+        }
         let path: String = path.clone().into();
-        self.0.start_file(path, file_options)?;
-        f(&mut self.0).map_err(|e| e.into())
+        self.used_paths.insert(path.clone());
+        let mut file_options = zip::write::SimpleFileOptions::default();
+        if self.deterministic{
+            file_options = file_options.last_modified_time(zip::DateTime::default());
+        }
+        let file_options = self.compression.for_path(&path).file_options(file_options);
+        self.zip.start_file(path, file_options)?;
+        let mut writer = HashingWriter{
+            inner: &mut self.zip,
+            hasher: sha2::Sha256::new(),
+            tracker: &mut self.tracker,
+            cancellation: &self.cancellation,
+        };
+        let result = f(&mut writer).map_err(|e| e.into());
+        if self.cancellation.is_cancelled(){
+            return Err(ModelPackingError::Cancelled)
+        }
+        let digest: [u8; 32] = writer.hasher.finalize().into();
+        Ok((result?, rdf::Sha256::from(digest)))
     }
 
     //FIXME: can we enforce the calling of this function with something like must_use ?
-    pub fn finish(self) -> Result<(), ModelPackingError> {
-        self.0.finish()?;
+    pub fn finish(mut self) -> Result<(), ModelPackingError> {
+        self.zip.finish()?;
+        self.tracker.report();
         Ok(())
     }
 }
+
+#[test]
+fn test_written_files_are_hashed(){
+    use crate::zip_archive_ext::SharedZipArchive;
+
+    let path = FsPath::unique_suffixed(".txt");
+    let mut sink = std::io::Cursor::new(Vec::<u8>::new());
+    let mut writer = ModelZipWriter::new(&mut sink);
+    let (_, sha256) = writer.write_file(&path, |w| w.write_all(b"hello")).unwrap();
+    writer.finish().unwrap();
+
+    assert_eq!(sha256.to_string(), "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824");
+    let archive = SharedZipArchive::from_raw_data(sink.into_inner(), "test".to_owned());
+    assert_eq!(archive.entry_sha256(&String::from(path)).unwrap(), sha256);
+}
+
+#[test]
+fn test_packing_progress_and_cancellation(){
+    use std::sync::{Arc, Mutex};
+
+    let reports = Arc::new(Mutex::new(Vec::<PackingProgress>::new()));
+    let observed = reports.clone();
+    let cancellation = CancellationToken::new();
+    let options = PackingOptions{
+        observer: Some(Box::new(move |progress: &PackingProgress| observed.lock().unwrap().push(progress.clone()))),
+        cancellation: cancellation.clone(),
+        ..Default::default()
+    };
+    let mut writer = ModelZipWriter::with_options(std::io::Cursor::new(Vec::<u8>::new()), options);
+    writer.set_total_bytes(Some(3 * 1024 * 1024));
+    writer.start_component("weights");
+    let data = vec![7u8; 3 * 1024 * 1024];
+    writer.write_file(&FsPath::unique_suffixed(".bin"), |w| w.write_all(&data)).unwrap();
+    {
+        let reports = reports.lock().unwrap();
+        assert_eq!(reports.first().unwrap().bytes_written, 0);
+        assert_eq!(reports.last().unwrap().bytes_written, 3 * 1024 * 1024);
+        assert!(reports.iter().all(|report| report.component == "weights" && report.total_bytes == Some(3 * 1024 * 1024)));
+    }
+
+    cancellation.cancel();
+    let result = writer.write_file(&FsPath::unique_suffixed(".bin"), |w| w.write_all(&data));
+    assert!(matches!(result, Err(ModelPackingError::Cancelled)));
+}
+
+#[test]
+fn test_compression_per_entry_type(){
+    use crate::zip_archive_ext::SharedZipArchive;
+
+    let data = vec![0u8; 100_000];
+    let pack = |compression: CompressionOptions| {
+        let mut sink = std::io::Cursor::new(Vec::<u8>::new());
+        let options = PackingOptions{compression, ..Default::default()};
+        let mut writer = ModelZipWriter::with_options(&mut sink, options);
+        let tensor_path = FsPath::unique_suffixed(".npy");
+        let weights_path = FsPath::unique_suffixed(".pt");
+        writer.write_file(&tensor_path, |w| w.write_all(&data)).unwrap();
+        writer.write_file(&weights_path, |w| w.write_all(&data)).unwrap();
+        writer.finish().unwrap();
+        let archive = SharedZipArchive::from_raw_data(sink.into_inner(), "test".to_owned());
+        let compressed_size = |path: FsPath| archive.with_entry(&String::from(path), |e| e.compressed_size()).unwrap();
+        assert_eq!(archive.read_full_entry(&String::from(&tensor_path)).unwrap(), data);
+        (compressed_size(tensor_path), compressed_size(weights_path))
+    };
+
+    let (tensor_size, weights_size) = pack(CompressionOptions::default());
+    assert!(tensor_size < 1000);
+    assert_eq!(weights_size, 100_000);
+    assert_eq!(pack(CompressionOptions::stored()), (100_000, 100_000));
+}
+
+#[test]
+fn test_deterministic_packing(){
+    use crate::FileSource;
+
+    let pack = || {
+        let mut sink = std::io::Cursor::new(Vec::<u8>::new());
+        let options = PackingOptions{deterministic: true, ..Default::default()};
+        let mut writer = ModelZipWriter::with_options(&mut sink, options);
+        let paths: Vec<String> = (0..2).map(|_| {
+            let path = writer.entry_path("covers/cover.png", "_cover_image.png");
+            writer.write_file(&path, |w| w.write_all(b"not really a png")).unwrap();
+            String::from(path)
+        }).collect();
+        let attachment = FileSource::Data{data: std::sync::Arc::from(b"a,b\n".as_slice()), name: Some("table.csv".into())};
+        let descr = attachment.dump_as_file_description(&mut writer, "attachments/attachment").unwrap();
+        writer.finish().unwrap();
+        assert_eq!(paths, ["covers/cover.png", "covers/cover_2.png"]);
+        assert_eq!(descr.source.to_string(), "table.csv");
+        sink.into_inner()
+    };
+    let first = pack();
+    assert_eq!(first, pack());
+}
@@ -1,9 +1,9 @@
 use std::{
-    io::{Read, Seek, Write}, path::{Path, PathBuf}, sync::Arc
+    io::{Seek, Write}, path::{Path, PathBuf}, sync::Arc
 };
 
-use bioimg_spec::rdf::{model::ModelRdfName, FileReference, FsPath, HttpUrl, LicenseId, ResourceId, Version};
-use bioimg_spec::rdf::model::unsupported::Version_0_5_4_OrLater;
+use bioimg_spec::rdf::{model::ModelRdfName, FileReference, FsPath, LicenseId, ResourceId, Version};
+use bioimg_spec::rdf::model::unsupported::Version_0_6_X_OrLater;
 use bioimg_spec::rdf::model::unsupported::UnsupportedLegacyModel;
 use bioimg_spec::rdf::model::unsupported::UnsupportedFutureModel;
 use bioimg_spec::rdf;
@@ -13,21 +13,32 @@ use bioimg_spec::rdf::model::RdfTypeModel;
 use bioimg_spec::rdf::bounded_string::BoundedString;
 use bioimg_spec::rdf::model::unsupported::Version_0_4_X_OrEarlier;
 use bioimg_spec::rdf::model::ModelRdfV0_5;
+use bioimg_spec::rdf::model::ModelRdfV0_4;
+use bioimg_spec::rdf::model::model_rdf_0_4::ModelUpgradeError;
+use bioimg_spec::rdf::model::LossyConversion;
 use bioimg_spec::rdf::maintainer::Maintainer;
 use bioimg_spec::rdf::file_reference::FsPathComponent;
 use bioimg_spec::rdf::author::Author2;
 use bioimg_spec::rdf::model as  modelrdf;
+use bioimg_spec::rdf::model::dataset_descr::DatasetDescrEnum;
 use image::ImageError;
 
 use crate::{FileSource, Icon, ModelInterface, NpyArray, TensorValidationError};
 use crate::zip_archive_ext::{SharedZipArchive, ZipArchiveOpenError};
+use crate::model_package::{ModelPackage, PackageReadError};
+use crate::file_source::FileSourceError;
+use crate::url_fetcher::{DefaultUrlFetcher, FetchError, UrlFetcher};
 use crate::cover_image::CoverImageLoadingError;
 use crate::CoverImage;
-use crate::zip_writer_ext::ModelZipWriter;
+use crate::zip_writer_ext::{ModelZipWriter, PackingOptions};
 use crate::npy_array::ArcNpyArray;
 use crate::model_weights::{ModelWeights, ModelWeightsLoadingError};
 use crate::model_interface::{InputSlot, ModelInterfaceLoadingError, OutputSlot};
 use crate::icon::IconLoadingError;
+#[cfg(not(target_arch="wasm32"))]
+use crate::test_model::{TestModelError, TestModelReport, Tolerance};
+#[cfg(test)]
+use crate::test_model::OutputComparison;
 
 #[derive(thiserror::Error, Debug)]
 pub enum ModelPackingError {
@@ -45,10 +56,12 @@ pub enum ModelPackingError {
     RdfSerializationError(#[from] serde_json::Error),
     #[error("Could not write yaml file to zip: {0}")]
     SerdeYamlError(#[from] serde_yaml::Error),
-    #[error("Http error: {reason}")]
-    HttpErro{reason: String},
-    #[error("Unexpected status ({status})when requesting {url}")]
-    UnexpectedHttpStatus{status: u16, url: HttpUrl},
+    #[error(transparent)]
+    FetchError(#[from] FetchError),
+    #[error("Packing was cancelled")]
+    Cancelled,
+    #[error("Checksum mismatch for {path}: expected sha256 {expected}, found {found}")]
+    Sha256Mismatch{path: String, expected: rdf::Sha256, found: rdf::Sha256},
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -61,30 +74,140 @@ pub enum ModelLoadingError{
     ZipError(#[from] zip::result::ZipError),
     #[error(transparent)]
     ZipArchiveOpen(#[from] ZipArchiveOpenError),
+    #[error("Could not read file from model package: {0}")]
+    PackageReadError(#[from] PackageReadError),
+    #[error("Could not load an attachment: {0}")]
+    AttachmentLoadingError(#[from] FileSourceError),
     #[error("Could not parse model rdf as yaml: {0}")]
     YamlParsingError(#[from] serde_yaml::Error),
     #[error("Could not load a cover image: {0}")]
     CoverImageLoadingError(#[from] CoverImageLoadingError),
     #[error("Could not load an icon: {0}")]
     IconLoadingError(#[from] IconLoadingError),
-    #[error("Url file reference not supported yet")]
-    UrlFileReferenceNotSupportedYet,
+    #[error(transparent)]
+    FetchError(#[from] FetchError),
+    #[error("Documentation is not valid UTF-8: {0}")]
+    DocumentationNotUtf8(#[from] std::string::FromUtf8Error),
     #[error("Error loading models from rdf: {0}")]
     ModelWeightsLoadingError(#[from] ModelWeightsLoadingError),
     #[error("Could not load model interface: {0}")]
-    ModelInterfaceLoadingError(#[from] ModelInterfaceLoadingError),
+    ModelInterfaceLoadingError(ModelInterfaceLoadingError),
     #[error("Could not produce a valid Input tensor description: {0}")]
     InputTensorParsingError(#[from] modelrdf::input_tensor::InputTensorParsingError),
     #[error("Invalid input/output configurtation: {0}")]
     TensorValidationError(#[from] TensorValidationError),
     #[error("Unsupported legacy model version: {version}. The earliest supported version is {earliest_supported}")]
-    UnsupportedLegacyModel{version: Version_0_4_X_OrEarlier, earliest_supported: Version},
+    UnsupportedLegacyModel{version: Box<Version_0_4_X_OrEarlier>, earliest_supported: Box<Version>},
     #[error("Rdf version is too new for this application: {format_version}. The latest supported version is {latest_supported}")]
-    FutureModel{format_version: Version_0_5_4_OrLater, latest_supported: Version},
+    FutureModel{format_version: Box<Version_0_6_X_OrLater>, latest_supported: Box<Version>},
     #[error("Bad rdf: {inner}")]
     BadModel{inner: serde_yaml::Error},
+    #[error("Could not upgrade legacy model: {0}")]
+    LegacyModelUpgradeError(#[from] ModelUpgradeError),
     #[error("Unrecognized rdf data (found version {format_version:?})")]
     UnrecognizedRdf{format_version: Option<String>},
+    #[error("Checksum mismatch for {path}: expected sha256 {expected}, found {found}")]
+    Sha256Mismatch{path: String, expected: rdf::Sha256, found: rdf::Sha256},
+}
+
+impl From<ModelInterfaceLoadingError> for ModelLoadingError{
+    fn from(err: ModelInterfaceLoadingError) -> Self {
+        use crate::sample_tensor::SampleTensorLoadingError;
+        match err{
+            ModelInterfaceLoadingError::FileSourceOpenError(FileSourceError::Sha256Mismatch { path, expected, found }) |
+            ModelInterfaceLoadingError::SampleTensorLoadingError(SampleTensorLoadingError::FileSourceError(
+                FileSourceError::Sha256Mismatch { path, expected, found }
+            )) => Self::Sha256Mismatch { path, expected, found },
+            err => Self::ModelInterfaceLoadingError(err),
+        }
+    }
+}
+
+/// The declared-checksum files that loading doesn't read anyway, as their field in the rdf and their
+/// path inside the package. Test and sample tensors are checked as they are parsed, and weights whenever they are read
+pub(crate) fn declared_sha256s(model_rdf: &ModelRdfV0_5) -> Vec<(String, String, rdf::Sha256)>{
+    let mut declared: Vec<(String, &FileReference, &Option<rdf::Sha256>)> = vec![];
+    for (idx, attachment) in model_rdf.attachments.iter().enumerate(){
+        declared.push((format!("attachments[{idx}]"), &attachment.source, &attachment.sha256));
+    }
+    let weights = &model_rdf.weights;
+    if let Some(pytorch) = &weights.pytorch_state_dict{
+        if let modelrdf::PytorchArchitectureDescr::FromFileDescr(arch) = &pytorch.architecture{
+            declared.push((
+                "weights.pytorch_state_dict.architecture".to_owned(), &arch.file_descr.source, &arch.file_descr.sha256
+            ));
+        }
+        if let Some(deps) = &pytorch.dependencies{
+            declared.push(("weights.pytorch_state_dict.dependencies".to_owned(), &deps.source, &deps.sha256));
+        }
+    }
+    if let Some(deps) = weights.tensorflow_saved_model_bundle.as_ref().and_then(|w| w.dependencies.as_ref()){
+        declared.push(("weights.tensorflow_saved_model_bundle.dependencies".to_owned(), &deps.source, &deps.sha256));
+    }
+
+    declared.into_iter()
+        .filter_map(|(rdf_path, source, sha256)| match (source, sha256){
+            (FileReference::Path(path), Some(sha256)) => Some((rdf_path, String::from(path), sha256.clone())),
+            // remote files can only be checked once they are fetched
+            _ => None,
+        })
+        .collect()
+}
+
+/// The contents of the documentation file of a model
+pub(crate) fn read_documentation(
+    documentation: &FileReference, package: &ModelPackage, fetcher: &dyn UrlFetcher
+) -> Result<String, ModelLoadingError>{
+    match documentation{
+        FileReference::Url(url) => Ok(String::from_utf8(fetcher.fetch(url)?)?),
+        FileReference::Path(path) => {
+            let mut documentation = String::new();
+            let path_string: String = path.into();
+            package.with_entry(&path_string, |entry| {
+                entry.read_to_string(&mut documentation)
+            })??;
+            Ok(documentation)
+        },
+    }
+}
+
+/// The raw contents of the rdf.yaml (or bioimageio.yaml) at the root of `package`
+pub(crate) fn read_model_rdf_bytes(package: &ModelPackage) -> Result<Vec<u8>, ModelLoadingError>{
+    let Some(rdf_file_name) = ["rdf.yaml", "bioimageio.yaml"].into_iter().find(|name| package.has_entry(name)) else {
+        return Err(ModelLoadingError::RdfYamlNotFound)
+    };
+    Ok(package.read_full_entry(rdf_file_name)?)
+}
+
+/// Reads and parses the rdf.yaml (or bioimageio.yaml) at the root of `package`. Legacy 0.4 models are
+/// upgraded to 0.5 and models in newer 0.5 patch versions are read as the latest supported one. Anything
+/// that could not be carried over exactly is returned alongside the model
+pub fn read_model_rdf(package: &ModelPackage) -> Result<(ModelRdfV0_5, Vec<LossyConversion>), ModelLoadingError>{
+    let model_rdf_bytes = read_model_rdf_bytes(package)?;
+    match ModelRdfV0_5::from_yaml_slice(&model_rdf_bytes){
+        Ok(parsed) => Ok(parsed),
+        Err(v5_err) => {
+            if let Ok(legacy_model) = serde_yaml::from_slice::<UnsupportedLegacyModel>(&model_rdf_bytes){
+                if !legacy_model.format_version.is_upgradable(){
+                    return Err(ModelLoadingError::UnsupportedLegacyModel {
+                        version: Box::new(legacy_model.format_version),
+                        earliest_supported: Box::new(Version::version_0_4_0()),
+                    })
+                }
+                let legacy_model_rdf = serde_yaml::from_slice::<ModelRdfV0_4>(&model_rdf_bytes)
+                    .map_err(|inner| ModelLoadingError::BadModel { inner })?;
+                let upgraded = legacy_model_rdf.upgrade()?;
+                return Ok((upgraded.model_rdf, upgraded.lossy_conversions))
+            }
+            if let Ok(future_model) = serde_yaml::from_slice::<UnsupportedFutureModel>(&model_rdf_bytes){
+                return Err(ModelLoadingError::FutureModel{
+                    format_version: Box::new(future_model.format_version),
+                    latest_supported: Box::new(Version_0_5_x::latest_supported_version()),
+                })
+            }
+            Err(ModelLoadingError::BadModel { inner: v5_err })
+        }
+    }
 }
 
 pub struct ZooModel {
@@ -105,9 +228,11 @@ pub struct ZooModel {
     pub license: LicenseId,
     pub name: ModelRdfName,
     pub id: Option<ResourceId>,
-    // training_data: DatasetDescrEnum, //FIXME
+    pub training_data: Option<DatasetDescrEnum>,
     pub weights: ModelWeights,
     pub interface: ModelInterface<ArcNpyArray>,
+    /// What could not be carried over exactly when this model was converted to the latest supported format on load
+    pub lossy_conversions: Vec<LossyConversion>,
 }
 
 impl ZooModel{
@@ -117,68 +242,52 @@ impl ZooModel{
     }
 
     pub fn try_load_archive(archive: SharedZipArchive) -> Result<Self, ModelLoadingError>{
-        let model_rdf_bytes: Vec<u8> = 'model_rdf: {
-            for file_name in ["rdf.yaml", "bioimageio.yaml"]{
-                match archive.read_full_entry(file_name) {
-                    Ok(bytes) => break 'model_rdf bytes,
-                    Err(zip_err) => match zip_err{
-                        zip::result::ZipError::FileNotFound => continue,
-                        err => return Err(ModelLoadingError::ZipError(err))
-                    }
-                };
-            }
-            return Err(ModelLoadingError::RdfYamlNotFound)
-        };
-        let model_rdf = match serde_yaml::from_slice::<ModelRdfV0_5>(&model_rdf_bytes){
-            Ok(model_rdf) => model_rdf,
-            Err(v5_err) => {
-                if let Ok(legacy_model) = serde_yaml::from_slice::<UnsupportedLegacyModel>(&model_rdf_bytes){
-                    return Err(ModelLoadingError::UnsupportedLegacyModel {
-                        version: legacy_model.format_version,
-                        earliest_supported: Version_0_5_x::earliest_supported_version(),
-                    })
-                }
-                if let Ok(future_model) = serde_yaml::from_slice::<UnsupportedFutureModel>(&model_rdf_bytes){
-                    return Err(ModelLoadingError::FutureModel{
-                        format_version: future_model.format_version,
-                        latest_supported: Version_0_5_x::latest_supported_version(),
-                    })
-                }
-                return Err(ModelLoadingError::BadModel { inner: v5_err })
+        Self::try_load_archive_with_fetcher(archive, &DefaultUrlFetcher::default())
+    }
+
+    /// Loads a model, using `fetcher` to retrieve the files the rdf references by URL and that must be
+    /// parsed right away. Other URL references are kept as `FileSource::HttpUrl`
+    pub fn try_load_archive_with_fetcher(
+        archive: SharedZipArchive, fetcher: &dyn UrlFetcher,
+    ) -> Result<Self, ModelLoadingError>{
+        Self::try_load_package_with_fetcher(ModelPackage::Zip(archive), fetcher)
+    }
+
+    /// Loads an unpacked model from a directory containing its rdf.yaml, with all relative
+    /// file references resolved against that directory
+    #[cfg(not(target_arch="wasm32"))]
+    pub fn try_load_dir(dir: &Path) -> Result<Self, ModelLoadingError>{
+        Self::try_load_package_with_fetcher(ModelPackage::Dir(Arc::from(dir)), &DefaultUrlFetcher::default())
+    }
+
+    pub fn try_load_package_with_fetcher(
+        package: ModelPackage, fetcher: &dyn UrlFetcher,
+    ) -> Result<Self, ModelLoadingError>{
+        let (model_rdf, lossy_conversions) = read_model_rdf(&package)?;
+        for (_, path, expected) in declared_sha256s(&model_rdf){
+            let found = package.entry_sha256(&path)?;
+            if found != expected{
+                return Err(ModelLoadingError::Sha256Mismatch { path, expected, found })
             }
-        };
+        }
 
         let covers: Vec<CoverImage> = model_rdf.covers.into_iter()
-            .map(|rdf_cover| CoverImage::try_load(rdf_cover, &archive))
+            .map(|rdf_cover| CoverImage::try_load(rdf_cover, &package, fetcher))
             .collect::<Result<_, _>>()?;
 
-        let attachments: Vec<FileSource> = model_rdf.attachments.into_iter()
-            .map(|att| match att.source{
-                rdf::FileReference::Url(_) => return Err(ModelLoadingError::UrlFileReferenceNotSupportedYet),
-                rdf::FileReference::Path(fs_path) => {
-                    Ok(FileSource::FileInZipArchive { archive: archive.clone(), inner_path: Arc::from(String::from(fs_path).as_str()) })
-                }
-            })
+        let attachments: Vec<FileSource> = model_rdf.attachments.iter()
+            .map(|att| FileSource::from_rdf_file_descr(package.clone(), att))
             .collect::<Result<_, _>>()?;
-        let icon = model_rdf.icon.map(|icon| Icon::try_load(icon, &archive)).transpose()?;
-
-        let mut documentation = String::new();
-        match model_rdf.documentation{
-            rdf::FileReference::Url(_) => return Err(ModelLoadingError::UrlFileReferenceNotSupportedYet),
-            FileReference::Path(path) => {
-                let path_string: String = path.into();
-                archive.with_entry(&path_string, |entry| {
-                    entry.read_to_string(&mut documentation)
-                })??;
-            },
-        }
-        let weights = ModelWeights::try_from_rdf(model_rdf.weights, archive.clone())?;
+        let icon = model_rdf.icon.map(|icon| Icon::try_load(icon, &package, fetcher)).transpose()?;
+
+        let documentation = read_documentation(&model_rdf.documentation, &package, fetcher)?;
+        let weights = ModelWeights::try_from_rdf(model_rdf.weights, package.clone(), fetcher)?;
 
         let input_slots: Vec<_> = model_rdf.inputs.into_inner().into_iter()
-            .map(|rdf| InputSlot::<Arc<NpyArray>>::try_from_rdf(rdf, archive.clone()))
+            .map(|rdf| InputSlot::<Arc<NpyArray>>::try_from_rdf(rdf, package.clone(), fetcher))
             .collect::<Result<_, _>>()?;
         let output_slots: Vec<_> = model_rdf.outputs.into_inner().into_iter()
-            .map(|rdf| OutputSlot::<Arc<NpyArray>>::try_from_rdf(rdf, archive.clone()))
+            .map(|rdf| OutputSlot::<Arc<NpyArray>>::try_from_rdf(rdf, package.clone(), fetcher))
             .collect::<Result<_, _>>()?;
 
         let model_interface = ModelInterface::try_build(input_slots, output_slots)?;
@@ -201,13 +310,26 @@ impl ZooModel{
             license: model_rdf.license,
             name: model_rdf.name,
             id: model_rdf.id,
+            training_data: model_rdf.training_data,
             weights,
             interface: model_interface,
+            lossy_conversions,
         })
     }
 }
 
 impl ZooModel {
+    /// Runs the model with the first weights format that can be executed in this runtime
+    /// and compares the results with the output test tensors
+    #[cfg(not(target_arch="wasm32"))]
+    pub fn test_model(&self, tolerance: Tolerance) -> Result<TestModelReport, TestModelError>{
+        let Some(onnx_weights) = self.weights.onnx() else {
+            return Err(TestModelError::NoRunnableWeights)
+        };
+        let mut runner = crate::onnx_runner::OnnxRunner::try_new(onnx_weights, &self.interface)?;
+        self.interface.test_model(&mut runner, tolerance)
+    }
+
     #[cfg(not(target_arch="wasm32"))]
     pub fn pack_into_tmp(self) -> Result<std::fs::File, ModelPackingError>{
         let mut tmp_file = tempfile::tempfile()?;
@@ -216,27 +338,111 @@ impl ZooModel {
         Ok(tmp_file)
     }
     pub fn pack_into<Sink: Write + Seek>(self, sink: Sink) -> Result<(), ModelPackingError> {
-        let mut writer = ModelZipWriter::new(sink);
+        self.pack_into_with(sink, PackingOptions::default())
+    }
+
+    /// Packs the model, downloading any remote files it refers to with `fetcher`
+    pub fn pack_into_with_fetcher<Sink: Write + Seek>(
+        self, sink: Sink, fetcher: impl UrlFetcher + 'static,
+    ) -> Result<(), ModelPackingError> {
+        self.pack_into_with(sink, PackingOptions{fetcher: Box::new(fetcher), ..Default::default()})
+    }
+
+    /// Packs into a temporary file next to `path` which is only moved into place if packing succeeds.
+    /// The temporary file is removed if packing fails or gets cancelled
+    #[cfg(not(target_arch="wasm32"))]
+    pub fn pack_into_path(self, path: &Path, options: PackingOptions) -> Result<(), ModelPackingError>{
+        let mut temp_path = path.as_os_str().to_owned();
+        temp_path.push(".partial");
+        let temp_path = PathBuf::from(temp_path);
+
+        let result = std::fs::File::create(&temp_path)
+            .map_err(ModelPackingError::from)
+            .and_then(|file| self.pack_into_with(file, options))
+            .and_then(|_| Ok(std::fs::rename(&temp_path, path)?));
+        if result.is_err(){
+            _ = std::fs::remove_file(&temp_path);
+        }
+        result
+    }
+
+    /// Writes the model as an unpacked package, i.e. an rdf.yaml and all its files, into `dir`, which
+    /// must either not exist or be empty. The model is packed into a temporary archive first so that
+    /// failing or cancelling doesn't leave a half-written package behind
+    #[cfg(not(target_arch="wasm32"))]
+    pub fn pack_into_dir(self, dir: &Path, options: PackingOptions) -> Result<(), ModelPackingError>{
+        if dir.exists() && std::fs::read_dir(dir)?.next().is_some(){
+            return Err(ModelPackingError::AlreadyExists(dir.to_owned()))
+        }
+        let mut tmp_file = tempfile::tempfile()?;
+        // there is no point in compressing files that will be extracted right away
+        self.pack_into_with(&mut tmp_file, PackingOptions{compression: crate::zip_writer_ext::CompressionOptions::stored(), ..options})?;
+        tmp_file.rewind()?;
+        zip::ZipArchive::new(tmp_file)?.extract(dir)?;
+        Ok(())
+    }
+
+    /// The URLs of the files that packing this model has to download
+    pub fn remote_urls(&self) -> Vec<&rdf::HttpUrl>{
+        self.attachments.iter()
+            .chain(self.weights.sources())
+            .filter_map(|source| match source{
+                FileSource::HttpUrl(url) => Some(url.as_ref()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Rough size of the packed model, if it can be known without downloading anything
+    pub fn estimated_packed_size(&self) -> Option<u64>{
+        let mut total = self.documentation.len() as u64;
+        for input in self.interface.inputs().iter(){
+            total += input.test_tensor.nbytes() as u64;
+            total += input.sample_tensor.as_ref().map(|sample| sample.bytes().len() as u64).unwrap_or(0);
+        }
+        for output in self.interface.outputs().iter(){
+            total += output.test_tensor.nbytes() as u64;
+            total += output.sample_tensor.as_ref().map(|sample| sample.bytes().len() as u64).unwrap_or(0);
+        }
+        for attachment in self.attachments.iter(){
+            total += attachment.size_hint()?;
+        }
+        Some(total + self.weights.size_hint()?)
+    }
+
+    pub fn pack_into_with<Sink: Write + Seek>(self, sink: Sink, options: PackingOptions) -> Result<(), ModelPackingError> {
+        let total_bytes = self.estimated_packed_size();
+        let mut writer = ModelZipWriter::with_options(sink, options);
+        writer.set_total_bytes(total_bytes);
 
+        writer.start_component("test tensors");
         let (inputs, outputs) = self.interface.dump(&mut writer)?;
+        writer.start_component("covers");
         let covers = self.covers.iter().map(|cov| {
             cov.dump(&mut writer)
         }).collect::<Result<Vec<_>, _>>()?;
+        writer.start_component("attachments");
         let attachments = self.attachments.iter().map(|file|{
-            file.dump_as_file_description(&mut writer)
+            file.dump_as_file_description(&mut writer, "attachments/attachment")
         }).collect::<Result<Vec<_>, _>>()?;
         let icon: Option<rdf::Icon> = match &self.icon{
             Some(icon) => Some(icon.dump(&mut writer)?),
             None => None,
         };
+        writer.start_component("documentation");
         let documentation: FileReference = {
-            let documentation_path = FsPath::unique_suffixed("_README.md");
+            let documentation_path = writer.entry_path("README.md", "_README.md");
             writer.write_file(&documentation_path, |writer| -> Result<FileReference, std::io::Error> {
                 writer.write_all(self.documentation.as_bytes())?;
                 Ok(FileReference::Path(documentation_path.clone()))
-            })?
+            })?.0
         };
-        let timestamp = iso8601_timestamp::Timestamp::now_utc();
+        let timestamp = if writer.is_deterministic(){
+            iso8601_timestamp::Timestamp::UNIX_EPOCH
+        } else {
+            iso8601_timestamp::Timestamp::now_utc()
+        };
+        writer.start_component("weights");
         let weights = self.weights.rdf_dump(&mut writer)?;
 
         let model_rdf = ModelRdfV0_5 {
@@ -263,16 +469,172 @@ impl ZooModel {
             outputs,
             run_mode: None,
             timestamp,
-            training_data: None, //FIXME
+            training_data: self.training_data,
             weights,
         };
         let model_json_val = serde_json::to_value(&model_rdf).unwrap();
 
         let rdf_file_name = FsPathComponent::try_from("rdf.yaml".to_owned()).unwrap();
         let rdf_path = FsPath::from_components(vec![rdf_file_name]).unwrap();
+        writer.start_component("rdf.yaml");
         writer.write_file(&rdf_path, |writer| serde_yaml::to_writer(writer, &model_json_val))?;
 
         writer.finish()?;
         Ok(())
     }
 }
+
+/// Writes an unpacked package of a model computing `relu(raw)` into `dir`, with `expected_output`
+/// as the output test tensor for the input test tensor `[-1, 2, -3, 4]`
+#[cfg(test)]
+pub(crate) fn write_relu_model_package(dir: &Path, expected_output: NpyArray){
+    use prost::Message;
+
+    let write_npy = |name: &str, arr: NpyArray| {
+        let mut raw = vec![];
+        arr.write_npy(&mut raw).unwrap();
+        std::fs::write(dir.join(name), raw).unwrap();
+    };
+    write_npy("test_input.npy", ndarray::arr2(&[[-1.0f32, 2.0, -3.0, 4.0]]).into_dyn().into());
+    write_npy("test_output.npy", expected_output);
+    std::fs::write(dir.join("weights.onnx"), crate::onnx_runner::relu_model_proto().encode_to_vec()).unwrap();
+    std::fs::write(dir.join("README.md"), "# Relu\n").unwrap();
+    std::fs::write(dir.join("rdf.yaml"), r#"
+format_version: 0.5.4
+type: model
+name: Relu model
+description: Zeroes out negative values
+authors: [{name: Jane Doe}]
+cite: [{text: Some paper, doi: 10.1000/xyz123}]
+documentation: README.md
+license: MIT
+inputs:
+  - id: raw
+    axes: [{type: batch}, {type: space, id: x, size: 4}]
+    test_tensor: {source: test_input.npy}
+outputs:
+  - id: activated
+    axes: [{type: batch}, {type: space, id: x, size: {tensor_id: raw, axis_id: x}}]
+    test_tensor: {source: test_output.npy}
+weights:
+  onnx: {source: weights.onnx, opset_version: 13}
+"#).unwrap();
+}
+
+#[test]
+fn test_zoo_model_test_model(){
+    let load = |expected_output: NpyArray| {
+        let dir = tempfile::tempdir().unwrap();
+        write_relu_model_package(dir.path(), expected_output);
+        (ZooModel::try_load_dir(dir.path()).unwrap(), dir)
+    };
+
+    let (model, _dir) = load(ndarray::arr2(&[[0.0f32, 2.0, 0.0, 4.0]]).into_dyn().into());
+    let report = model.test_model(Tolerance::default()).unwrap();
+    assert!(report.passed(), "{report}");
+    assert_eq!(report.outputs[0].0.to_string(), "activated");
+
+    let (model, _dir) = load(ndarray::arr2(&[[0.0f32, 2.0, 0.0, 5.0]]).into_dyn().into());
+    let report = model.test_model(Tolerance::default()).unwrap();
+    assert!(!report.passed());
+    assert!(matches!(report.outputs[0].1, OutputComparison::Compared { num_mismatched: 1, num_elements: 4, .. }));
+}
+
+#[test]
+fn test_unpacked_dir_round_trip(){
+    let source_dir = tempfile::tempdir().unwrap();
+    write_relu_model_package(source_dir.path(), ndarray::arr2(&[[0.0f32, 2.0, 0.0, 4.0]]).into_dyn().into());
+    let model = ZooModel::try_load_dir(source_dir.path()).unwrap();
+
+    let out_dir = tempfile::tempdir().unwrap();
+    let packed_dir = out_dir.path().join("packed");
+    ZooModel::try_load_dir(source_dir.path()).unwrap().pack_into_dir(&packed_dir, PackingOptions::default()).unwrap();
+    assert!(matches!(
+        ZooModel::try_load_dir(source_dir.path()).unwrap().pack_into_dir(&packed_dir, PackingOptions::default()),
+        Err(ModelPackingError::AlreadyExists(_))
+    ));
+
+    let reloaded = ZooModel::try_load_dir(&packed_dir).unwrap();
+    assert_eq!(reloaded.name, model.name);
+    assert_eq!(reloaded.documentation, model.documentation);
+    assert_eq!(reloaded.interface.inputs()[0].test_tensor.shape(), &[1, 4]);
+    let read_weights = |model: &ZooModel| {
+        let mut weights = vec![];
+        model.weights.onnx().unwrap().weights.read_to_end(&mut weights).unwrap();
+        weights
+    };
+    assert_eq!(read_weights(&reloaded), read_weights(&model));
+    assert!(reloaded.test_model(Tolerance::default()).unwrap().passed());
+
+    // rdfs can't reach files outside of their directory
+    std::fs::write(out_dir.path().join("secret.txt"), "secret").unwrap();
+    let rdf = std::fs::read_to_string(packed_dir.join("rdf.yaml")).unwrap();
+    let documentation = format!("documentation: {}", model_rdf_documentation(&rdf));
+    std::fs::write(packed_dir.join("rdf.yaml"), rdf.replace(&documentation, "documentation: ../secret.txt")).unwrap();
+    assert!(ZooModel::try_load_dir(&packed_dir).is_err());
+}
+
+/// The value of the `documentation` field of a yaml rdf
+#[cfg(test)]
+fn model_rdf_documentation(rdf: &str) -> String{
+    let rdf: serde_yaml::Value = serde_yaml::from_str(rdf).unwrap();
+    rdf["documentation"].as_str().unwrap().to_owned()
+}
+
+#[test]
+fn test_url_test_tensors_go_through_the_fetcher(){
+    use crate::url_fetcher::{InMemoryFetcher, NoFetcher};
+
+    let dir = tempfile::tempdir().unwrap();
+    write_relu_model_package(dir.path(), ndarray::arr2(&[[0.0f32, 2.0, 0.0, 4.0]]).into_dyn().into());
+    let rdf = std::fs::read_to_string(dir.path().join("rdf.yaml")).unwrap();
+    let url = "https://example.com/test_input.npy";
+    std::fs::write(dir.path().join("rdf.yaml"), rdf.replace("{source: test_input.npy}", &format!("{{source: '{url}'}}"))).unwrap();
+    let package = || ModelPackage::Dir(Arc::from(dir.path()));
+
+    assert!(matches!(
+        ZooModel::try_load_package_with_fetcher(package(), &NoFetcher),
+        Err(ModelLoadingError::ModelInterfaceLoadingError(ModelInterfaceLoadingError::FileSourceOpenError(
+            FileSourceError::FetchError(FetchError::Unsupported { .. })
+        )))
+    ));
+
+    let fetcher = InMemoryFetcher::default().with_file(url, std::fs::read(dir.path().join("test_input.npy")).unwrap());
+    let model = ZooModel::try_load_package_with_fetcher(package(), &fetcher).unwrap();
+    assert_eq!(model.interface.inputs()[0].test_tensor.shape(), &[1, 4]);
+}
+
+#[test]
+fn test_declared_checksums_are_verified_when_files_are_read(){
+    let wrong_sha256 = "0".repeat(64);
+    let write_package = |dir: &Path, original: &str, replacement: &str| {
+        write_relu_model_package(dir, ndarray::arr2(&[[0.0f32, 2.0, 0.0, 4.0]]).into_dyn().into());
+        let rdf = std::fs::read_to_string(dir.join("rdf.yaml")).unwrap();
+        std::fs::write(dir.join("rdf.yaml"), rdf.replace(original, replacement)).unwrap();
+    };
+
+    let dir = tempfile::tempdir().unwrap();
+    write_package(dir.path(), "{source: test_input.npy}", &format!("{{source: test_input.npy, sha256: {wrong_sha256}}}"));
+    assert!(matches!(ZooModel::try_load_dir(dir.path()), Err(ModelLoadingError::Sha256Mismatch { .. })));
+
+    // weights are only hashed once they are needed
+    let dir = tempfile::tempdir().unwrap();
+    write_package(dir.path(), "opset_version: 13", &format!("opset_version: 13, sha256: {wrong_sha256}"));
+    let model = ZooModel::try_load_dir(dir.path()).unwrap();
+    assert!(matches!(
+        model.test_model(Tolerance::default()),
+        Err(TestModelError::ModelRunnerError(crate::model_runner::ModelRunnerError::WeightsReadError(
+            FileSourceError::Sha256Mismatch { .. }
+        )))
+    ));
+    assert!(matches!(model.pack_into_tmp(), Err(ModelPackingError::Sha256Mismatch { .. })));
+
+    let dir = tempfile::tempdir().unwrap();
+    write_relu_model_package(dir.path(), ndarray::arr2(&[[0.0f32, 2.0, 0.0, 4.0]]).into_dyn().into());
+    let model = ZooModel::try_load_dir(dir.path()).unwrap();
+    let mut packed = vec![];
+    std::io::Read::read_to_end(&mut model.pack_into_tmp().unwrap(), &mut packed).unwrap();
+    // the packed model declares the checksums of all of its files, which must check out
+    let repacked = ZooModel::try_load_archive(SharedZipArchive::from_raw_data(packed, "packed".to_owned())).unwrap();
+    assert!(repacked.test_model(Tolerance::default()).unwrap().passed());
+}
@@ -33,6 +33,19 @@ impl<R: Borrow<FileReference>> Display for FileDescription<R>{
 #[derive(Clone, Debug, serde::Serialize, serde::Deserialize, PartialEq, Eq)]
 pub struct Sha256(Lowercase<BoundedString<64, 64>>);
 
+impl From<[u8; 32]> for Sha256 {
+    fn from(digest: [u8; 32]) -> Self {
+        let hex: String = digest.iter().map(|byte| format!("{byte:02x}")).collect();
+        Self(Lowercase::try_from(hex.as_str()).unwrap())
+    }
+}
+
+impl Display for Sha256 {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
 impl AsPartial for Sha256 {
     type Partial = String;
     fn to_partial(self) -> Self::Partial {
@@ -134,6 +134,15 @@ impl InputAxis{
             Self::Space(axis) => Some(axis.size.clone()),
         }
     }
+
+    /// Physical size of one pixel along this axis. Only time and space axes carry a scale
+    pub fn scale(&self) -> AxisScale {
+        match self {
+            Self::Time(axis) => axis.scale,
+            Self::Space(axis) => axis.scale,
+            _ => AxisScale::default(),
+        }
+    }
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
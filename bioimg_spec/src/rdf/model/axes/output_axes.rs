@@ -175,6 +175,28 @@ impl OutputAxis{
             Self::Space(axis) => Some(axis.size.size()),
         }
     }
+
+    /// Physical size of one pixel along this axis. Only time and space axes carry a scale
+    pub fn scale(&self) -> AxisScale {
+        match self {
+            Self::Time(axis) => axis.scale,
+            Self::Space(axis) => axis.scale,
+            _ => AxisScale::default(),
+        }
+    }
+
+    /// Number of pixels on each side of this axis that should be cropped from the output
+    pub fn halo(&self) -> Option<Halo> {
+        let size = match self {
+            Self::Time(axis) => &axis.size,
+            Self::Space(axis) => &axis.size,
+            _ => return None,
+        };
+        match size {
+            OutputSpacetimeSize::Haloed(HaloedOutputSpacetimeSize{halo, ..}) => Some(*halo),
+            OutputSpacetimeSize::Standard(_) => None,
+        }
+    }
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone, AsPartial)]
@@ -13,7 +13,7 @@ pub enum DatasetDescrEnum{
     LinkedDatasetDescr(LinkedDatasetDescr),
 }
 
-#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, Default)]
 #[serde(into = "String")]
 #[serde(try_from = "String")]
 pub struct DatasetDescrMarker;
@@ -42,14 +42,18 @@ impl TryFrom<String> for DatasetDescrMarker{
     }
 }
 
-#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, AsPartial)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default, AsPartial)]
 #[aspartial(name = PartialDatasetDescr)]
 pub struct DatasetDescr{
     #[serde(rename = "type")]
-    marker: DatasetDescrMarker,
+    pub marker: DatasetDescrMarker,
     /// URL to the source of the dataset
     #[serde(default)]
-    source: Option<HttpUrl>
+    pub source: Option<HttpUrl>,
+    /// The remaining fields of the dataset description (e.g. `name`, `description`, `authors`),
+    /// kept verbatim so they survive being loaded and written back
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
 
@@ -59,5 +63,34 @@ pub struct DatasetDescr{
 #[aspartial(name = PartialLinkedDatasetDescr)]
 pub struct LinkedDatasetDescr{
     /// A valid dataset `id` from the bioimage.io collection.
-    id: ResourceId
+    pub id: ResourceId,
+    /// The version number (n-th published version, not the semantic version) of the linked dataset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version_number: Option<u64>,
+}
+
+#[test]
+fn test_dataset_descr_round_trip(){
+    use serde_json::json;
+
+    let inline = json!({
+        "type": "dataset",
+        "source": "https://example.com/dataset.zip",
+        "name": "Nuclei",
+        "description": "Fluorescence images of nuclei",
+        "authors": [{"name": "John Doe"}],
+        "license": "CC-BY-4.0",
+    });
+    let parsed: DatasetDescrEnum = serde_json::from_value(inline.clone()).unwrap();
+    assert!(matches!(&parsed, DatasetDescrEnum::DatasetDescr(descr) if descr.extra["name"] == "Nuclei"));
+    assert_eq!(serde_json::to_value(&parsed).unwrap(), inline);
+
+    let linked = json!({"id": "affable-shark", "version_number": 2});
+    let parsed: DatasetDescrEnum = serde_json::from_value(linked.clone()).unwrap();
+    assert!(matches!(&parsed, DatasetDescrEnum::LinkedDatasetDescr(LinkedDatasetDescr{version_number: Some(2), ..})));
+    assert_eq!(serde_json::to_value(&parsed).unwrap(), linked);
+
+    let unversioned = json!({"id": "affable-shark"});
+    let parsed: DatasetDescrEnum = serde_json::from_value(unversioned.clone()).unwrap();
+    assert_eq!(serde_json::to_value(&parsed).unwrap(), unversioned);
 }
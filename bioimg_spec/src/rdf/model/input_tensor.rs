@@ -4,12 +4,15 @@ use aspartial::AsPartial;
 use serde::{Deserialize, Serialize};
 
 use crate::rdf::FileDescription;
-use super::{axes::input_axes::InputAxisGroup, preprocessing::{BinarizeDescr, PreprocessingDescr, ScaleLinearDescr, ScaleRangeDescr, Zmuv}, AxisId, TensorId, TensorTextDescription};
+use super::tensor_data_descr::{AnyTensorDataDescr, TensorDataDescrValidationError};
+use super::{axes::input_axes::{InputAxis, InputAxisGroup}, preprocessing::{BinarizeDescr, PreprocessingDescr, ScaleLinearDescr, ScaleRangeDescr, Zmuv}, AxisId, TensorId, TensorTextDescription};
 
 #[derive(thiserror::Error, Debug)]
 pub enum InputTensorParsingError{
     #[error("{tensor_id}.preprocessing[{preproc_idx}] references non-existing axis '{reference}'")]
     PreprocessingReferencesNonExistingAxis{tensor_id: TensorId, preproc_idx: usize, reference: AxisId},
+    #[error("{tensor_id}.data is invalid: {source}")]
+    BadDataDescription{tensor_id: TensorId, source: TensorDataDescrValidationError},
 }
 
 
@@ -53,11 +56,15 @@ pub struct InputTensorMetadata {
     ///   changing the data type.
     preprocessing: Vec<PreprocessingDescr>,
     axes: InputAxisGroup,
+    /// Description of the tensor's data values, optionally per channel.
+    /// When missing, the spec's default (unbounded float32 values) applies.
+    data: Option<AnyTensorDataDescr>,
 }
 
 impl InputTensorMetadata{
     pub fn axes(&self) -> &InputAxisGroup{ &self.axes }
     pub fn preprocessing(&self) -> &Vec<PreprocessingDescr>{ &self.preprocessing }
+    pub fn data(&self) -> Option<&AnyTensorDataDescr>{ self.data.as_ref() }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -70,6 +77,8 @@ pub struct InputTensorMetadataMsg {
     #[serde(default)]
     pub description: TensorTextDescription,
     pub axes: InputAxisGroup,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<AnyTensorDataDescr>,
 }
 
 impl TryFrom<InputTensorMetadataMsg> for InputTensorMetadata{
@@ -112,12 +121,23 @@ impl TryFrom<InputTensorMetadataMsg> for InputTensorMetadata{
             }
         }
 
+        if let Some(data) = &message.data{
+            let num_channels = message.axes.iter().find_map(|axis| match axis{
+                InputAxis::Channel(channel_axis) => Some(channel_axis.channel_names.len().get()),
+                _ => None,
+            });
+            data.validate(num_channels).map_err(|source| InputTensorParsingError::BadDataDescription {
+                tensor_id: message.id.clone(), source
+            })?;
+        }
+
         Ok(Self{
             id: message.id,
             optional: message.optional,
             preprocessing: message.preprocessing,
             description: message.description,
             axes: message.axes,
+            data: message.data,
         })
     }
 }
@@ -130,6 +150,7 @@ impl From<InputTensorMetadata> for InputTensorMetadataMsg{
             preprocessing: value.preprocessing,
             description: value.description,
             axes: value.axes,
+            data: value.data,
         }
     }
 }
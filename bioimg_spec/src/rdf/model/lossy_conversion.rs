@@ -0,0 +1,71 @@
+use std::fmt::Display;
+
+use serde_json::Value;
+
+/// Something in a model description that had to be dropped or approximated when reading it as
+/// the latest supported 0.5 version, e.g. when upgrading a 0.4 model
+#[derive(serde::Serialize, Clone, Debug, PartialEq)]
+pub struct LossyConversion{
+    /// Where in the original rdf.yaml the affected field is, e.g. `outputs[0].halo`
+    pub path: String,
+    pub message: String,
+}
+
+impl Display for LossyConversion{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+/// The paths of all fields that are set in `raw` but missing from `parsed`, which is `raw` after being
+/// deserialized and serialized back. List items are identified by their position, e.g. `outputs[0].halo`
+pub(crate) fn dropped_fields(raw: &Value, parsed: &Value) -> Vec<String>{
+    let mut dropped = vec![];
+    collect_dropped_fields("", raw, parsed, &mut dropped);
+    dropped
+}
+
+fn collect_dropped_fields(path: &str, raw: &Value, parsed: &Value, dropped: &mut Vec<String>){
+    match (raw, parsed){
+        (Value::Object(raw), Value::Object(parsed)) => {
+            for (key, raw_value) in raw.iter().filter(|(_, value)| !value.is_null()){
+                let field_path = if path.is_empty() { key.clone() } else { format!("{path}.{key}") };
+                match parsed.get(key){
+                    Some(parsed_value) if !parsed_value.is_null() => {
+                        collect_dropped_fields(&field_path, raw_value, parsed_value, dropped)
+                    },
+                    _ => dropped.push(field_path),
+                }
+            }
+        },
+        (Value::Array(raw), Value::Array(parsed)) => {
+            for (idx, (raw_item, parsed_item)) in raw.iter().zip(parsed.iter()).enumerate(){
+                collect_dropped_fields(&format!("{path}[{idx}]"), raw_item, parsed_item, dropped)
+            }
+        },
+        _ => (),
+    }
+}
+
+#[test]
+fn test_dropped_fields(){
+    use serde_json::json;
+
+    let raw = json!({
+        "name": "my model",
+        "git_repo": null,
+        "outputs": [
+            {"id": "mask", "halo": [1, 2]},
+            {"id": "labels", "some_future_field": {"a": 1}},
+        ],
+    });
+    let parsed = json!({
+        "name": "my model",
+        "outputs": [
+            {"id": "mask"},
+            {"id": "labels"},
+        ],
+    });
+    assert_eq!(dropped_fields(&raw, &parsed), ["outputs[0].halo", "outputs[1].some_future_field"]);
+    assert!(dropped_fields(&parsed, &parsed).is_empty());
+}
@@ -20,7 +20,10 @@ pub mod run_mode;
 pub mod dataset_descr;
 pub mod unsupported;
 pub mod model_rdf_0_5;
-// pub mod model_rdf_0_4;
+pub mod model_diff;
+pub mod lossy_conversion;
+pub mod model_rdf_0_4;
+pub mod validation;
 
 pub use axes::{
     AxisType, AxisId, AxisScale,
@@ -41,6 +44,7 @@ pub use weights::{
     MaybeSomeWeightsDescr,
     KerasHdf5WeightsDescr,
     WeightsDescrBase,
+    WeightsFormat,
     OnnxWeightsDescr,
     OnnxOpsetVersion,
     PytorchStateDictWeightsDescr,
@@ -52,6 +56,10 @@ pub use weights::{
 pub use preprocessing::PreprocessingDescr;
 pub use data_type::DataType;
 pub use model_rdf_0_5::ModelRdfV0_5;
+pub use model_rdf_0_4::ModelRdfV0_4;
+pub use model_diff::{ModelChange, ModelDiff};
+pub use lossy_conversion::LossyConversion;
+pub use validation::{Severity, ValidationIssue, ValidationReport};
 
 #[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
 #[serde(try_from = "String")]
@@ -0,0 +1,238 @@
+use std::fmt::Display;
+
+use serde_json::{Map, Value};
+
+use super::ModelRdfV0_5;
+
+/// A single difference between two model descriptions.
+///
+/// `path` points at the field that differs, e.g. `inputs[raw].axes[x].size`. Items of lists whose
+/// entries all have an `id` are identified by it, other list items by their position
+#[derive(serde::Serialize, Clone, Debug, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ModelChange{
+    Added{path: String, value: Value},
+    Removed{path: String, value: Value},
+    Changed{path: String, old: Value, new: Value},
+    /// A file that is present in both models but whose contents, as told by their sha256, differ
+    FileContentChanged{path: String, old_sha256: String, new_sha256: String},
+}
+
+impl ModelChange{
+    pub fn path(&self) -> &str{
+        match self{
+            Self::Added { path, .. } => path,
+            Self::Removed { path, .. } => path,
+            Self::Changed { path, .. } => path,
+            Self::FileContentChanged { path, .. } => path,
+        }
+    }
+}
+
+impl Display for ModelChange{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self{
+            Self::Added { path, value } => write!(f, "+ {path}: {value}"),
+            Self::Removed { path, value } => write!(f, "- {path}: {value}"),
+            Self::Changed { path, old, new } => write!(f, "~ {path}: {old} -> {new}"),
+            Self::FileContentChanged { path, old_sha256, new_sha256 } => {
+                write!(f, "~ {path}: file contents changed (sha256 {old_sha256} -> {new_sha256})")
+            },
+        }
+    }
+}
+
+/// The field by field differences between two model descriptions.
+///
+/// Files described with a sha256 are compared by their contents rather than by their paths,
+/// so that re-packing a model under different file names doesn't show up as a change
+#[derive(serde::Serialize, Clone, Debug, Default, PartialEq)]
+pub struct ModelDiff{
+    pub changes: Vec<ModelChange>,
+}
+
+impl ModelDiff{
+    pub fn new(old: &ModelRdfV0_5, new: &ModelRdfV0_5) -> Self{
+        Self::from_values(&serde_json::to_value(old).unwrap(), &serde_json::to_value(new).unwrap())
+    }
+
+    /// Compares two serialized model descriptions, e.g. after filling in hashes of files that
+    /// the descriptions only reference by path
+    pub fn from_values(old: &Value, new: &Value) -> Self{
+        let mut changes = vec![];
+        diff_values("", old, new, &mut changes);
+        Self{changes}
+    }
+
+    pub fn is_empty(&self) -> bool{
+        self.changes.is_empty()
+    }
+
+    pub fn to_json(&self) -> Value{
+        serde_json::to_value(self).unwrap()
+    }
+}
+
+impl Display for ModelDiff{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.changes.is_empty(){
+            return write!(f, "No changes")
+        }
+        for change in &self.changes{
+            writeln!(f, "{change}")?;
+        }
+        Ok(())
+    }
+}
+
+fn join(path: &str, key: &str) -> String{
+    if path.is_empty(){
+        key.to_owned()
+    } else {
+        format!("{path}.{key}")
+    }
+}
+
+fn diff_values(path: &str, old: &Value, new: &Value, changes: &mut Vec<ModelChange>){
+    match (old, new){
+        (Value::Object(old), Value::Object(new)) => diff_objects(path, old, new, changes),
+        (Value::Array(old), Value::Array(new)) => diff_arrays(path, old, new, changes),
+        (old, new) if old == new => (),
+        (old, new) => changes.push(ModelChange::Changed { path: path.to_owned(), old: old.clone(), new: new.clone() }),
+    }
+}
+
+fn diff_objects(path: &str, old: &Map<String, Value>, new: &Map<String, Value>, changes: &mut Vec<ModelChange>){
+    // files with known contents are compared by hash, ignoring where they are stored
+    let mut skipped: &[&str] = &[];
+    if let (Some(Value::String(old_sha256)), Some(Value::String(new_sha256))) = (old.get("sha256"), new.get("sha256")){
+        if old_sha256 != new_sha256{
+            changes.push(ModelChange::FileContentChanged {
+                path: path.to_owned(), old_sha256: old_sha256.clone(), new_sha256: new_sha256.clone(),
+            });
+        }
+        skipped = &["source", "sha256"];
+    }
+    // a null field is the same as a missing one
+    let get = |map: &'_ Map<String, Value>, key: &str| -> Option<Value> {
+        map.get(key).filter(|value| !value.is_null()).cloned()
+    };
+    for key in old.keys().chain(new.keys().filter(|key| !old.contains_key(*key))){
+        if skipped.contains(&key.as_str()){
+            continue
+        }
+        let field_path = join(path, key);
+        match (get(old, key), get(new, key)){
+            (Some(old_value), Some(new_value)) => diff_values(&field_path, &old_value, &new_value, changes),
+            (Some(value), None) => changes.push(ModelChange::Removed { path: field_path, value }),
+            (None, Some(value)) => changes.push(ModelChange::Added { path: field_path, value }),
+            (None, None) => (),
+        }
+    }
+}
+
+/// The ids of all items in `items`, if every item has a unique one
+fn item_ids(items: &[Value]) -> Option<Vec<&str>>{
+    let ids: Vec<&str> = items.iter()
+        .map(|item| item.get("id").and_then(|id| id.as_str()))
+        .collect::<Option<_>>()?;
+    let mut unique_ids = ids.clone();
+    unique_ids.sort();
+    unique_ids.dedup();
+    (unique_ids.len() == ids.len()).then_some(ids)
+}
+
+fn diff_arrays(path: &str, old: &[Value], new: &[Value], changes: &mut Vec<ModelChange>){
+    let is_scalar = |value: &Value| !value.is_object() && !value.is_array();
+    // processing steps are identified by the operation they perform, which may repeat, and their order matters
+    let is_processing = path.ends_with("preprocessing") || path.ends_with("postprocessing");
+
+    if !is_processing && old.iter().chain(new.iter()).all(is_scalar){
+        // lists of plain values like tags are compared as sets
+        for value in old.iter().filter(|value| !new.contains(value)){
+            changes.push(ModelChange::Removed { path: path.to_owned(), value: value.clone() });
+        }
+        for value in new.iter().filter(|value| !old.contains(value)){
+            changes.push(ModelChange::Added { path: path.to_owned(), value: value.clone() });
+        }
+        return
+    }
+
+    if let (false, Some(old_ids), Some(new_ids)) = (is_processing, item_ids(old), item_ids(new)){
+        for (old_item, id) in old.iter().zip(old_ids.iter()){
+            let item_path = format!("{path}[{id}]");
+            match new_ids.iter().position(|new_id| new_id == id){
+                Some(new_idx) => diff_values(&item_path, old_item, &new[new_idx], changes),
+                None => changes.push(ModelChange::Removed { path: item_path, value: old_item.clone() }),
+            }
+        }
+        for (new_item, id) in new.iter().zip(new_ids.iter()){
+            if !old_ids.contains(id){
+                changes.push(ModelChange::Added { path: format!("{path}[{id}]"), value: new_item.clone() });
+            }
+        }
+        return
+    }
+
+    for (idx, (old_item, new_item)) in old.iter().zip(new.iter()).enumerate(){
+        diff_values(&format!("{path}[{idx}]"), old_item, new_item, changes);
+    }
+    for (idx, old_item) in old.iter().enumerate().skip(new.len()){
+        changes.push(ModelChange::Removed { path: format!("{path}[{idx}]"), value: old_item.clone() });
+    }
+    for (idx, new_item) in new.iter().enumerate().skip(old.len()){
+        changes.push(ModelChange::Added { path: format!("{path}[{idx}]"), value: new_item.clone() });
+    }
+}
+
+#[test]
+fn test_model_diff(){
+    use serde_json::json;
+
+    let old = json!({
+        "name": "my model",
+        "tags": ["unet", "2d"],
+        "inputs": [
+            {"id": "raw", "axes": [{"type": "batch"}, {"type": "space", "id": "x", "size": 64}], "preprocessing": [{"id": "binarize"}]},
+            {"axes": [], "id": "mask"},
+        ],
+        "weights": {
+            "onnx": {"source": "a.onnx", "sha256": "aaa", "opset_version": 15},
+            "torchscript": {"source": "b.pt", "sha256": "bbb"},
+        },
+        "git_repo": null,
+    });
+    let new = json!({
+        "name": "my model",
+        "tags": ["2d", "segmentation", "unet"],
+        "inputs": [
+            {"id": "raw", "axes": [{"type": "batch"}, {"type": "space", "id": "x", "size": 128}], "preprocessing": [{"id": "binarize"}, {"id": "clip"}]},
+        ],
+        "weights": {
+            "onnx": {"source": "some/other/path.onnx", "sha256": "aaa", "opset_version": 15},
+            "torchscript": {"source": "b.pt", "sha256": "ccc"},
+            "tensorflow_js": {"sha256": "ddd", "source": "c.zip"},
+        },
+        "git_repo": "https://github.com/example/model",
+    });
+
+    let diff = ModelDiff::from_values(&old, &new);
+    let mut lines: Vec<String> = diff.changes.iter().map(|change| change.to_string()).collect();
+    lines.sort();
+    assert_eq!(
+        lines,
+        [
+            "+ git_repo: \"https://github.com/example/model\"",
+            "+ inputs[raw].preprocessing[1]: {\"id\":\"clip\"}",
+            "+ tags: \"segmentation\"",
+            "+ weights.tensorflow_js: {\"sha256\":\"ddd\",\"source\":\"c.zip\"}",
+            "- inputs[mask]: {\"axes\":[],\"id\":\"mask\"}",
+            "~ inputs[raw].axes[1].size: 64 -> 128",
+            "~ weights.torchscript: file contents changed (sha256 bbb -> ccc)",
+        ]
+    );
+    let change = diff.changes.iter().find(|change| change.path() == "inputs[raw].axes[1].size").unwrap();
+    assert_eq!(serde_json::to_value(change).unwrap()["kind"], "changed");
+    assert_eq!(diff.to_json()["changes"].as_array().unwrap().len(), 7);
+    assert!(ModelDiff::from_values(&old, &old).is_empty());
+}
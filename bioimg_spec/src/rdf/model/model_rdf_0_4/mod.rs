@@ -0,0 +1,350 @@
+//! Model descriptions in the legacy 0.4.x format.
+//!
+//! These types only exist so that older models can be read and upgraded into a [ModelRdfV0_5](super::ModelRdfV0_5)
+//! via [ModelRdfV0_4::upgrade]; they are never written back out.
+
+pub mod upgrade;
+
+use crate::rdf::{
+    version::Version_0_4_x, Author2, CiteEntry2, CoverImageSource, FileReference, HttpUrl, Icon, LicenseId,
+    Maintainer, NonEmptyList, Sha256, Version,
+};
+use crate::util::SingleOrMultiple;
+
+use super::{DataType, RdfTypeModel, WeightsFormat};
+
+pub use upgrade::{ModelUpgradeError, UpgradedModelRdf};
+
+/// A 0.4 axes string like `"bcyx"`, where each character is one of
+/// `b` (batch), `i` (index), `t` (time), `c` (channel), `z`, `y` or `x` (space)
+pub type AxesV0_4 = String;
+
+#[derive(serde::Deserialize, Debug, Clone)]
+pub struct ParameterizedInputShapeV0_4{
+    pub min: Vec<usize>,
+    pub step: Vec<usize>,
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum InputShapeV0_4{
+    Fixed(Vec<usize>),
+    Parameterized(ParameterizedInputShapeV0_4),
+}
+
+/// Output shape given relative to an input tensor: `shape = reference_shape * scale + 2 * offset`.
+/// A `scale` of `None` marks an axis that is not present in the reference tensor
+#[derive(serde::Deserialize, Debug, Clone)]
+pub struct ImplicitOutputShapeV0_4{
+    pub reference_tensor: String,
+    pub scale: Vec<Option<f32>>,
+    pub offset: Vec<f32>,
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum OutputShapeV0_4{
+    Fixed(Vec<usize>),
+    Implicit(ImplicitOutputShapeV0_4),
+}
+
+#[derive(serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProcessingModeV0_4{
+    #[serde(rename = "fixed")]
+    Fixed,
+    #[serde(rename = "per_dataset")]
+    PerDataset,
+    #[serde(rename = "per_sample")]
+    PerSample,
+}
+
+fn _default_to_100f32() -> f32{
+    100.0
+}
+
+fn _default_eps() -> f32{
+    1e-6
+}
+
+fn _default_to_single_1() -> SingleOrMultiple<f32>{
+    SingleOrMultiple::Single(1.0)
+}
+
+fn _default_to_single_0() -> SingleOrMultiple<f32>{
+    SingleOrMultiple::Single(0.0)
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+pub struct BinarizeKwargsV0_4{
+    pub threshold: f32,
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+pub struct ClipKwargsV0_4{
+    pub min: f32,
+    pub max: f32,
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+pub struct ScaleLinearKwargsV0_4{
+    #[serde(default)]
+    pub axes: Option<AxesV0_4>,
+    #[serde(default = "_default_to_single_1")]
+    pub gain: SingleOrMultiple<f32>,
+    #[serde(default = "_default_to_single_0")]
+    pub offset: SingleOrMultiple<f32>,
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+pub struct ZeroMeanUnitVarianceKwargsV0_4{
+    #[serde(default = "_default_per_sample")]
+    pub mode: ProcessingModeV0_4,
+    #[serde(default)]
+    pub axes: Option<AxesV0_4>,
+    #[serde(default)]
+    pub mean: Option<SingleOrMultiple<f32>>,
+    #[serde(default)]
+    pub std: Option<SingleOrMultiple<f32>>,
+    #[serde(default = "_default_eps")]
+    pub eps: f32,
+}
+
+fn _default_per_sample() -> ProcessingModeV0_4{
+    ProcessingModeV0_4::PerSample
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+pub struct ScaleRangeKwargsV0_4{
+    #[serde(default = "_default_per_sample")]
+    pub mode: ProcessingModeV0_4,
+    #[serde(default)]
+    pub axes: Option<AxesV0_4>,
+    #[serde(default)]
+    pub min_percentile: f32,
+    #[serde(default = "_default_to_100f32")]
+    pub max_percentile: f32,
+    #[serde(default = "_default_eps")]
+    pub eps: f32,
+    #[serde(default)]
+    pub reference_tensor: Option<String>,
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+pub struct ScaleMeanVarianceKwargsV0_4{
+    #[serde(default = "_default_per_sample")]
+    pub mode: ProcessingModeV0_4,
+    pub reference_tensor: String,
+    #[serde(default)]
+    pub axes: Option<AxesV0_4>,
+    #[serde(default = "_default_eps")]
+    pub eps: f32,
+}
+
+/// A pre- or postprocessing step. `scale_mean_variance` is only allowed in postprocessing
+#[derive(serde::Deserialize, Debug, Clone)]
+#[serde(tag = "name", content = "kwargs")]
+pub enum ProcessingV0_4{
+    #[serde(rename = "binarize")]
+    Binarize(BinarizeKwargsV0_4),
+    #[serde(rename = "clip")]
+    Clip(ClipKwargsV0_4),
+    #[serde(rename = "scale_linear")]
+    ScaleLinear(ScaleLinearKwargsV0_4),
+    #[serde(rename = "sigmoid")]
+    Sigmoid(Option<serde_json::Map<String, serde_json::Value>>),
+    #[serde(rename = "zero_mean_unit_variance")]
+    ZeroMeanUnitVariance(ZeroMeanUnitVarianceKwargsV0_4),
+    #[serde(rename = "scale_range")]
+    ScaleRange(ScaleRangeKwargsV0_4),
+    #[serde(rename = "scale_mean_variance")]
+    ScaleMeanVariance(ScaleMeanVarianceKwargsV0_4),
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+pub struct InputTensorDescrV0_4{
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub axes: AxesV0_4,
+    pub data_type: DataType,
+    #[serde(default)]
+    pub data_range: Option<(Option<f32>, Option<f32>)>,
+    pub shape: InputShapeV0_4,
+    #[serde(default)]
+    pub preprocessing: Vec<ProcessingV0_4>,
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+pub struct OutputTensorDescrV0_4{
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub axes: AxesV0_4,
+    pub data_type: DataType,
+    #[serde(default)]
+    pub data_range: Option<(Option<f32>, Option<f32>)>,
+    pub shape: OutputShapeV0_4,
+    /// Number of pixels on each side of each axis that are unreliable and should be cropped
+    #[serde(default)]
+    pub halo: Option<Vec<usize>>,
+    #[serde(default)]
+    pub postprocessing: Vec<ProcessingV0_4>,
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+pub struct WeightsDescrBaseV0_4{
+    pub source: FileReference,
+    #[serde(default)]
+    pub sha256: Option<Sha256>,
+    #[serde(default)]
+    pub authors: Option<Vec<Author2>>,
+    #[serde(default)]
+    pub attachments: Option<serde_json::Value>,
+    #[serde(default)]
+    pub parent: Option<WeightsFormat>,
+    /// Dependency manager and file, e.g. `conda:environment.yaml`
+    #[serde(default)]
+    pub dependencies: Option<String>,
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+pub struct KerasHdf5WeightsDescrV0_4{
+    #[serde(flatten)]
+    pub base: WeightsDescrBaseV0_4,
+    #[serde(default)]
+    pub tensorflow_version: Option<Version>,
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+pub struct OnnxWeightsDescrV0_4{
+    #[serde(flatten)]
+    pub base: WeightsDescrBaseV0_4,
+    #[serde(default)]
+    pub opset_version: Option<u32>,
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+pub struct PytorchStateDictWeightsDescrV0_4{
+    #[serde(flatten)]
+    pub base: WeightsDescrBaseV0_4,
+    /// Either `path/to/file.py:MyNetworkClass` or `my_module.submodule.get_my_model`
+    pub architecture: String,
+    #[serde(default)]
+    pub architecture_sha256: Option<Sha256>,
+    #[serde(default)]
+    pub kwargs: serde_json::Map<String, serde_json::Value>,
+    #[serde(default)]
+    pub pytorch_version: Option<Version>,
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+pub struct TensorflowJsWeightsDescrV0_4{
+    #[serde(flatten)]
+    pub base: WeightsDescrBaseV0_4,
+    #[serde(default)]
+    pub tensorflow_version: Option<Version>,
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+pub struct TensorflowSavedModelBundleWeightsDescrV0_4{
+    #[serde(flatten)]
+    pub base: WeightsDescrBaseV0_4,
+    #[serde(default)]
+    pub tensorflow_version: Option<Version>,
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+pub struct TorchscriptWeightsDescrV0_4{
+    #[serde(flatten)]
+    pub base: WeightsDescrBaseV0_4,
+    #[serde(default)]
+    pub pytorch_version: Option<Version>,
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+pub struct WeightsDescrV0_4{
+    #[serde(default)]
+    pub keras_hdf5: Option<KerasHdf5WeightsDescrV0_4>,
+    #[serde(default)]
+    pub onnx: Option<OnnxWeightsDescrV0_4>,
+    #[serde(default)]
+    pub pytorch_state_dict: Option<PytorchStateDictWeightsDescrV0_4>,
+    #[serde(default)]
+    pub tensorflow_js: Option<TensorflowJsWeightsDescrV0_4>,
+    #[serde(default)]
+    pub tensorflow_saved_model_bundle: Option<TensorflowSavedModelBundleWeightsDescrV0_4>,
+    #[serde(default)]
+    #[serde(alias = "pytorch_script")]
+    pub torchscript: Option<TorchscriptWeightsDescrV0_4>,
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+pub struct AttachmentsV0_4{
+    #[serde(default)]
+    pub files: Vec<FileReference>,
+    /// Any other keys, which have no counterpart in 0.5
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+pub struct RunModeV0_4{
+    pub name: String,
+    #[serde(default)]
+    pub kwargs: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+pub struct ModelRdfV0_4{
+    pub format_version: Version_0_4_x,
+    #[serde(rename = "type")]
+    pub rdf_type: RdfTypeModel,
+    pub name: String,
+    pub description: String,
+    pub authors: NonEmptyList<Author2>,
+    #[serde(default)]
+    pub maintainers: Vec<Maintainer>,
+    #[serde(default)]
+    pub packaged_by: Vec<Author2>,
+    pub cite: NonEmptyList<CiteEntry2>,
+    pub documentation: FileReference,
+    pub license: LicenseId,
+    #[serde(default)]
+    pub covers: Vec<CoverImageSource>,
+    #[serde(default)]
+    pub attachments: Option<AttachmentsV0_4>,
+    #[serde(default)]
+    pub tags: Vec<crate::rdf::Tag>,
+    #[serde(default)]
+    pub links: Vec<String>,
+    #[serde(default)]
+    pub config: serde_json::Map<String, serde_json::Value>,
+    #[serde(default)]
+    pub git_repo: Option<HttpUrl>,
+    #[serde(default)]
+    pub icon: Option<Icon>,
+    /// 0.4 ids may contain slashes, e.g. zenodo DOIs, which 0.5 ids don't allow
+    #[serde(default)]
+    pub id: Option<String>,
+    #[serde(default)]
+    pub version: Option<Version>,
+    pub timestamp: iso8601_timestamp::Timestamp,
+    #[serde(default)]
+    pub run_mode: Option<RunModeV0_4>,
+    #[serde(default)]
+    pub training_data: Option<serde_json::Value>,
+    #[serde(default)]
+    pub parent: Option<serde_json::Value>,
+
+    pub inputs: NonEmptyList<InputTensorDescrV0_4>,
+    pub outputs: NonEmptyList<OutputTensorDescrV0_4>,
+    /// One test tensor per input, in the same order as `inputs`
+    pub test_inputs: NonEmptyList<FileReference>,
+    /// One test tensor per output, in the same order as `outputs`
+    pub test_outputs: NonEmptyList<FileReference>,
+    #[serde(default)]
+    pub sample_inputs: Vec<FileReference>,
+    #[serde(default)]
+    pub sample_outputs: Vec<FileReference>,
+    pub weights: WeightsDescrV0_4,
+}
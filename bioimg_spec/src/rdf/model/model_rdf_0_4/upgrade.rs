@@ -0,0 +1,842 @@
+use std::fmt::Display;
+use std::num::NonZeroUsize;
+
+use crate::rdf::model::{
+    self as modelrdf,
+    axes::NonBatchAxisId,
+    input_tensor::{InputTensorMetadata, InputTensorMetadataMsg},
+    output_tensor::{OutputTensorMetadata, OutputTensorMetadataMsg},
+    axes::output_axes::{HaloedOutputSpacetimeSize, OutputSpacetimeSize},
+    axis_size::FixedOrRefAxisSize,
+    dataset_descr::DatasetDescrEnum,
+    lossy_conversion::LossyConversion,
+    postprocessing::{PostprocessingDescr, ScaleMeanVarianceDescr},
+    preprocessing::{
+        BinarizeDescr, ClipDescr, FixedZmuv, FixedZmuvAlongAxis, PreprocessingDescr, PreprocessingEpsilon,
+        ScaleLinearDescr, ScaleRangeDescr, ScaleRangePercentile, SimpleBinarizeDescr, SimpleFixedZmuv,
+        SimpleScaleLinearDescr, Sigmoid, Zmuv,
+    },
+    preprocessing::scale_linear::{ScaleLinearAlongAxisDescr, ScaleLinearAlongAxisDescrMessage},
+    preprocessing::zero_mean_unit_variance::ZmuvStdDeviation,
+    run_mode::RunMode,
+    tensor_data_descr::{AnyTensorDataDescr, IntervalOrRatioDataDescr, TensorDataDescr},
+    AnyAxisSize, AxisId, AxisScale, AxisSizeReference, BatchAxis, ChannelAxis, Halo, IndexAxis, InputAxis,
+    InputTensorDescr, ModelRdfV0_5, OutputAxis, OutputTensorDescr, ParameterizedAxisSize, QualifiedAxisId,
+    SpaceInputAxis, SpaceOutputAxis, TensorId, TimeInputAxis, TimeOutputAxis, WeightsDescr,
+};
+use crate::rdf::{
+    version::Version_0_5_x, BoundedString, EnvironmentFile, FileDescription, FileReference, Identifier,
+    LitStr, NonEmptyList, ResourceId, Version,
+};
+
+use super::{
+    AxesV0_4, InputShapeV0_4, InputTensorDescrV0_4, ModelRdfV0_4, OutputShapeV0_4, OutputTensorDescrV0_4,
+    ProcessingModeV0_4, ProcessingV0_4, WeightsDescrBaseV0_4, WeightsDescrV0_4,
+};
+
+#[derive(thiserror::Error, Debug)]
+pub enum ModelUpgradeError{
+    #[error("Could not convert {path} to format 0.5: {reason}")]
+    Unconvertible{path: String, reason: String},
+}
+
+fn unconvertible(path: impl Into<String>, reason: impl Display) -> ModelUpgradeError{
+    ModelUpgradeError::Unconvertible { path: path.into(), reason: reason.to_string() }
+}
+
+pub struct UpgradedModelRdf{
+    pub model_rdf: ModelRdfV0_5,
+    /// Everything that could not be carried over exactly. Empty if the conversion was lossless
+    pub lossy_conversions: Vec<LossyConversion>,
+}
+
+impl ModelRdfV0_4{
+    /// Converts this description into the current format, failing only if something essential
+    /// (like the shape of a tensor) has no 0.5 equivalent
+    pub fn upgrade(self) -> Result<UpgradedModelRdf, ModelUpgradeError>{
+        let mut upgrader = Upgrader::default();
+        let model_rdf = upgrader.upgrade_model(self)?;
+        Ok(UpgradedModelRdf { model_rdf, lossy_conversions: upgrader.lossy_conversions })
+    }
+}
+
+fn axis_id(path: &str, letter: char) -> Result<AxisId, ModelUpgradeError>{
+    let raw = match letter{
+        'b' => "batch",
+        'i' => "index",
+        't' => "time",
+        'c' => "channel",
+        'x' => "x",
+        'y' => "y",
+        'z' => "z",
+        _ => return Err(unconvertible(path, format!("Unknown axis '{letter}'"))),
+    };
+    Ok(AxisId::try_from(raw).unwrap())
+}
+
+fn axis_ids(path: &str, axes: Option<&AxesV0_4>) -> Result<Option<NonEmptyList<AxisId>>, ModelUpgradeError>{
+    let Some(axes) = axes else {
+        return Ok(None)
+    };
+    let ids = axes.chars().map(|letter| axis_id(path, letter)).collect::<Result<Vec<_>, _>>()?;
+    Ok(NonEmptyList::try_from(ids).ok())
+}
+
+/// The one axis of `tensor_axes` that per-axis values like a list of gains apply to, i.e. the
+/// only non-batch axis that is not in `reduced_axes`
+fn along_axis(path: &str, tensor_axes: &str, reduced_axes: Option<&AxesV0_4>) -> Result<NonBatchAxisId, ModelUpgradeError>{
+    let reduced_axes = reduced_axes.map(|axes| axes.as_str()).unwrap_or("");
+    let remaining: Vec<char> = tensor_axes.chars()
+        .filter(|letter| *letter != 'b' && !reduced_axes.contains(*letter))
+        .collect();
+    let [letter] = remaining.as_slice() else {
+        return Err(unconvertible(path, format!(
+            "Can't tell which axis of '{tensor_axes}' the values apply to when reducing over '{reduced_axes}'"
+        )))
+    };
+    NonBatchAxisId::try_from(axis_id(path, *letter)?).map_err(|e| unconvertible(path, e))
+}
+
+fn channel_names(num_channels: NonZeroUsize) -> NonEmptyList<Identifier>{
+    let names: Vec<Identifier> = (0..num_channels.get())
+        .map(|idx| Identifier::try_from(format!("channel{idx}")).unwrap())
+        .collect();
+    names.try_into().unwrap()
+}
+
+fn data_descr(data_type: modelrdf::DataType, data_range: Option<(Option<f32>, Option<f32>)>) -> AnyTensorDataDescr{
+    let (min, max) = data_range.unwrap_or((None, None));
+    // 0.4 uses -inf and inf for unbounded ranges
+    let finite = |value: Option<f32>| value.filter(|v| v.is_finite());
+    AnyTensorDataDescr::Single(TensorDataDescr::IntervalOrRatio(IntervalOrRatioDataDescr{
+        data_type,
+        range: (finite(min), finite(max)),
+        ..Default::default()
+    }))
+}
+
+fn into_preprocessing(step: PostprocessingDescr) -> Option<PreprocessingDescr>{
+    Some(match step{
+        PostprocessingDescr::Binarize(descr) => PreprocessingDescr::Binarize(descr),
+        PostprocessingDescr::Clip(descr) => PreprocessingDescr::Clip(descr),
+        PostprocessingDescr::EnsureDtype(descr) => PreprocessingDescr::EnsureDtype(descr),
+        PostprocessingDescr::ScaleLinear(descr) => PreprocessingDescr::ScaleLinear(descr),
+        PostprocessingDescr::Sigmoid(descr) => PreprocessingDescr::Sigmoid(descr),
+        PostprocessingDescr::FixedZeroMeanUnitVariance(descr) => PreprocessingDescr::FixedZeroMeanUnitVariance(descr),
+        PostprocessingDescr::ZeroMeanUnitVariance(descr) => PreprocessingDescr::ZeroMeanUnitVariance(descr),
+        PostprocessingDescr::ScaleRange(descr) => PreprocessingDescr::ScaleRange(descr),
+        PostprocessingDescr::ScaleMeanVarianceDescr(_) => return None,
+    })
+}
+
+#[derive(Default)]
+struct Upgrader{
+    lossy_conversions: Vec<LossyConversion>,
+}
+
+impl Upgrader{
+    fn note(&mut self, path: impl Into<String>, message: impl Into<String>){
+        self.lossy_conversions.push(LossyConversion { path: path.into(), message: message.into() })
+    }
+
+    fn text<const MAX_CHARS: usize>(&mut self, path: &str, text: &str) -> BoundedString<0, MAX_CHARS>{
+        if let Ok(bounded) = BoundedString::try_from(text){
+            return bounded
+        }
+        let mut truncated = String::with_capacity(MAX_CHARS);
+        for c in text.chars(){
+            if truncated.len() + c.len_utf8() > MAX_CHARS{
+                break
+            }
+            truncated.push(c);
+        }
+        self.note(path, format!("Truncated to the maximum length of {MAX_CHARS} bytes"));
+        BoundedString::try_from(truncated).unwrap()
+    }
+
+    /// 0.5 tensor ids must be lowercase, so mixed case names are lowercased everywhere they appear
+    fn tensor_id(&mut self, path: &str, name: &str) -> Result<TensorId, ModelUpgradeError>{
+        if let Ok(tensor_id) = TensorId::try_from(name){
+            return Ok(tensor_id)
+        }
+        let tensor_id = TensorId::try_from(name.to_lowercase()).map_err(|e| unconvertible(path, e))?;
+        self.note(path, format!("Tensor '{name}' was renamed to '{tensor_id}'"));
+        Ok(tensor_id)
+    }
+
+    fn eps(&mut self, path: &str, eps: f32) -> Result<PreprocessingEpsilon, ModelUpgradeError>{
+        PreprocessingEpsilon::try_from(eps).map_err(|e| unconvertible(format!("{path}.eps"), e))
+    }
+
+    fn upgrade_processing(
+        &mut self, path: &str, step: ProcessingV0_4, tensor_axes: &str,
+    ) -> Result<PostprocessingDescr, ModelUpgradeError>{
+        let kwargs_path = format!("{path}.kwargs");
+        let dataset_mode_note = |this: &mut Self, mode: ProcessingModeV0_4|{
+            if mode == ProcessingModeV0_4::PerDataset{
+                this.note(
+                    format!("{kwargs_path}.mode"),
+                    "Statistics can no longer be computed over the whole dataset; they are now computed per sample",
+                );
+            }
+        };
+        Ok(match step{
+            ProcessingV0_4::Binarize(kwargs) => {
+                PostprocessingDescr::Binarize(BinarizeDescr::Simple(SimpleBinarizeDescr{threshold: kwargs.threshold}))
+            },
+            ProcessingV0_4::Clip(kwargs) => PostprocessingDescr::Clip(
+                ClipDescr::try_from_min_max(kwargs.min, kwargs.max).map_err(|e| unconvertible(&kwargs_path, e))?
+            ),
+            ProcessingV0_4::Sigmoid(_) => PostprocessingDescr::Sigmoid(Sigmoid),
+            ProcessingV0_4::ScaleLinear(kwargs) => {
+                let descr = match (kwargs.gain.as_slice(), kwargs.offset.as_slice()){
+                    ([gain], [offset]) => ScaleLinearDescr::Simple(SimpleScaleLinearDescr{gain: *gain, offset: *offset}),
+                    _ => {
+                        let message = ScaleLinearAlongAxisDescrMessage{
+                            axis: along_axis(&kwargs_path, tensor_axes, kwargs.axes.as_ref())?,
+                            gain: kwargs.gain,
+                            offset: kwargs.offset,
+                        };
+                        ScaleLinearDescr::AlongAxis(
+                            ScaleLinearAlongAxisDescr::try_from(message).map_err(|e| unconvertible(&kwargs_path, e))?
+                        )
+                    },
+                };
+                PostprocessingDescr::ScaleLinear(descr)
+            },
+            ProcessingV0_4::ZeroMeanUnitVariance(kwargs) if kwargs.mode == ProcessingModeV0_4::Fixed => {
+                let (Some(mean), Some(std)) = (&kwargs.mean, &kwargs.std) else {
+                    return Err(unconvertible(&kwargs_path, "'fixed' mode requires both 'mean' and 'std'"))
+                };
+                let std_dev = |std: f32| ZmuvStdDeviation::try_from(std).map_err(|e| unconvertible(format!("{kwargs_path}.std"), e));
+                let descr = match (mean.as_slice(), std.as_slice()){
+                    ([mean], [std]) => FixedZmuv::Simple(SimpleFixedZmuv{mean: *mean, std: std_dev(*std)?}),
+                    (means, stds) => {
+                        if means.len() != stds.len(){
+                            return Err(unconvertible(&kwargs_path, "'mean' and 'std' have different lengths"))
+                        }
+                        let mean_and_std = means.iter().zip(stds.iter())
+                            .map(|(mean, std)| Ok(SimpleFixedZmuv{mean: *mean, std: std_dev(*std)?}))
+                            .collect::<Result<Vec<_>, ModelUpgradeError>>()?;
+                        FixedZmuv::AlongAxis(FixedZmuvAlongAxis{
+                            mean_and_std: NonEmptyList::try_from(mean_and_std)
+                                .map_err(|_| unconvertible(&kwargs_path, "'mean' and 'std' can't be empty"))?,
+                            axis: along_axis(&kwargs_path, tensor_axes, kwargs.axes.as_ref())?,
+                        })
+                    },
+                };
+                PostprocessingDescr::FixedZeroMeanUnitVariance(descr)
+            },
+            ProcessingV0_4::ZeroMeanUnitVariance(kwargs) => {
+                dataset_mode_note(self, kwargs.mode);
+                PostprocessingDescr::ZeroMeanUnitVariance(Zmuv{
+                    axes: axis_ids(&kwargs_path, kwargs.axes.as_ref())?,
+                    eps: self.eps(&kwargs_path, kwargs.eps)?,
+                })
+            },
+            ProcessingV0_4::ScaleRange(kwargs) => {
+                if kwargs.mode == ProcessingModeV0_4::Fixed{
+                    return Err(unconvertible(format!("{kwargs_path}.mode"), "scale_range can't have a 'fixed' mode"))
+                }
+                dataset_mode_note(self, kwargs.mode);
+                let reference_tensor = match &kwargs.reference_tensor{
+                    Some(name) => Some(self.tensor_id(&format!("{kwargs_path}.reference_tensor"), name)?),
+                    None => None,
+                };
+                PostprocessingDescr::ScaleRange(ScaleRangeDescr{
+                    axes: axis_ids(&kwargs_path, kwargs.axes.as_ref())?.map(Vec::from),
+                    percentiles: ScaleRangePercentile::try_from_min_max(kwargs.min_percentile, kwargs.max_percentile)
+                        .map_err(|e| unconvertible(&kwargs_path, e))?,
+                    eps: self.eps(&kwargs_path, kwargs.eps)?,
+                    reference_tensor,
+                })
+            },
+            ProcessingV0_4::ScaleMeanVariance(kwargs) => {
+                dataset_mode_note(self, kwargs.mode);
+                PostprocessingDescr::ScaleMeanVarianceDescr(ScaleMeanVarianceDescr{
+                    reference_tensor: self.tensor_id(&format!("{kwargs_path}.reference_tensor"), &kwargs.reference_tensor)?,
+                    axes: axis_ids(&kwargs_path, kwargs.axes.as_ref())?,
+                    eps: self.eps(&kwargs_path, kwargs.eps)?,
+                })
+            },
+        })
+    }
+
+    fn upgrade_input(
+        &mut self,
+        path: &str,
+        descr: InputTensorDescrV0_4,
+        test_tensor: FileReference,
+        sample_tensor: Option<FileReference>,
+    ) -> Result<InputTensorDescr, ModelUpgradeError>{
+        let id = self.tensor_id(&format!("{path}.name"), &descr.name)?;
+        let (mins, steps) = match descr.shape{
+            InputShapeV0_4::Fixed(shape) => {
+                let steps = vec![0; shape.len()];
+                (shape, steps)
+            },
+            InputShapeV0_4::Parameterized(shape) => (shape.min, shape.step),
+        };
+        let num_axes = descr.axes.chars().count();
+        if mins.len() != num_axes || steps.len() != num_axes{
+            return Err(unconvertible(format!("{path}.shape"), format!("Expected {num_axes} values to match axes '{}'", descr.axes)))
+        }
+
+        let mut axes = Vec::<InputAxis>::with_capacity(num_axes);
+        for (axis_idx, (letter, (min, step))) in descr.axes.chars().zip(mins.into_iter().zip(steps)).enumerate(){
+            let shape_path = format!("{path}.shape[{axis_idx}]");
+            let Some(min) = NonZeroUsize::new(min) else {
+                return Err(unconvertible(shape_path, "Axis sizes must be greater than 0"))
+            };
+            let size = match NonZeroUsize::new(step){
+                None => AnyAxisSize::Fixed(min),
+                Some(step) => AnyAxisSize::Parameterized(ParameterizedAxisSize{min, step}),
+            };
+            let axis = match letter{
+                'b' => {
+                    let is_fixed_to_1 = step == 0 && min.get() == 1;
+                    if step == 0 && !is_fixed_to_1{
+                        self.note(&shape_path, format!("Batch size can only be fixed to 1, so a batch size of {min} is no longer enforced"));
+                    }
+                    InputAxis::Batch(BatchAxis{
+                        size: is_fixed_to_1.then(|| 1usize.try_into().unwrap()),
+                        ..Default::default()
+                    })
+                },
+                'c' => {
+                    if step != 0{
+                        return Err(unconvertible(shape_path, "Channel axes must have a fixed size"))
+                    }
+                    InputAxis::Channel(ChannelAxis{id: LitStr::new(), description: Default::default(), channel_names: channel_names(min)})
+                },
+                'i' => InputAxis::Index(IndexAxis{id: LitStr::new(), description: Default::default(), size}),
+                't' => InputAxis::Time(TimeInputAxis{
+                    id: axis_id(path, letter)?, description: Default::default(), unit: None, scale: AxisScale::default(), size,
+                }),
+                _ => InputAxis::Space(SpaceInputAxis{
+                    id: axis_id(&format!("{path}.axes"), letter)?, description: Default::default(), unit: None, scale: AxisScale::default(), size,
+                }),
+            };
+            axes.push(axis);
+        }
+
+        let mut preprocessing = Vec::with_capacity(descr.preprocessing.len());
+        for (step_idx, step) in descr.preprocessing.into_iter().enumerate(){
+            let step_path = format!("{path}.preprocessing[{step_idx}]");
+            let step = self.upgrade_processing(&step_path, step, &descr.axes)?;
+            let Some(step) = into_preprocessing(step) else {
+                return Err(unconvertible(step_path, "scale_mean_variance is only allowed in postprocessing"))
+            };
+            preprocessing.push(step);
+        }
+
+        let message = InputTensorMetadataMsg{
+            description: self.text(&format!("{path}.description"), &descr.description),
+            id,
+            optional: false,
+            preprocessing,
+            axes: axes.try_into().map_err(|e| unconvertible(format!("{path}.axes"), e))?,
+            data: Some(data_descr(descr.data_type, descr.data_range)),
+        };
+        Ok(InputTensorDescr{
+            meta: InputTensorMetadata::try_from(message).map_err(|e| unconvertible(path, e))?,
+            test_tensor: FileDescription { source: test_tensor, sha256: None },
+            sample_tensor: sample_tensor.map(|source| FileDescription { source, sha256: None }),
+        })
+    }
+
+    /// The size and scale of an output axis whose 0.4 size is `reference_size * scale + 2 * offset`
+    fn referenced_axis_size(
+        &mut self, path: &str, reference_tensor: &TensorId, reference_letter: char, scale: f32, offset: f32,
+    ) -> Result<(AnyAxisSize, AxisScale), ModelUpgradeError>{
+        let offset = 2.0 * offset;
+        if offset < 0.0 || offset.fract() != 0.0{
+            return Err(unconvertible(path, format!("A total offset of {offset} can't be expressed in 0.5, which only allows non-negative integers")))
+        }
+        // 0.5 references resolve to `reference_size * reference_scale / scale + offset`
+        let axis_scale = AxisScale::try_from(1.0 / scale).map_err(|e| unconvertible(path, e))?;
+        let reference = AxisSizeReference{
+            qualified_axis_id: QualifiedAxisId{tensor_id: reference_tensor.clone(), axis_id: axis_id(path, reference_letter)?},
+            offset: offset as usize,
+        };
+        Ok((AnyAxisSize::Reference(reference), axis_scale))
+    }
+
+    fn upgrade_output(
+        &mut self,
+        path: &str,
+        descr: OutputTensorDescrV0_4,
+        inputs: &[InputTensorDescrV0_4],
+        test_tensor: FileReference,
+        sample_tensor: Option<FileReference>,
+    ) -> Result<OutputTensorDescr, ModelUpgradeError>{
+        let id = self.tensor_id(&format!("{path}.name"), &descr.name)?;
+        let letters: Vec<char> = descr.axes.chars().collect();
+        let halos = descr.halo.clone().unwrap_or_else(|| vec![0; letters.len()]);
+        if halos.len() != letters.len(){
+            return Err(unconvertible(format!("{path}.halo"), format!("Expected {} values to match axes '{}'", letters.len(), descr.axes)))
+        }
+
+        let reference = match &descr.shape{
+            OutputShapeV0_4::Fixed(shape) => {
+                if shape.len() != letters.len(){
+                    return Err(unconvertible(format!("{path}.shape"), format!("Expected {} values to match axes '{}'", letters.len(), descr.axes)))
+                }
+                None
+            },
+            OutputShapeV0_4::Implicit(shape) => {
+                let shape_path = format!("{path}.shape");
+                if shape.scale.len() != letters.len() || shape.offset.len() != letters.len(){
+                    return Err(unconvertible(shape_path, format!("Expected {} scales and offsets to match axes '{}'", letters.len(), descr.axes)))
+                }
+                let Some(reference) = inputs.iter().find(|input| input.name == shape.reference_tensor) else {
+                    return Err(unconvertible(shape_path, format!("'{}' is not an input tensor", shape.reference_tensor)))
+                };
+                if reference.axes.chars().count() != letters.len(){
+                    return Err(unconvertible(shape_path, format!("Axes '{}' don't line up with the axes of '{}'", descr.axes, reference.name)))
+                }
+                let reference_id = self.tensor_id(&format!("{shape_path}.reference_tensor"), &reference.name)?;
+                Some((shape, reference, reference_id))
+            },
+        };
+
+        let mut axes = Vec::<OutputAxis>::with_capacity(letters.len());
+        for (axis_idx, letter) in letters.iter().copied().enumerate(){
+            let shape_path = format!("{path}.shape[{axis_idx}]");
+            let (size, scale) = match (&descr.shape, &reference){
+                (OutputShapeV0_4::Fixed(shape), _) => {
+                    let Some(size) = NonZeroUsize::new(shape[axis_idx]) else {
+                        return Err(unconvertible(shape_path, "Axis sizes must be greater than 0"))
+                    };
+                    (AnyAxisSize::Fixed(size), AxisScale::default())
+                },
+                (OutputShapeV0_4::Implicit(_), Some((shape, reference, reference_id))) => {
+                    let Some(scale) = shape.scale[axis_idx] else {
+                        return Err(unconvertible(shape_path, "Axes that are not present in the reference tensor are not supported"))
+                    };
+                    let offset = shape.offset[axis_idx];
+                    let reference_letter = reference.axes.chars().nth(axis_idx).unwrap();
+                    if letter == 'c'{
+                        let fixed_reference_channels = match &reference.shape{
+                            InputShapeV0_4::Fixed(reference_shape) => Some(reference_shape[axis_idx]),
+                            InputShapeV0_4::Parameterized(reference_shape) => {
+                                (reference_shape.step[axis_idx] == 0).then(|| reference_shape.min[axis_idx])
+                            },
+                        };
+                        let num_channels = fixed_reference_channels.map(|size| size as f32 * scale + 2.0 * offset)
+                            .filter(|num_channels| num_channels.fract() == 0.0 && *num_channels >= 1.0);
+                        let Some(num_channels) = num_channels else {
+                            return Err(unconvertible(shape_path, "Channel axes must have a fixed size"))
+                        };
+                        (AnyAxisSize::Fixed(NonZeroUsize::new(num_channels as usize).unwrap()), AxisScale::default())
+                    } else if letter == 'b'{
+                        (AnyAxisSize::Fixed(NonZeroUsize::new(1).unwrap()), AxisScale::default())
+                    } else {
+                        self.referenced_axis_size(&shape_path, reference_id, reference_letter, scale, offset)?
+                    }
+                },
+                (OutputShapeV0_4::Implicit(_), None) => unreachable!(),
+            };
+
+            let halo_path = format!("{path}.halo[{axis_idx}]");
+            let halo = match Halo::try_from(halos[axis_idx] as u64){
+                Ok(halo) if matches!(letter, 't' | 'x' | 'y' | 'z') => Some(halo),
+                Ok(_) => {
+                    self.note(&halo_path, format!("Only space and time axes can have a halo, so the halo of axis '{letter}' was dropped"));
+                    None
+                },
+                Err(_) => None,
+            };
+            let spacetime_size = match (halo, FixedOrRefAxisSize::try_from(size.clone())){
+                (None, _) => OutputSpacetimeSize::from(size.clone()),
+                (Some(halo), Ok(size)) => OutputSpacetimeSize::Haloed(HaloedOutputSpacetimeSize{size, halo}),
+                (Some(_), Err(_)) => {
+                    self.note(&halo_path, "Axes with a parameterized size can't have a halo, so it was dropped");
+                    OutputSpacetimeSize::from(size.clone())
+                },
+            };
+
+            let axis = match letter{
+                'b' => OutputAxis::Batch(BatchAxis::default()),
+                'c' => {
+                    let AnyAxisSize::Fixed(num_channels) = size else {
+                        return Err(unconvertible(shape_path, "Channel axes must have a fixed size"))
+                    };
+                    OutputAxis::Channel(ChannelAxis{id: LitStr::new(), description: Default::default(), channel_names: channel_names(num_channels)})
+                },
+                'i' => {
+                    if f32::from(scale) != 1.0{
+                        return Err(unconvertible(shape_path, "Index axes can't be scaled relative to their reference"))
+                    }
+                    OutputAxis::Index(IndexAxis{id: LitStr::new(), description: Default::default(), size})
+                },
+                't' => OutputAxis::Time(TimeOutputAxis{
+                    id: axis_id(path, letter)?, description: Default::default(), unit: None, scale, size: spacetime_size,
+                }),
+                _ => OutputAxis::Space(SpaceOutputAxis{
+                    id: axis_id(&format!("{path}.axes"), letter)?, description: Default::default(), unit: None, scale, size: spacetime_size,
+                }),
+            };
+            axes.push(axis);
+        }
+
+        let mut postprocessing = Vec::with_capacity(descr.postprocessing.len());
+        for (step_idx, step) in descr.postprocessing.into_iter().enumerate(){
+            let step_path = format!("{path}.postprocessing[{step_idx}]");
+            postprocessing.push(self.upgrade_processing(&step_path, step, &descr.axes)?);
+        }
+
+        let message = OutputTensorMetadataMsg{
+            description: self.text(&format!("{path}.description"), &descr.description),
+            id,
+            postprocessing,
+            axes: axes.try_into().map_err(|e| unconvertible(format!("{path}.axes"), e))?,
+            data: Some(data_descr(descr.data_type, descr.data_range)),
+        };
+        Ok(OutputTensorDescr{
+            metadata: OutputTensorMetadata::try_from(message).map_err(|e| unconvertible(path, e))?,
+            test_tensor: FileDescription { source: test_tensor, sha256: None },
+            sample_tensor: sample_tensor.map(|source| FileDescription { source, sha256: None }),
+        })
+    }
+
+    fn weights_base(&mut self, path: &str, base: WeightsDescrBaseV0_4) -> (modelrdf::WeightsDescrBase, Option<String>){
+        if base.attachments.is_some(){
+            self.note(format!("{path}.attachments"), "Weights can no longer have attachments, so they were dropped");
+        }
+        let converted = modelrdf::WeightsDescrBase{
+            source: base.source,
+            sha256: base.sha256,
+            authors: base.authors,
+            parent: base.parent,
+            comment: None,
+        };
+        (converted, base.dependencies)
+    }
+
+    /// Converts a `conda:environment.yaml` style dependency into an environment file description
+    fn conda_env(
+        &mut self, path: &str, dependencies: Option<String>,
+    ) -> Result<Option<FileDescription<EnvironmentFile>>, ModelUpgradeError>{
+        let Some(dependencies) = dependencies else {
+            return Ok(None)
+        };
+        let deps_path = format!("{path}.dependencies");
+        let Some(env_file) = dependencies.strip_prefix("conda:") else {
+            self.note(deps_path, format!("Only conda environment files are supported, so '{dependencies}' was dropped"));
+            return Ok(None)
+        };
+        let source = FileReference::try_from(env_file.to_owned())
+            .and_then(EnvironmentFile::try_from)
+            .map_err(|e| unconvertible(&deps_path, e))?;
+        Ok(Some(FileDescription{source, sha256: None}))
+    }
+
+    fn drop_dependencies(&mut self, path: &str, dependencies: Option<String>){
+        if let Some(dependencies) = dependencies{
+            self.note(
+                format!("{path}.dependencies"),
+                format!("This weights format can no longer declare dependencies, so '{dependencies}' was dropped"),
+            );
+        }
+    }
+
+    fn version_or(&mut self, path: &str, version: Option<Version>, default: (u32, u32, u32)) -> Version{
+        if let Some(version) = version{
+            return version
+        }
+        let (major, minor, patch) = default;
+        let version = Version::major_minor_patch(major, minor, patch);
+        self.note(path, format!("Missing, assumed to be {version}"));
+        version
+    }
+
+    fn upgrade_weights(&mut self, weights: WeightsDescrV0_4) -> Result<WeightsDescr, ModelUpgradeError>{
+        let keras_hdf5 = weights.keras_hdf5.map(|weights| {
+            let path = "weights.keras_hdf5";
+            let (base, dependencies) = self.weights_base(path, weights.base);
+            self.drop_dependencies(path, dependencies);
+            modelrdf::KerasHdf5WeightsDescr{
+                base,
+                tensorflow_version: self.version_or(&format!("{path}.tensorflow_version"), weights.tensorflow_version, (1, 15, 0)),
+            }
+        });
+        let onnx = match weights.onnx{
+            None => None,
+            Some(weights) => {
+                let path = "weights.onnx";
+                let (base, dependencies) = self.weights_base(path, weights.base);
+                self.drop_dependencies(path, dependencies);
+                let opset_path = format!("{path}.opset_version");
+                let opset_version = match weights.opset_version{
+                    Some(opset_version) => opset_version,
+                    None => {
+                        self.note(&opset_path, "Missing, assumed to be 15");
+                        15
+                    },
+                };
+                Some(modelrdf::OnnxWeightsDescr{
+                    base,
+                    opset_version: opset_version.try_into().map_err(|e| unconvertible(opset_path, e))?,
+                })
+            },
+        };
+        let pytorch_state_dict = match weights.pytorch_state_dict{
+            None => None,
+            Some(weights) => {
+                let path = "weights.pytorch_state_dict";
+                let arch_path = format!("{path}.architecture");
+                let (base, dependencies) = self.weights_base(path, weights.base);
+                let parse_callable = |callable: &str| Identifier::try_from(callable).map_err(|e| unconvertible(&arch_path, e));
+                let architecture = match weights.architecture.rsplit_once(':'){
+                    Some((file, callable)) => modelrdf::PytorchArchitectureDescr::FromFileDescr(modelrdf::weights::PyTorchArchitectureFromFileDescr{
+                        file_descr: FileDescription{
+                            source: FileReference::try_from(file.to_owned()).map_err(|e| unconvertible(&arch_path, e))?,
+                            sha256: weights.architecture_sha256,
+                        },
+                        callable: parse_callable(callable)?,
+                        kwargs: weights.kwargs,
+                    }),
+                    None => {
+                        let Some((import_from, callable)) = weights.architecture.rsplit_once('.') else {
+                            return Err(unconvertible(arch_path, "Expected either 'path/to/file.py:Callable' or 'module.Callable'"))
+                        };
+                        modelrdf::PytorchArchitectureDescr::FromLibraryDescr(modelrdf::weights::PyTorchArchitectureFromLibraryDescr{
+                            callable: parse_callable(callable)?,
+                            kwargs: weights.kwargs,
+                            import_from: import_from.to_owned(),
+                        })
+                    },
+                };
+                Some(modelrdf::PytorchStateDictWeightsDescr{
+                    base,
+                    architecture,
+                    pytorch_version: self.version_or(&format!("{path}.pytorch_version"), weights.pytorch_version, (1, 10, 0)),
+                    dependencies: self.conda_env(path, dependencies)?,
+                })
+            },
+        };
+        let tensorflow_js = weights.tensorflow_js.map(|weights| {
+            let path = "weights.tensorflow_js";
+            let (base, dependencies) = self.weights_base(path, weights.base);
+            self.drop_dependencies(path, dependencies);
+            modelrdf::TensorflowJsWeightsDescr{
+                base,
+                tensorflow_version: self.version_or(&format!("{path}.tensorflow_version"), weights.tensorflow_version, (1, 15, 0)),
+            }
+        });
+        let tensorflow_saved_model_bundle = match weights.tensorflow_saved_model_bundle{
+            None => None,
+            Some(weights) => {
+                let path = "weights.tensorflow_saved_model_bundle";
+                let (base, dependencies) = self.weights_base(path, weights.base);
+                Some(modelrdf::TensorflowSavedModelBundleWeightsDescr{
+                    base,
+                    tensorflow_version: self.version_or(&format!("{path}.tensorflow_version"), weights.tensorflow_version, (1, 15, 0)),
+                    dependencies: self.conda_env(path, dependencies)?,
+                })
+            },
+        };
+        let torchscript = weights.torchscript.map(|weights| {
+            let path = "weights.torchscript";
+            let (base, dependencies) = self.weights_base(path, weights.base);
+            self.drop_dependencies(path, dependencies);
+            modelrdf::TorchscriptWeightsDescr{
+                base,
+                pytorch_version: self.version_or(&format!("{path}.pytorch_version"), weights.pytorch_version, (1, 10, 0)),
+            }
+        });
+
+        WeightsDescr::try_from(modelrdf::MaybeSomeWeightsDescr{
+            keras_hdf5, onnx, pytorch_state_dict, tensorflow_js, tensorflow_saved_model_bundle, torchscript,
+        }).map_err(|e| unconvertible("weights", e))
+    }
+
+    fn upgrade_model(&mut self, model: ModelRdfV0_4) -> Result<ModelRdfV0_5, ModelUpgradeError>{
+        if model.test_inputs.len() != model.inputs.len(){
+            return Err(unconvertible("test_inputs", format!("Found {} test inputs for {} inputs", model.test_inputs.len(), model.inputs.len())))
+        }
+        if model.test_outputs.len() != model.outputs.len(){
+            return Err(unconvertible("test_outputs", format!("Found {} test outputs for {} outputs", model.test_outputs.len(), model.outputs.len())))
+        }
+        if model.sample_inputs.len() > model.inputs.len().get(){
+            self.note("sample_inputs", "There are more sample inputs than inputs, so the extra ones were dropped");
+        }
+        if model.sample_outputs.len() > model.outputs.len().get(){
+            self.note("sample_outputs", "There are more sample outputs than outputs, so the extra ones were dropped");
+        }
+
+        let inputs_v0_4: Vec<InputTensorDescrV0_4> = model.inputs.into_inner();
+        let mut inputs = Vec::with_capacity(inputs_v0_4.len());
+        for (idx, (descr, test_tensor)) in inputs_v0_4.iter().cloned().zip(model.test_inputs.into_inner()).enumerate(){
+            let sample_tensor = model.sample_inputs.get(idx).cloned();
+            inputs.push(self.upgrade_input(&format!("inputs[{idx}]"), descr, test_tensor, sample_tensor)?);
+        }
+        let mut outputs = Vec::with_capacity(model.outputs.len().get());
+        for (idx, (descr, test_tensor)) in model.outputs.into_inner().into_iter().zip(model.test_outputs.into_inner()).enumerate(){
+            let sample_tensor = model.sample_outputs.get(idx).cloned();
+            outputs.push(self.upgrade_output(&format!("outputs[{idx}]"), descr, &inputs_v0_4, test_tensor, sample_tensor)?);
+        }
+
+        let attachments = match model.attachments{
+            None => vec![],
+            Some(attachments) => {
+                for key in attachments.extra.keys(){
+                    self.note(format!("attachments.{key}"), "Only file attachments are supported, so this entry was dropped");
+                }
+                attachments.files.into_iter().map(|source| FileDescription{source, sha256: None}).collect()
+            },
+        };
+        let id = model.id.and_then(|id| match ResourceId::try_from(id.as_str()){
+            Ok(resource_id) => Some(resource_id),
+            Err(_) => {
+                self.note("id", format!("'{id}' is not a valid 0.5 id, so it was dropped"));
+                None
+            },
+        });
+        let run_mode = model.run_mode.and_then(|run_mode| {
+            match serde_json::from_value::<RunMode>(serde_json::Value::String(run_mode.name.clone())){
+                Ok(converted) => {
+                    if !run_mode.kwargs.is_empty(){
+                        self.note("run_mode.kwargs", "Run modes can no longer have arguments, so they were dropped");
+                    }
+                    Some(converted)
+                },
+                Err(_) => {
+                    self.note("run_mode", format!("Unknown run mode '{}' was dropped", run_mode.name));
+                    None
+                },
+            }
+        });
+        let training_data = model.training_data.and_then(|training_data| {
+            match serde_json::from_value::<DatasetDescrEnum>(training_data){
+                Ok(training_data) => Some(training_data),
+                Err(e) => {
+                    self.note("training_data", format!("Could not be converted, so it was dropped: {e}"));
+                    None
+                },
+            }
+        });
+        if !model.packaged_by.is_empty(){
+            self.note("packaged_by", "This field no longer exists, so it was dropped");
+        }
+        if model.parent.is_some(){
+            self.note("parent", "This field no longer exists, so it was dropped");
+        }
+
+        Ok(ModelRdfV0_5{
+            format_version: Version_0_5_x::new(),
+            description: self.text("description", &model.description),
+            covers: model.covers,
+            id,
+            attachments,
+            cite: model.cite,
+            config: model.config,
+            git_repo: model.git_repo,
+            icon: model.icon,
+            links: model.links,
+            maintainers: model.maintainers,
+            tags: model.tags,
+            version: model.version,
+            version_comment: None,
+            rdf_type: model.rdf_type,
+            authors: model.authors,
+            documentation: model.documentation,
+            inputs: inputs.try_into().unwrap(),
+            license: model.license,
+            name: model.name.as_str().try_into().map_err(|e| unconvertible("name", e))?,
+            outputs: outputs.try_into().unwrap(),
+            run_mode,
+            timestamp: model.timestamp,
+            training_data,
+            weights: self.upgrade_weights(model.weights)?,
+        })
+    }
+}
+
+#[test]
+fn test_upgrading_0_4_model(){
+    let raw = r#"
+format_version: 0.4.10
+type: model
+name: Legacy UNet 2D
+description: A model from the 0.4 days
+authors: [{name: Jane Doe}]
+cite: [{text: Some paper, doi: 10.1000/xyz123}]
+documentation: README.md
+license: MIT
+timestamp: 2022-03-01T10:00:00
+packaged_by: [{name: Packaging Bot}]
+attachments: {files: [extra.txt], notebook_url: https://example.com}
+test_inputs: [test_input.npy]
+test_outputs: [test_output.npy]
+inputs:
+  - name: raw
+    axes: bcyx
+    data_type: uint8
+    data_range: [0, 255]
+    shape: {min: [1, 2, 64, 64], step: [0, 0, 16, 16]}
+    preprocessing:
+      - name: zero_mean_unit_variance
+        kwargs: {mode: fixed, axes: byx, mean: [1.0, 2.0], std: [3.0, 4.0]}
+      - name: scale_range
+        kwargs: {mode: per_dataset, axes: yx, max_percentile: 99.8}
+outputs:
+  - name: Mask
+    axes: bcyx
+    data_type: float32
+    data_range: [-.inf, .inf]
+    shape: {reference_tensor: raw, scale: [1, 0.5, 2, 2], offset: [0, 0, 4, 4]}
+    halo: [0, 1, 8, 8]
+    postprocessing:
+      - name: sigmoid
+weights:
+  pytorch_state_dict:
+    source: weights.pt
+    architecture: unet.py:UNet2d
+    kwargs: {depth: 4}
+    dependencies: conda:environment.yaml
+  onnx:
+    source: weights.onnx
+"#;
+    let legacy: ModelRdfV0_4 = serde_yaml::from_str(raw).unwrap();
+    let UpgradedModelRdf{model_rdf, lossy_conversions} = legacy.upgrade().unwrap();
+
+    let mut paths: Vec<&str> = lossy_conversions.iter().map(|conv| conv.path.as_str()).collect();
+    paths.sort();
+    assert_eq!(
+        paths,
+        [
+            "attachments.notebook_url",
+            "inputs[0].preprocessing[1].kwargs.mode",
+            "outputs[0].halo[1]",
+            "outputs[0].name",
+            "packaged_by",
+            "weights.onnx.opset_version",
+            "weights.pytorch_state_dict.pytorch_version",
+        ]
+    );
+
+    let input = serde_json::to_value(&model_rdf.inputs[0]).unwrap();
+    assert_eq!(input["axes"][1]["channel_names"], serde_json::json!(["channel0", "channel1"]));
+    assert_eq!(input["axes"][2]["size"], serde_json::json!({"min": 64, "step": 16}));
+    assert_eq!(input["preprocessing"][0]["kwargs"]["axis"], "channel");
+    assert_eq!(input["data"]["range"], serde_json::json!([0.0, 255.0]));
+
+    let output = serde_json::to_value(&model_rdf.outputs[0]).unwrap();
+    assert_eq!(output["id"], "mask");
+    assert_eq!(output["axes"][1]["channel_names"], serde_json::json!(["channel0"]));
+    assert_eq!(output["axes"][3]["size"], serde_json::json!({"tensor_id": "raw", "axis_id": "x", "offset": 8}));
+    assert_eq!(output["axes"][3]["scale"], 0.5);
+    assert_eq!(output["axes"][3]["halo"], 8);
+    assert_eq!(output["data"]["range"], serde_json::json!([null, null]));
+
+    let weights = serde_json::to_value(&model_rdf.weights).unwrap();
+    assert_eq!(weights["pytorch_state_dict"]["architecture"]["source"], "unet.py");
+    assert_eq!(weights["pytorch_state_dict"]["architecture"]["callable"], "UNet2d");
+    assert_eq!(weights["pytorch_state_dict"]["dependencies"]["source"], "environment.yaml");
+
+    // the result must be a valid 0.5 description
+    let reparsed: ModelRdfV0_5 = serde_json::from_value(serde_json::to_value(&model_rdf).unwrap()).unwrap();
+    assert_eq!(reparsed.attachments.len(), 1);
+}
@@ -6,6 +6,7 @@ use crate::rdf::{
 use crate::rdf::{BoundedString, FileDescription};
 use aspartial::AsPartial;
 use super::{WeightsDescr, _now};
+use super::lossy_conversion::{dropped_fields, LossyConversion};
 
 use super::dataset_descr::DatasetDescrEnum;
 use super::{run_mode::RunMode, InputTensorDescr, ModelRdfName, OutputTensorDescr, RdfTypeModel};
@@ -152,3 +153,75 @@ pub struct ModelRdfV0_5 {
     pub weights: WeightsDescr,
 }
 
+
+impl ModelRdfV0_5{
+    /// Parses a 0.5 model description. Descriptions in a patch version newer than
+    /// [Version_0_5_x::latest_supported_version] are accepted too, but the fields introduced after that
+    /// version are dropped, and each of them is reported back
+    pub fn from_yaml_slice(yaml: &[u8]) -> Result<(Self, Vec<LossyConversion>), serde_yaml::Error>{
+        let model_rdf: Self = serde_yaml::from_slice(yaml)?;
+        if !model_rdf.format_version.is_newer_than_supported(){
+            return Ok((model_rdf, vec![]))
+        }
+        let raw: serde_json::Value = serde_yaml::from_slice(yaml)?;
+        let lossy_conversions = model_rdf.newer_version_conversions(&raw);
+        Ok((model_rdf, lossy_conversions))
+    }
+
+    /// What was lost when `raw`, a description in a newer patch version, was parsed into `self`
+    pub(crate) fn newer_version_conversions(&self, raw: &serde_json::Value) -> Vec<LossyConversion>{
+        let latest = Version_0_5_x::latest_supported_version();
+        let mut lossy_conversions = vec![LossyConversion{
+            path: "format_version".to_owned(),
+            message: format!(
+                "Version {} is newer than the latest supported one, so the model is treated as {latest}",
+                self.format_version.version(),
+            ),
+        }];
+        let parsed = serde_json::to_value(self).unwrap();
+        let dropped = dropped_fields(raw, &parsed).into_iter().map(|path| LossyConversion{
+            path,
+            message: format!("Unknown in format version {latest}, so it was dropped"),
+        });
+        lossy_conversions.extend(dropped);
+        lossy_conversions
+    }
+}
+
+#[test]
+fn test_parsing_newer_patch_version(){
+    let raw = |format_version: &str| format!(r#"
+format_version: {format_version}
+type: model
+name: Newer Model
+description: A model from a newer patch release
+authors: [{{name: Jane Doe}}]
+cite: [{{text: Some paper, doi: 10.1000/xyz123}}]
+documentation: README.md
+license: MIT
+inputs:
+  - id: raw
+    axes: [{{type: batch}}, {{type: space, id: x, size: 64}}]
+    test_tensor: {{source: test_input.npy}}
+outputs:
+  - id: mask
+    axes: [{{type: batch}}, {{type: space, id: x, size: 64}}]
+    test_tensor: {{source: test_output.npy}}
+    some_future_field: 42
+weights:
+  onnx: {{source: weights.onnx, opset_version: 15, comment: converted from torchscript}}
+"#);
+
+    let (model_rdf, lossy_conversions) = ModelRdfV0_5::from_yaml_slice(raw("0.5.4").as_bytes()).unwrap();
+    assert!(lossy_conversions.is_empty());
+    assert_eq!(
+        model_rdf.weights.onnx.as_ref().and_then(|onnx| onnx.base.comment.clone()).map(String::from).as_deref(),
+        Some("converted from torchscript"),
+    );
+
+    let (_, lossy_conversions) = ModelRdfV0_5::from_yaml_slice(raw("0.5.9").as_bytes()).unwrap();
+    let paths: Vec<&str> = lossy_conversions.iter().map(|conversion| conversion.path.as_str()).collect();
+    assert_eq!(paths, ["format_version", "outputs[0].some_future_field"]);
+
+    assert!(ModelRdfV0_5::from_yaml_slice(raw("0.6.0").as_bytes()).is_err());
+}
@@ -5,14 +5,17 @@ use serde::{Deserialize, Serialize};
 
 use crate::rdf::{model::{postprocessing::ScaleMeanVarianceDescr, preprocessing::{BinarizeDescr, ScaleLinearDescr, ScaleRangeDescr, Zmuv}, AxisId}, FileDescription};
 
-use super::{axes::output_axes::OutputAxisGroup, postprocessing::PostprocessingDescr, TensorId, TensorTextDescription};
+use super::tensor_data_descr::{AnyTensorDataDescr, TensorDataDescrValidationError};
+use super::{axes::output_axes::{OutputAxis, OutputAxisGroup}, postprocessing::PostprocessingDescr, TensorId, TensorTextDescription};
 
 #[derive(thiserror::Error, Debug)]
 pub enum OutputTensorParsingError{
     #[error("{tensor_id}.postprocessing[{postproc_idx}] references non-existing axis '{reference}'")]
     PostprocessingReferencesNonExistingAxis{tensor_id: TensorId, postproc_idx: usize, reference: AxisId},
     #[error("Found a self-reference from/to {tensor_id}")]
-    SelfReference{tensor_id: TensorId}
+    SelfReference{tensor_id: TensorId},
+    #[error("{tensor_id}.data is invalid: {source}")]
+    BadDataDescription{tensor_id: TensorId, source: TensorDataDescrValidationError},
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, AsPartial)]
@@ -39,11 +42,15 @@ pub struct OutputTensorMetadata{
     pub description: TensorTextDescription,
     axes: OutputAxisGroup,
     postprocessing: Vec<PostprocessingDescr>,
+    /// Description of the tensor's data values, optionally per channel.
+    /// When missing, the spec's default (unbounded float32 values) applies.
+    data: Option<AnyTensorDataDescr>,
 }
 
 impl OutputTensorMetadata{
     pub fn axes(&self) -> &OutputAxisGroup{ &self.axes }
     pub fn postprocessing(&self) -> &Vec<PostprocessingDescr>{ &self.postprocessing }
+    pub fn data(&self) -> Option<&AnyTensorDataDescr>{ self.data.as_ref() }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -55,6 +62,8 @@ pub struct OutputTensorMetadataMsg{
     #[serde(default)]
     pub description: TensorTextDescription,
     pub axes: OutputAxisGroup,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<AnyTensorDataDescr>,
 }
 
 impl TryFrom<OutputTensorMetadataMsg> for OutputTensorMetadata{
@@ -101,11 +110,21 @@ impl TryFrom<OutputTensorMetadataMsg> for OutputTensorMetadata{
                 _ => (),
             }
         }
+        if let Some(data) = &message.data{
+            let num_channels = message.axes.iter().find_map(|axis| match axis{
+                OutputAxis::Channel(channel_axis) => Some(channel_axis.channel_names.len().get()),
+                _ => None,
+            });
+            data.validate(num_channels).map_err(|source| OutputTensorParsingError::BadDataDescription {
+                tensor_id: message.id.clone(), source
+            })?;
+        }
         Ok(Self{
             id: message.id,
             postprocessing: message.postprocessing,
             description: message.description,
             axes: message.axes,
+            data: message.data,
         })
     }
 }
@@ -117,6 +136,7 @@ impl From<OutputTensorMetadata> for OutputTensorMetadataMsg{
             description: value.description,
             postprocessing: value.postprocessing,
             axes: value.axes,
+            data: value.data,
         }
     }
 }
@@ -1,3 +1,4 @@
+use aspartial::AsPartial;
 use serde::{Deserialize, Serialize};
 
 use crate::rdf::{literal::StrMarker, non_empty_list::NonEmptyList, si_units::SiUnit, LitStr};
@@ -11,7 +12,85 @@ pub enum TensorDataDescr {
     IntervalOrRatio(IntervalOrRatioDataDescr),
 }
 
+impl TensorDataDescr {
+    pub fn data_type(&self) -> DataType {
+        match self {
+            Self::NominalOrOrdinal(descr) => descr.data_type,
+            Self::IntervalOrRatio(descr) => descr.data_type,
+        }
+    }
+}
+
+impl AsPartial for TensorDataDescr {
+    type Partial = Self;
+    fn to_partial(self) -> Self::Partial {
+        self
+    }
+}
+
+/// The `data` field of a tensor description: either a single description for the whole tensor
+/// or one description per channel
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum AnyTensorDataDescr {
+    Single(TensorDataDescr),
+    PerChannel(NonEmptyList<TensorDataDescr>),
+}
+
+impl AnyTensorDataDescr {
+    /// The data type of the tensor, or `None` if the per-channel descriptions disagree on it
+    pub fn data_type(&self) -> Option<DataType> {
+        match self {
+            Self::Single(descr) => Some(descr.data_type()),
+            Self::PerChannel(descrs) => {
+                let data_type = descrs[0].data_type();
+                descrs.iter().all(|descr| descr.data_type() == data_type).then_some(data_type)
+            }
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum TensorDataDescrValidationError {
+    #[error("Per-channel data descriptions must all have the same data type")]
+    MismatchedDataTypes,
+    #[error("Found {num_descrs} per-channel data descriptions but the tensor has {num_channels} channels")]
+    MismatchedNumChannels { num_descrs: usize, num_channels: usize },
+}
+
+impl AnyTensorDataDescr {
+    /// Checks that per-channel descriptions agree on their data type and match `num_channels`,
+    /// the number of channels of the tensor (or `None` if it has no channel axis)
+    pub fn validate(&self, num_channels: Option<usize>) -> Result<(), TensorDataDescrValidationError> {
+        let Self::PerChannel(descrs) = self else {
+            return Ok(())
+        };
+        if self.data_type().is_none() {
+            return Err(TensorDataDescrValidationError::MismatchedDataTypes)
+        }
+        let num_channels = num_channels.unwrap_or(1);
+        if descrs.len().get() != num_channels {
+            return Err(TensorDataDescrValidationError::MismatchedNumChannels { num_descrs: descrs.len().get(), num_channels })
+        }
+        Ok(())
+    }
+}
+
+impl From<TensorDataDescr> for AnyTensorDataDescr {
+    fn from(value: TensorDataDescr) -> Self {
+        Self::Single(value)
+    }
+}
+
+impl AsPartial for AnyTensorDataDescr {
+    type Partial = Self;
+    fn to_partial(self) -> Self::Partial {
+        self
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
 pub enum TVs {
     Ints(NonEmptyList<i64>),
     Floats(NonEmptyList<f32>),
@@ -33,6 +112,12 @@ pub enum TensorDataUnit {
     Si(SiUnit),
 }
 
+impl Default for TensorDataUnit {
+    fn default() -> Self {
+        Self::ArbitraryUnit(LitStr::new())
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct NominalOrOrdinalDataDescr {
     /// A fixed set of nominal or an ascending sequence of ordinal values.
@@ -55,13 +140,30 @@ fn _default_data_type() -> DataType {
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct IntervalOrRatioDataDescr {
     #[serde(rename = "type")]
-    data_type: DataType,
-    range: (Option<f32>, Option<f32>),
-    unit: TensorDataUnit,
+    #[serde(default)]
+    pub data_type: DataType,
+    /// Tuple `(minimum, maximum)` specifying the allowed range of the data in this tensor.
+    /// `None` means the range is not limited in that direction.
+    #[serde(default)]
+    pub range: (Option<f32>, Option<f32>),
+    #[serde(default)]
+    pub unit: TensorDataUnit,
     #[serde(default = "_default_scale")]
-    scale: f32,
+    pub scale: f32,
     #[serde(default)]
-    offset: Option<f32>,
+    pub offset: Option<f32>,
+}
+
+impl Default for IntervalOrRatioDataDescr {
+    fn default() -> Self {
+        Self {
+            data_type: DataType::Float32,
+            range: (None, None),
+            unit: TensorDataUnit::default(),
+            scale: _default_scale(),
+            offset: None,
+        }
+    }
 }
 
 fn _default_scale() -> f32 {
@@ -19,6 +19,13 @@ impl Display for Version_0_4_X_OrEarlier{
     }
 }
 
+impl Version_0_4_X_OrEarlier{
+    /// Whether models in this version can be upgraded via [ModelRdfV0_4](super::ModelRdfV0_4)
+    pub fn is_upgradable(&self) -> bool{
+        self.0 >= Version::version_0_4_0()
+    }
+}
+
 impl TryFrom<Version> for Version_0_4_X_OrEarlier{
     type Error = LegacyVersionParsingError;
     fn try_from(value: Version) -> Result<Self, Self::Error> {
@@ -48,18 +55,18 @@ pub enum FutureVersionParsingError{
 #[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
 #[allow(non_camel_case_types)]
 #[serde(try_from = "Version")]
-pub struct Version_0_5_4_OrLater(Version);
+pub struct Version_0_6_X_OrLater(Version);
 
-impl Display for Version_0_5_4_OrLater{
+impl Display for Version_0_6_X_OrLater{
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         self.0.fmt(f)
     }
 }
 
-impl TryFrom<Version> for Version_0_5_4_OrLater{
+impl TryFrom<Version> for Version_0_6_X_OrLater{
     type Error = FutureVersionParsingError;
     fn try_from(value: Version) -> Result<Self, Self::Error> {
-        if value > Version::version_0_5_3() {
+        if value >= Version::version_0_6_0() {
             return Ok(Self(value))
         }
         return Err(FutureVersionParsingError::VersionTooLow { found: value })
@@ -71,6 +78,6 @@ pub struct UnsupportedFutureModel{
     /// Version of the bioimage.io model description specification used.
     /// When creating a new model always use the latest micro/patch version described here.
     /// The `format_version` is important for any consumer software to understand how to parse the fields.
-    pub format_version: Version_0_5_4_OrLater,
+    pub format_version: Version_0_6_X_OrLater,
 }
 
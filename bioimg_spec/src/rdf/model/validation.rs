@@ -0,0 +1,710 @@
+use std::fmt::Display;
+
+use serde::de::{self, DeserializeOwned, IntoDeserializer, Visitor};
+use serde::de::value::{MapAccessDeserializer, MapDeserializer, SeqDeserializer};
+use serde::forward_to_deserialize_any;
+use serde_json::{Map, Value};
+use serde_path_to_error::Segment;
+
+use crate::rdf::{
+    cite_entry::{CiteEntry2, CiteEntry2Msg},
+    version::Version_0_5_x,
+    Author2, FileDescription, Maintainer, Version,
+};
+use super::{
+    axes::{input_axes::{InputAxisGroup, SpaceInputAxis, TimeInputAxis}, output_axes::{OutputAxisGroup, SpaceOutputAxis, TimeOutputAxis}},
+    axis_size::{AxisSizeReference, FixedAxisSize, FixedOrRefAxisSize, ParameterizedAxisSize, QualifiedAxisId},
+    input_tensor::InputTensorMetadataMsg,
+    output_tensor::OutputTensorMetadataMsg,
+    postprocessing::PostprocessingDescr,
+    AnyAxisSize, AxisType, BatchAxis, ChannelAxis, Halo, IndexAxis, InputTensorDescr, KerasHdf5WeightsDescr,
+    MaybeSomeWeightsDescr, ModelRdfV0_4, ModelRdfV0_5, OnnxWeightsDescr, OutputTensorDescr, PreprocessingDescr,
+    PytorchStateDictWeightsDescr, TensorflowJsWeightsDescr, TensorflowSavedModelBundleWeightsDescr,
+    TorchscriptWeightsDescr, WeightsDescr, WeightsDescrBase,
+};
+use super::model_rdf_0_4::ModelUpgradeError;
+
+#[derive(serde::Serialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity{
+    Warning,
+    Error,
+}
+
+impl Display for Severity{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self{
+            Self::Warning => write!(f, "warning"),
+            Self::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// A single problem found in a model description.
+///
+/// `path` points at the offending field in the rdf.yaml, e.g. `inputs[0].axes[2].size.min`.
+/// An empty path refers to the description as a whole
+#[derive(serde::Serialize, Clone, Debug, PartialEq)]
+pub struct ValidationIssue{
+    pub path: String,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl ValidationIssue{
+    pub fn error(path: impl Into<String>, message: impl Display) -> Self{
+        Self{path: path.into(), severity: Severity::Error, message: message.to_string()}
+    }
+    pub fn warning(path: impl Into<String>, message: impl Display) -> Self{
+        Self{path: path.into(), severity: Severity::Warning, message: message.to_string()}
+    }
+}
+
+impl Display for ValidationIssue{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.path.is_empty(){
+            write!(f, "{}: {}", self.severity, self.message)
+        } else {
+            write!(f, "{} at {}: {}", self.severity, self.path, self.message)
+        }
+    }
+}
+
+/// Every problem found in a model description, as opposed to the first one that parsing stops at.
+///
+/// Fields are checked one by one, so a bad field doesn't hide problems in the fields after it. Checks that
+/// span several fields (e.g. preprocessing referencing an axis of its tensor) only run once those fields
+/// are valid on their own
+#[derive(serde::Serialize, Clone, Debug, Default, PartialEq)]
+pub struct ValidationReport{
+    pub issues: Vec<ValidationIssue>,
+}
+
+impl ValidationReport{
+    pub fn validate_yaml(yaml: &[u8]) -> Self{
+        match serde_yaml::from_slice::<Value>(yaml){
+            Ok(raw) => Self::validate_value(&raw),
+            Err(err) => Self{issues: vec![ValidationIssue::error("", format!("Not valid yaml: {err}"))]},
+        }
+    }
+
+    /// Validates a model description in any supported format version. It works on the raw fields, so it can
+    /// also look into descriptions that would only load as a partial model. Legacy 0.4 models are validated
+    /// by upgrading them, so their problems are only reported one at a time
+    pub fn validate_value(raw: &Value) -> Self{
+        let mut validator = Validator::default();
+        validator.model(raw);
+        Self{issues: validator.issues}
+    }
+
+    pub fn push(&mut self, issue: ValidationIssue){
+        self.issues.push(issue)
+    }
+
+    /// Whether no errors were found. Warnings don't make a description invalid
+    pub fn is_valid(&self) -> bool{
+        self.errors().next().is_none()
+    }
+
+    pub fn errors(&self) -> impl Iterator<Item=&ValidationIssue>{
+        self.issues.iter().filter(|issue| issue.severity == Severity::Error)
+    }
+
+    pub fn warnings(&self) -> impl Iterator<Item=&ValidationIssue>{
+        self.issues.iter().filter(|issue| issue.severity == Severity::Warning)
+    }
+
+    pub fn to_json(&self) -> Value{
+        serde_json::to_value(self).unwrap()
+    }
+}
+
+impl Display for ValidationReport{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.issues.is_empty(){
+            return write!(f, "No issues found")
+        }
+        for issue in &self.issues{
+            writeln!(f, "{issue}")?;
+        }
+        Ok(())
+    }
+}
+
+fn join(path: &str, key: &str) -> String{
+    if path.is_empty(){
+        key.to_owned()
+    } else {
+        format!("{path}.{key}")
+    }
+}
+
+/// `path` extended with the location of a deserialization error inside the value at `path`
+fn join_error_path(path: &str, error_path: &serde_path_to_error::Path) -> String{
+    let mut out = path.to_owned();
+    for segment in error_path.iter(){
+        match segment{
+            Segment::Seq { index } => out += &format!("[{index}]"),
+            Segment::Map { key } => out = join(&out, key),
+            Segment::Enum { variant } => out = join(&out, variant),
+            Segment::Unknown => out = join(&out, "?"),
+        }
+    }
+    out
+}
+
+/// The value of `key` in `object`, treating `null` like a missing field
+fn get<'v>(object: &'v Map<String, Value>, key: &str) -> Option<&'v Value>{
+    object.get(key).filter(|value| !value.is_null())
+}
+
+/// What went wrong deserializing a field. Unlike with `serde_json::Error`, missing fields
+/// can be told apart from other problems without looking at the error message
+#[derive(Debug)]
+enum FieldError{
+    Missing(&'static str),
+    Invalid(String),
+}
+
+impl Display for FieldError{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self{
+            Self::Missing(field) => write!(f, "missing field `{field}`"),
+            Self::Invalid(message) => write!(f, "{message}"),
+        }
+    }
+}
+
+impl std::error::Error for FieldError{}
+
+impl de::Error for FieldError{
+    fn custom<T: Display>(msg: T) -> Self {
+        Self::Invalid(msg.to_string())
+    }
+    fn missing_field(field: &'static str) -> Self {
+        Self::Missing(field)
+    }
+}
+
+/// Deserializes a borrowed json value like `serde_json::Value` itself would, but failing with a [FieldError]
+#[derive(Clone, Copy)]
+struct RawValue<'v>(&'v Value);
+
+impl<'de> IntoDeserializer<'de, FieldError> for RawValue<'de>{
+    type Deserializer = Self;
+    fn into_deserializer(self) -> Self::Deserializer {
+        self
+    }
+}
+
+impl<'de> RawValue<'de>{
+    fn entries(object: &'de Map<String, Value>) -> MapDeserializer<'de, impl Iterator<Item=(&'de str, Self)>, FieldError>{
+        MapDeserializer::new(object.iter().map(|(key, value)| (key.as_str(), RawValue(value))))
+    }
+}
+
+impl<'de> de::Deserializer<'de> for RawValue<'de>{
+    type Error = FieldError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.0{
+            Value::Null => visitor.visit_unit(),
+            Value::Bool(value) => visitor.visit_bool(*value),
+            Value::Number(number) => match (number.as_u64(), number.as_i64()){
+                (Some(value), _) => visitor.visit_u64(value),
+                (None, Some(value)) => visitor.visit_i64(value),
+                (None, None) => visitor.visit_f64(number.as_f64().unwrap_or(f64::NAN)),
+            },
+            Value::String(value) => visitor.visit_borrowed_str(value),
+            Value::Array(items) => {
+                let mut seq = SeqDeserializer::new(items.iter().map(RawValue));
+                let value = visitor.visit_seq(&mut seq)?;
+                seq.end()?;
+                Ok(value)
+            },
+            Value::Object(object) => {
+                let mut map = Self::entries(object);
+                let value = visitor.visit_map(&mut map)?;
+                map.end()?;
+                Ok(value)
+            },
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.0{
+            Value::Null => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self, _name: &'static str, _variants: &'static [&'static str], visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        match self.0{
+            Value::String(variant) => visitor.visit_enum(variant.as_str().into_deserializer()),
+            Value::Object(object) if object.len() == 1 => visitor.visit_enum(MapAccessDeserializer::new(Self::entries(object))),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+#[derive(Default)]
+struct Validator{
+    issues: Vec<ValidationIssue>,
+}
+
+impl Validator{
+    fn error(&mut self, path: impl Into<String>, message: impl Display){
+        self.issues.push(ValidationIssue::error(path, message))
+    }
+
+    fn warning(&mut self, path: impl Into<String>, message: impl Display){
+        self.issues.push(ValidationIssue::warning(path, message))
+    }
+
+    fn num_errors(&self) -> usize{
+        self.issues.iter().filter(|issue| issue.severity == Severity::Error).count()
+    }
+
+    /// Checks `value` as a whole, reporting only the first problem in it
+    fn check<T: DeserializeOwned>(&mut self, path: &str, value: &Value) -> Option<T>{
+        match serde_path_to_error::deserialize::<_, T>(value){
+            Ok(parsed) => Some(parsed),
+            Err(err) => {
+                self.error(join_error_path(path, err.path()), err.inner());
+                None
+            }
+        }
+    }
+
+    fn field_error(&mut self, location: String, err: FieldError){
+        match err{
+            FieldError::Missing(field) => self.error(join(&location, field), "Missing required field"),
+            FieldError::Invalid(message) => self.error(location, message),
+        }
+    }
+
+    /// Checks the fields of the object `value` against `T`, reporting every bad field rather than only the first one.
+    ///
+    /// Problems inside the fields in `nested` are left to the caller, which walks them separately. Of the
+    /// required fields that `T` finds missing, only the first one is reported, and none if that one was
+    /// only missing because it had been left out for being bad
+    fn fields<T: DeserializeOwned>(&mut self, path: &str, value: &Value, nested: &[&str]){
+        let Value::Object(object) = value else {
+            self.check::<T>(path, value);
+            return
+        };
+        let mut rest = object.clone();
+        let mut bad_fields: Vec<String> = vec![];
+        loop {
+            let remaining = Value::Object(rest.clone());
+            let Err(err) = serde_path_to_error::deserialize::<_, T>(RawValue(&remaining)) else {
+                return
+            };
+            let location = join_error_path(path, err.path());
+            let bad_field = match err.path().iter().next(){
+                Some(Segment::Map { key }) => rest.remove(key).map(|_| key.clone()),
+                _ => None,
+            };
+            if let Some(bad_field) = bad_field{
+                if !nested.contains(&bad_field.as_str()){
+                    self.field_error(location, err.into_inner());
+                }
+                bad_fields.push(bad_field);
+                continue
+            }
+            match err.into_inner(){
+                FieldError::Missing(field) => if !bad_fields.iter().any(|bad_field| bad_field == field){
+                    self.field_error(location, FieldError::Missing(field))
+                },
+                // checks across fields only make sense when no field had to be left out
+                FieldError::Invalid(message) => if bad_fields.is_empty(){
+                    self.error(location, message)
+                },
+            }
+            return
+        }
+    }
+
+    /// Walks every item of the list in `object[key]`
+    fn items(
+        &mut self,
+        path: &str,
+        object: &Map<String, Value>,
+        key: &str,
+        non_empty: bool,
+        mut walk_item: impl FnMut(&mut Self, &str, &Value),
+    ){
+        let path = join(path, key);
+        let items = match get(object, key){
+            None => return,
+            Some(Value::Array(items)) => items,
+            Some(_) => return self.error(path, "Expected a list"),
+        };
+        if non_empty && items.is_empty(){
+            self.error(&path, "List must not be empty");
+        }
+        for (idx, item) in items.iter().enumerate(){
+            walk_item(self, &format!("{path}[{idx}]"), item);
+        }
+    }
+
+    fn model(&mut self, raw: &Value){
+        let Value::Object(object) = raw else {
+            return self.error("", "Model description must be a mapping of fields")
+        };
+        let Some(raw_version) = get(object, "format_version") else {
+            return self.error("format_version", "Missing required field")
+        };
+        let Some(version) = self.check::<Version>("format_version", raw_version) else {
+            return
+        };
+        if version < Version_0_5_x::earliest_supported_version(){
+            return self.legacy_model(raw, version)
+        }
+        let Some(format_version) = self.check::<Version_0_5_x>("format_version", raw_version) else {
+            return
+        };
+
+        let num_errors = self.num_errors();
+        self.fields::<ModelRdfV0_5>("", raw, &["authors", "cite", "attachments", "maintainers", "inputs", "outputs", "weights"]);
+        self.items("", object, "authors", true, |v, path, author| v.fields::<Author2>(path, author, &[]));
+        self.items("", object, "cite", true, |v, path, cite_entry| {
+            let num_errors = v.num_errors();
+            v.fields::<CiteEntry2Msg>(path, cite_entry, &[]);
+            if v.num_errors() == num_errors{
+                v.check::<CiteEntry2>(path, cite_entry);
+            }
+        });
+        self.items("", object, "attachments", false, |v, path, attachment| v.fields::<FileDescription>(path, attachment, &[]));
+        self.items("", object, "maintainers", false, |v, path, maintainer| v.fields::<Maintainer>(path, maintainer, &[]));
+        self.items("", object, "inputs", true, Self::input_tensor);
+        self.items("", object, "outputs", true, Self::output_tensor);
+        if let Some(weights) = get(object, "weights"){
+            self.weights("weights", weights);
+        }
+        if self.num_errors() != num_errors{
+            return
+        }
+        let Some(model_rdf) = self.check::<ModelRdfV0_5>("", raw) else {
+            return
+        };
+        if format_version.is_newer_than_supported(){
+            for conversion in model_rdf.newer_version_conversions(raw){
+                self.warning(conversion.path, conversion.message);
+            }
+        }
+    }
+
+    fn legacy_model(&mut self, raw: &Value, version: Version){
+        if version < Version::version_0_4_0(){
+            return self.error(
+                "format_version",
+                format!("Version {version} is not supported. The earliest supported version is {}", Version::version_0_4_0()),
+            )
+        }
+        let Some(legacy_model) = self.check::<ModelRdfV0_4>("", raw) else {
+            return
+        };
+        match legacy_model.upgrade(){
+            Ok(upgraded) => {
+                self.warning(
+                    "format_version",
+                    format!("Legacy version {version} was validated by upgrading it to {}", Version_0_5_x::latest_supported_version()),
+                );
+                for conversion in upgraded.lossy_conversions{
+                    self.warning(conversion.path, conversion.message);
+                }
+            },
+            Err(ModelUpgradeError::Unconvertible { path, reason }) => self.error(path, reason),
+        }
+    }
+
+    fn input_tensor(&mut self, path: &str, tensor: &Value){
+        let Value::Object(object) = tensor else {
+            return self.error(path, "Expected a tensor description")
+        };
+        let num_errors = self.num_errors();
+        self.fields::<InputTensorMetadataMsg>(path, tensor, &["axes", "preprocessing"]);
+        self.items(path, object, "axes", true, |v, path, axis| v.input_axis(path, axis));
+        self.items(path, object, "preprocessing", false, |v, path, step| { v.check::<PreprocessingDescr>(path, step); });
+        self.test_and_sample_tensors(path, object);
+        if self.num_errors() != num_errors{
+            return
+        }
+        if let Some(axes) = get(object, "axes"){
+            self.check::<InputAxisGroup>(&join(path, "axes"), axes);
+        }
+        if self.num_errors() == num_errors{
+            self.check::<InputTensorDescr>(path, tensor);
+        }
+    }
+
+    fn output_tensor(&mut self, path: &str, tensor: &Value){
+        let Value::Object(object) = tensor else {
+            return self.error(path, "Expected a tensor description")
+        };
+        let num_errors = self.num_errors();
+        self.fields::<OutputTensorMetadataMsg>(path, tensor, &["axes", "postprocessing"]);
+        self.items(path, object, "axes", true, |v, path, axis| v.output_axis(path, axis));
+        self.items(path, object, "postprocessing", false, |v, path, step| { v.check::<PostprocessingDescr>(path, step); });
+        self.test_and_sample_tensors(path, object);
+        if self.num_errors() != num_errors{
+            return
+        }
+        if let Some(axes) = get(object, "axes"){
+            self.check::<OutputAxisGroup>(&join(path, "axes"), axes);
+        }
+        if self.num_errors() == num_errors{
+            self.check::<OutputTensorDescr>(path, tensor);
+        }
+    }
+
+    fn test_and_sample_tensors(&mut self, path: &str, object: &Map<String, Value>){
+        match get(object, "test_tensor"){
+            Some(test_tensor) => self.fields::<FileDescription>(&join(path, "test_tensor"), test_tensor, &[]),
+            None => self.error(join(path, "test_tensor"), "Missing required field"),
+        }
+        if let Some(sample_tensor) = get(object, "sample_tensor"){
+            self.fields::<FileDescription>(&join(path, "sample_tensor"), sample_tensor, &[]);
+        }
+    }
+
+    /// The type of the axis at `path`, which decides how the rest of its fields are checked
+    fn axis_type(&mut self, path: &str, axis: &Value) -> Option<AxisType>{
+        let Value::Object(object) = axis else {
+            self.error(path, "Expected an axis description");
+            return None
+        };
+        let Some(axis_type) = get(object, "type") else {
+            self.error(join(path, "type"), "Missing required field");
+            return None
+        };
+        self.check::<AxisType>(&join(path, "type"), axis_type)
+    }
+
+    fn input_axis(&mut self, path: &str, axis: &Value){
+        match self.axis_type(path, axis){
+            None => (),
+            Some(AxisType::Batch) => self.fields::<BatchAxis>(path, axis, &[]),
+            Some(AxisType::Channel) => self.fields::<ChannelAxis>(path, axis, &[]),
+            Some(AxisType::Index) => self.sized_axis::<IndexAxis>(path, axis),
+            Some(AxisType::Time) => self.sized_axis::<TimeInputAxis>(path, axis),
+            Some(AxisType::Space) => self.sized_axis::<SpaceInputAxis>(path, axis),
+        }
+    }
+
+    fn output_axis(&mut self, path: &str, axis: &Value){
+        match self.axis_type(path, axis){
+            None => (),
+            Some(AxisType::Batch) => self.fields::<BatchAxis>(path, axis, &[]),
+            Some(AxisType::Channel) => self.fields::<ChannelAxis>(path, axis, &[]),
+            Some(AxisType::Index) => self.sized_axis::<IndexAxis>(path, axis),
+            Some(AxisType::Time) => self.haloed_axis::<TimeOutputAxis>(path, axis),
+            Some(AxisType::Space) => self.haloed_axis::<SpaceOutputAxis>(path, axis),
+        }
+    }
+
+    fn sized_axis<T: DeserializeOwned>(&mut self, path: &str, axis: &Value){
+        self.fields::<T>(path, axis, &["size"]);
+        if let Some(size) = axis.get("size").filter(|size| !size.is_null()){
+            self.axis_size(&join(path, "size"), size);
+        }
+    }
+
+    fn haloed_axis<T: DeserializeOwned>(&mut self, path: &str, axis: &Value){
+        self.fields::<T>(path, axis, &["size", "halo"]);
+        let Some(size) = axis.get("size").filter(|size| !size.is_null()) else {
+            return
+        };
+        let num_errors = self.num_errors();
+        let size_path = join(path, "size");
+        self.axis_size(&size_path, size);
+        if let Some(halo) = axis.get("halo").filter(|halo| !halo.is_null()){
+            self.check::<Halo>(&join(path, "halo"), halo);
+            if self.num_errors() == num_errors{
+                self.check::<FixedOrRefAxisSize>(&size_path, size);
+            }
+        }
+    }
+
+    fn axis_size(&mut self, path: &str, size: &Value){
+        match size{
+            Value::Number(_) => { self.check::<FixedAxisSize>(path, size); },
+            Value::Object(object) if object.contains_key("min") || object.contains_key("step") => {
+                self.fields::<ParameterizedAxisSize>(path, size, &[])
+            },
+            Value::Object(_) => {
+                let num_errors = self.num_errors();
+                self.fields::<QualifiedAxisId>(path, size, &[]);
+                if self.num_errors() == num_errors{
+                    self.check::<AxisSizeReference>(path, size);
+                }
+            },
+            _ => { self.check::<AnyAxisSize>(path, size); },
+        }
+    }
+
+    fn weights(&mut self, path: &str, weights: &Value){
+        let Value::Object(object) = weights else {
+            return self.error(path, "Expected a mapping of weights formats to weights descriptions")
+        };
+        let num_errors = self.num_errors();
+        let formats = ["keras_hdf5", "onnx", "pytorch_state_dict", "tensorflow_js", "tensorflow_saved_model_bundle", "torchscript"];
+        self.fields::<MaybeSomeWeightsDescr>(path, weights, &formats);
+        for format in formats{
+            let Some(entry) = get(object, format) else {
+                continue
+            };
+            let entry_path = join(path, format);
+            match format{
+                "keras_hdf5" => self.weights_entry::<KerasHdf5WeightsDescr>(&entry_path, entry),
+                "onnx" => self.weights_entry::<OnnxWeightsDescr>(&entry_path, entry),
+                "pytorch_state_dict" => self.weights_entry::<PytorchStateDictWeightsDescr>(&entry_path, entry),
+                "tensorflow_js" => self.weights_entry::<TensorflowJsWeightsDescr>(&entry_path, entry),
+                "tensorflow_saved_model_bundle" => self.weights_entry::<TensorflowSavedModelBundleWeightsDescr>(&entry_path, entry),
+                _ => self.weights_entry::<TorchscriptWeightsDescr>(&entry_path, entry),
+            }
+        }
+        if self.num_errors() == num_errors{
+            self.check::<WeightsDescr>(path, weights);
+        }
+    }
+
+    fn weights_entry<T: DeserializeOwned>(&mut self, path: &str, entry: &Value){
+        let num_errors = self.num_errors();
+        self.fields::<WeightsDescrBase>(path, entry, &[]);
+        if self.num_errors() == num_errors{
+            self.fields::<T>(path, entry, &[]);
+        }
+        if self.num_errors() == num_errors && entry.get("sha256").is_none_or(|sha256| sha256.is_null()){
+            self.warning(join(path, "sha256"), "No sha256 is given, so the contents of the weights file cannot be verified");
+        }
+    }
+}
+
+#[test]
+fn test_validation_collects_all_issues(){
+    let raw = r#"
+format_version: 0.5.4
+type: model
+name: Problematic Model
+authors: [{name: Jane Doe}, {affiliation: Nowhere}]
+cite: [{text: Some paper}]
+documentation: README.md
+license: MIT
+inputs:
+  - id: raw
+    axes:
+      - {type: batch}
+      - {type: space, id: x, size: {min: 0, step: 16}}
+      - {type: space, id: y, size: 64, unit: lightyear}
+    test_tensor: {source: test_input.npy}
+  - id: Bad Id!
+    axes: [{type: wormhole}]
+outputs:
+  - id: mask
+    axes: [{type: batch}, {type: space, id: x, size: 64}]
+    test_tensor: {source: test_output.npy}
+weights:
+  onnx: {source: weights.onnx, opset_version: latest}
+"#;
+    let report = ValidationReport::validate_yaml(raw.as_bytes());
+    let mut errors: Vec<&str> = report.errors().map(|issue| issue.path.as_str()).collect();
+    errors.sort();
+    assert_eq!(
+        errors,
+        [
+            "authors[1].name",
+            "cite[0]",
+            "description",
+            "inputs[0].axes[1].size.min",
+            "inputs[0].axes[2].unit",
+            "inputs[1].axes[0].type",
+            "inputs[1].id",
+            "inputs[1].test_tensor",
+            "weights.onnx.opset_version",
+        ]
+    );
+    assert!(!report.is_valid());
+
+    let json = report.to_json();
+    assert_eq!(json["issues"][0]["severity"], "error");
+}
+
+#[test]
+fn test_validating_other_format_versions(){
+    let legacy = |format_version: &str, axes: &str| format!(r#"
+format_version: {format_version}
+type: model
+name: Legacy Model
+description: A model from the 0.4 days
+authors: [{{name: Jane Doe}}]
+cite: [{{text: Some paper, doi: 10.1000/xyz123}}]
+documentation: README.md
+license: MIT
+timestamp: 2022-03-01T10:00:00
+packaged_by: [{{name: Packaging Bot}}]
+test_inputs: [test_input.npy]
+test_outputs: [test_output.npy]
+inputs:
+  - {{name: raw, axes: {axes}, data_type: float32, shape: [1, 64]}}
+outputs:
+  - {{name: mask, axes: {axes}, data_type: float32, shape: {{reference_tensor: raw, scale: [1, 1], offset: [0, 0]}}}}
+weights:
+  onnx: {{source: weights.onnx, opset_version: 15}}
+"#);
+    let issue_paths = |report: &ValidationReport, severity: Severity| -> Vec<String> {
+        let mut paths: Vec<String> = report.issues.iter()
+            .filter(|issue| issue.severity == severity)
+            .map(|issue| issue.path.clone())
+            .collect();
+        paths.sort();
+        paths
+    };
+
+    let report = ValidationReport::validate_yaml(legacy("0.4.10", "bx").as_bytes());
+    assert!(report.is_valid(), "{report}");
+    assert_eq!(issue_paths(&report, Severity::Warning), ["format_version", "packaged_by"]);
+
+    let report = ValidationReport::validate_yaml(legacy("0.4.10", "bq").as_bytes());
+    assert_eq!(issue_paths(&report, Severity::Error), ["inputs[0].axes"]);
+
+    let report = ValidationReport::validate_yaml(legacy("0.3.6", "bx").as_bytes());
+    assert_eq!(issue_paths(&report, Severity::Error), ["format_version"]);
+
+    let newer = r#"
+format_version: 0.5.9
+type: model
+name: Newer Model
+description: A model from a newer patch release
+authors: [{name: Jane Doe}]
+cite: [{text: Some paper, doi: 10.1000/xyz123}]
+documentation: README.md
+license: MIT
+inputs:
+  - id: raw
+    axes: [{type: batch}, {type: space, id: x, size: 64}]
+    test_tensor: {source: test_input.npy}
+outputs:
+  - id: mask
+    axes: [{type: batch}, {type: space, id: x, size: 64}]
+    test_tensor: {source: test_output.npy}
+    some_future_field: 42
+weights:
+  onnx: {source: weights.onnx, sha256: 0000000000000000000000000000000000000000000000000000000000000000, opset_version: 15}
+"#;
+    let report = ValidationReport::validate_yaml(newer.as_bytes());
+    assert!(report.is_valid(), "{report}");
+    assert_eq!(issue_paths(&report, Severity::Warning), ["format_version", "outputs[0].some_future_field"]);
+}
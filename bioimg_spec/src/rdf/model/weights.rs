@@ -2,7 +2,7 @@ use std::ops::Deref;
 
 use aspartial::AsPartial;
 
-use crate::rdf::{author::Author2, file_description::{FileDescription, Sha256}, file_reference::EnvironmentFile, FileReference, Identifier, Version};
+use crate::rdf::{author::Author2, file_description::{FileDescription, Sha256}, file_reference::EnvironmentFile, BoundedString, FileReference, Identifier, Version};
 
 #[derive(thiserror::Error, Debug, Clone)]
 pub enum ModelWeightsParsingError{
@@ -65,7 +65,8 @@ impl TryFrom<MaybeSomeWeightsDescr> for WeightsDescr{
     }
 }
 
-#[derive(serde::Serialize, serde::Deserialize, PartialEq, Eq, Clone, Debug, strum::Display)]
+#[derive(serde::Serialize, serde::Deserialize, PartialEq, Eq, Hash, Copy, Clone, Debug, Default)]
+#[derive(strum::Display, strum::EnumString, strum::VariantArray)]
 pub enum WeightsFormat{
     #[serde(rename = "keras_hdf5")]
     #[strum(serialize = "keras_hdf5")]
@@ -73,6 +74,7 @@ pub enum WeightsFormat{
     #[serde(rename="onnx")]
     #[strum(serialize="onnx")]
     Onnx,
+    #[default]
     #[serde(rename="pytorch_state_dict")]
     #[strum(serialize="pytorch_state_dict")]
     PytorchStateDict,
@@ -121,6 +123,9 @@ pub struct WeightsDescrBase{
     #[serde(default)]
     pub authors: Option<Vec<Author2>>,
     pub parent: Option<WeightsFormat>,
+    /// A comment about this weights entry, e.g. how these weights were converted. Introduced in 0.5.4
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comment: Option<BoundedString<0, 512>>,
 }
 
 
@@ -40,12 +40,21 @@ impl Version{
             ..Default::default()
         })
     }
+    pub fn version_0_6_0() -> Version{
+        Self::major_minor_patch(0, 6, 0)
+    }
+    pub fn version_0_5_4() -> Version{
+        Self::major_minor_patch(0, 5, 4)
+    }
     pub fn version_0_5_3() -> Version{
         Self::major_minor_patch(0, 5, 3)
     }
     pub fn version_0_5_0() -> Version{
         Self::major_minor_patch(0, 5, 0)
     }
+    pub fn version_0_4_0() -> Version{
+        Self::major_minor_patch(0, 4, 0)
+    }
 }
 
 impl TryFrom<String> for Version{
@@ -70,14 +79,22 @@ pub struct Version_0_5_x(Version);
 
 impl Version_0_5_x{
     pub fn new() -> Self{
-        Self(Version::version_0_5_3())
+        Self(Self::latest_supported_version())
     }
     pub fn latest_supported_version() -> Version{
-        Version::version_0_5_3()
+        Version::version_0_5_4()
     }
     pub fn earliest_supported_version() -> Version{
         Version::version_0_5_0()
     }
+    /// Whether this is a 0.5 patch release published after the latest one whose fields are known to these types.
+    /// Such models can still be parsed, but any fields introduced after [Self::latest_supported_version] are ignored
+    pub fn is_newer_than_supported(&self) -> bool{
+        self.0 > Self::latest_supported_version()
+    }
+    pub fn version(&self) -> &Version{
+        &self.0
+    }
 }
 
 impl TryFrom<Version> for Version_0_5_x {
@@ -86,8 +103,26 @@ impl TryFrom<Version> for Version_0_5_x {
         if  version < Version::version_0_5_0() {
             return Err(VersionParsingError::TooLow { version })
         }
-        if  version > Version::version_0_5_3() {
-            return Err(VersionParsingError::TooHigh { version, max_supported: Version::version_0_5_3() })
+        if  version >= Version::version_0_6_0() {
+            return Err(VersionParsingError::TooHigh { version, max_supported: Self::latest_supported_version() })
+        }
+        Ok(Self(version))
+    }
+}
+
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, PartialEq, Eq)]
+#[serde(try_from="Version")]
+pub struct Version_0_4_x(Version);
+
+impl TryFrom<Version> for Version_0_4_x {
+    type Error = VersionParsingError;
+    fn try_from(version: Version) -> Result<Self, Self::Error> {
+        if  version < Version::version_0_4_0() {
+            return Err(VersionParsingError::TooLow { version })
+        }
+        if  version >= Version::version_0_5_0() {
+            return Err(VersionParsingError::TooHigh { version, max_supported: Version::major_minor_patch(0, 4, 10) })
         }
         Ok(Self(version))
     }